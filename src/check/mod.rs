@@ -0,0 +1,349 @@
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use super::error::{Error, ErrorKind, Result};
+use super::{sanitize, scrape, Config, Everything};
+
+// tests as sub module
+#[cfg(test)] // only add when running tests
+mod test;
+
+/// Status is the result of a check, the value is the plugin exit code
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum Status {
+    Ok = 0,
+    Warning = 1,
+    Critical = 2,
+    Unknown = 3,
+}
+
+impl Status {
+    /// returns the status as used in the plugin output
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Status::Ok => "OK",
+            Status::Warning => "WARNING",
+            Status::Critical => "CRITICAL",
+            Status::Unknown => "UNKNOWN",
+        }
+    }
+
+    /// returns the exit code of the plugin
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+
+    /// maps the error of a failed scrape to a status
+    ///
+    /// A tracker which cannot be reached or does not answer properly is critical,
+    /// everything else, like an invalid address or an unknown response, is unknown.
+    fn from_error(kind: &ErrorKind) -> Self {
+        match kind {
+            ErrorKind::Unreachable(_) | ErrorKind::HttpStatus(_) => Status::Critical,
+            ErrorKind::IoTimedOut
+            | ErrorKind::IoConnectionRefused
+            | ErrorKind::IoConnectionReset
+            | ErrorKind::IoConnectionAborted
+            | ErrorKind::IoNotConnected
+            | ErrorKind::IoBrokenPipe
+            | ErrorKind::IoUnexpectedEof => Status::Critical,
+            _ => Status::Unknown,
+        }
+    }
+}
+
+/// Range is a threshold in the nagios range format `[@][start:][end]`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Range {
+    text: String,
+    start: f64,
+    end: f64,
+    inside: bool,
+}
+
+impl Range {
+    /// returns true if `value` has to raise an alert
+    pub fn alert(&self, value: f64) -> bool {
+        let within = value >= self.start && value <= self.end;
+        within == self.inside
+    }
+}
+
+impl FromStr for Range {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let invalid = || Error::new(ErrorKind::NotParsable(format!("invalid range {}", text)));
+        let (inside, range) = match text.strip_prefix('@') {
+            Some(range) => (true, range),
+            None => (false, text),
+        };
+        let (start, end) = match range.find(':') {
+            Some(split) => (&range[..split], &range[split + 1..]),
+            None => ("0", range),
+        };
+
+        let start = match start {
+            "~" => f64::NEG_INFINITY,
+            "" => 0.0,
+            start => start.parse().map_err(|_| invalid())?,
+        };
+        let end = match end {
+            "" => f64::INFINITY,
+            end => end.parse().map_err(|_| invalid())?,
+        };
+        if start > end {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            text: text.to_string(),
+            start,
+            end,
+            inside,
+        })
+    }
+}
+
+/// Threshold holds the optional warning and critical range of a field
+#[derive(Clone, Debug, Default)]
+pub struct Threshold {
+    pub warning: Option<Range>,
+    pub critical: Option<Range>,
+}
+
+impl Threshold {
+    /// evaluates `value` against the ranges
+    pub fn status(&self, value: f64) -> Status {
+        if self.critical.as_ref().is_some_and(|range| range.alert(value)) {
+            Status::Critical
+        } else if self.warning.as_ref().is_some_and(|range| range.alert(value)) {
+            Status::Warning
+        } else {
+            Status::Ok
+        }
+    }
+}
+
+/// Field is a value the thresholds can be set for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Field {
+    Peers,
+    Seeds,
+    Torrents,
+    Uptime,
+    /// increase of mutex stalls since the last check
+    MutexStall,
+    /// udp connect id mismatches per second since the last check
+    UdpMissmatch,
+}
+
+impl Field {
+    /// all fields in the order of the perfdata
+    const ALL: [Field; 6] = [
+        Field::Peers,
+        Field::Seeds,
+        Field::Torrents,
+        Field::Uptime,
+        Field::MutexStall,
+        Field::UdpMissmatch,
+    ];
+
+    /// returns the label of the field
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Field::Peers => "peers",
+            Field::Seeds => "seeds",
+            Field::Torrents => "torrents",
+            Field::Uptime => "uptime",
+            Field::MutexStall => "mutex_stall",
+            Field::UdpMissmatch => "udp_missmatch",
+        }
+    }
+
+    /// returns the unit of measurement for the perfdata
+    fn unit(self) -> &'static str {
+        match self {
+            Field::Uptime => "s",
+            // mutex_stall is the growth since the last run, "c" would make it a counter
+            _ => "",
+        }
+    }
+}
+
+impl FromStr for Field {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        match Field::ALL.iter().find(|field| field.as_str() == name) {
+            Some(field) => Ok(*field),
+            None => Err(Error::new_field_not_exists(name.to_string())),
+        }
+    }
+}
+
+/// State are the values of the last check needed for growth and rates
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct State {
+    uptime: usize,
+    mutex_stall: usize,
+    udp_missmatch: usize,
+}
+
+impl State {
+    fn from(data: &Everything) -> Self {
+        Self {
+            uptime: data.uptime,
+            mutex_stall: data.mutex_stall,
+            udp_missmatch: data.connections.udp_missmatch,
+        }
+    }
+
+    fn parse(content: &str) -> Option<Self> {
+        let mut values = content.split_whitespace().map(|value| value.parse::<usize>().ok());
+        Some(Self {
+            uptime: values.next()??,
+            mutex_stall: values.next()??,
+            udp_missmatch: values.next()??,
+        })
+    }
+
+    fn serialize(self) -> String {
+        format!("{} {} {}\n", self.uptime, self.mutex_stall, self.udp_missmatch)
+    }
+}
+
+/// Check evaluates thresholds against one scrape of the tracker
+#[derive(Clone, Debug, Default)]
+pub struct Check {
+    thresholds: Vec<(Field, Threshold)>,
+
+    /// file to keep the values of the last check in, defaults to the temp dir
+    pub state: Option<PathBuf>,
+}
+
+impl Check {
+    /// creates a new Check without thresholds
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// returns the threshold of a field to set ranges
+    pub fn threshold(&mut self, field: Field) -> &mut Threshold {
+        let index = match self.thresholds.iter().position(|(f, _)| *f == field) {
+            Some(index) => index,
+            None => {
+                self.thresholds.push((field, Threshold::default()));
+                self.thresholds.len() - 1
+            }
+        };
+        &mut self.thresholds[index].1
+    }
+
+    /// scrapes the tracker and returns the status and plugin output
+    pub fn run(&self, conf: &Config) -> (Status, String) {
+        let data = match scrape(&conf.upstream()) {
+            Ok(data) => data,
+            Err(err) => {
+                let status = Status::from_error(&err.kind());
+                return (
+                    status,
+                    format!("OPENTRACKER {} - {}: {}", status.as_str(), conf.url, err.kind().error_string()),
+                );
+            }
+        };
+
+        let path = self.state.clone().unwrap_or_else(|| {
+            std::env::temp_dir().join(format!("opentracker_exporter_check_{}.state", sanitize(&conf.name)))
+        });
+        let last = fs::read_to_string(&path).ok().and_then(|content| State::parse(&content));
+        // the state is only needed for the next run, failing to keep it just disables the rates
+        let _ = fs::write(&path, State::from(&data).serialize());
+
+        self.evaluate(&data, last)
+    }
+
+    /// evaluates the thresholds against `data`, `last` is the state of the previous check
+    fn evaluate(&self, data: &Everything, last: Option<State>) -> (Status, String) {
+        let mut status = Status::Ok;
+        let mut problems = Vec::new();
+        let mut perfdata = Vec::new();
+
+        for field in Field::ALL.iter() {
+            let value = match value(*field, data, last) {
+                Some(value) => value,
+                None => continue,
+            };
+            let threshold = self
+                .thresholds
+                .iter()
+                .find(|(f, _)| f == field)
+                .map(|(_, threshold)| threshold.clone())
+                .unwrap_or_default();
+
+            let field_status = threshold.status(value);
+            if field_status != Status::Ok {
+                problems.push(format!("{} {} is {}", field.as_str(), value, field_status.as_str()));
+            }
+            if field_status > status {
+                status = field_status;
+            }
+
+            let range = |range: &Option<Range>| range.as_ref().map_or(String::new(), |range| range.text.clone());
+            perfdata.push(format!(
+                "{}={}{};{};{};0;",
+                field.as_str(),
+                value,
+                field.unit(),
+                range(&threshold.warning),
+                range(&threshold.critical)
+            ));
+        }
+
+        let summary = if problems.is_empty() {
+            format!(
+                "{} peers, {} seeds, {} torrents, up {}s",
+                data.peers, data.seeds, data.torrents.mutex, data.uptime
+            )
+        } else {
+            problems.join(", ")
+        };
+        (
+            status,
+            format!("OPENTRACKER {} - {} | {}", status.as_str(), summary, perfdata.join(" ")),
+        )
+    }
+}
+
+/// returns the value of a field, growth and rates are only known with a previous state
+fn value(field: Field, data: &Everything, last: Option<State>) -> Option<f64> {
+    // a smaller uptime means the tracker restarted, so the counters start at zero
+    let last = last.map(|last| {
+        if last.uptime > data.uptime {
+            State {
+                uptime: 0,
+                mutex_stall: 0,
+                udp_missmatch: 0,
+            }
+        } else {
+            last
+        }
+    });
+
+    match field {
+        Field::Peers => Some(data.peers as f64),
+        Field::Seeds => Some(data.seeds as f64),
+        Field::Torrents => Some(data.torrents.mutex as f64),
+        Field::Uptime => Some(data.uptime as f64),
+        Field::MutexStall => last.map(|last| data.mutex_stall.saturating_sub(last.mutex_stall) as f64),
+        Field::UdpMissmatch => last.and_then(|last| {
+            let elapsed = data.uptime - last.uptime;
+            if elapsed == 0 {
+                return None;
+            }
+            let missmatch = data.connections.udp_missmatch.saturating_sub(last.udp_missmatch) as f64;
+            Some((missmatch / elapsed as f64 * 1000.0).round() / 1000.0)
+        }),
+    }
+}
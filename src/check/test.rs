@@ -0,0 +1,118 @@
+//! test file to test the threshold evaluation of the check
+
+use super::{Check, Field, Range, State, Status};
+use crate::error::ErrorKind;
+use crate::Everything;
+
+fn data() -> Everything {
+    let mut data = Everything::new();
+    data.uptime = 3600;
+    data.peers = 300;
+    data.seeds = 120;
+    data.torrents.mutex = 42;
+    data.mutex_stall = 5;
+    data.connections.udp_missmatch = 700;
+    data
+}
+
+#[test]
+fn range() {
+    let range: Range = "10".parse().unwrap();
+    assert!(range.alert(-1.0));
+    assert!(!range.alert(0.0));
+    assert!(!range.alert(10.0));
+    assert!(range.alert(11.0));
+
+    let range: Range = "10:".parse().unwrap();
+    assert!(range.alert(9.0));
+    assert!(!range.alert(1e9));
+
+    let range: Range = "~:10".parse().unwrap();
+    assert!(!range.alert(-1e9));
+    assert!(range.alert(11.0));
+
+    let range: Range = "@10:20".parse().unwrap();
+    assert!(!range.alert(9.0));
+    assert!(range.alert(15.0));
+    assert!(!range.alert(21.0));
+
+    assert!("20:10".parse::<Range>().is_err());
+    assert!("ten".parse::<Range>().is_err());
+}
+
+#[test]
+fn status_from_error() {
+    assert_eq!(Status::from_error(&ErrorKind::IoConnectionRefused), Status::Critical);
+    assert_eq!(Status::from_error(&ErrorKind::IoTimedOut), Status::Critical);
+    assert_eq!(Status::from_error(&ErrorKind::HttpStatus(404)), Status::Critical);
+    assert_eq!(
+        Status::from_error(&ErrorKind::Unreachable(String::from("tracker.invalid:6969: no address"))),
+        Status::Critical
+    );
+    assert_eq!(Status::from_error(&ErrorKind::IoInvalidInput), Status::Unknown);
+    assert_eq!(
+        Status::from_error(&ErrorKind::NotParsable(String::from("html"))),
+        Status::Unknown
+    );
+}
+
+#[test]
+fn ok() {
+    let (status, output) = Check::new().evaluate(&data(), None);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(
+        output,
+        "OPENTRACKER OK - 300 peers, 120 seeds, 42 torrents, up 3600s | peers=300;;;0; seeds=120;;;0; torrents=42;;;0; uptime=3600s;;;0;"
+    );
+}
+
+#[test]
+fn thresholds() {
+    let mut check = Check::new();
+    check.threshold(Field::Peers).warning = Some("500:".parse().unwrap());
+    check.threshold(Field::Peers).critical = Some("100:".parse().unwrap());
+    check.threshold(Field::Uptime).critical = Some("600:".parse().unwrap());
+
+    let (status, output) = check.evaluate(&data(), None);
+    assert_eq!(status, Status::Warning);
+    assert!(output.starts_with("OPENTRACKER WARNING - peers 300 is WARNING | peers=300;500:;100:;0; "));
+
+    let mut data = data();
+    data.uptime = 60;
+    let (status, output) = check.evaluate(&data, None);
+    assert_eq!(status, Status::Critical);
+    assert!(output.contains("peers 300 is WARNING, uptime 60 is CRITICAL |"));
+}
+
+#[test]
+fn growth_and_rate() {
+    let mut check = Check::new();
+    check.threshold(Field::MutexStall).critical = Some("2".parse().unwrap());
+    let last = State {
+        uptime: 3500,
+        mutex_stall: 1,
+        udp_missmatch: 500,
+    };
+
+    let (status, output) = check.evaluate(&data(), Some(last));
+    assert_eq!(status, Status::Critical);
+    assert!(output.contains(" mutex_stall=4;;2;0; udp_missmatch=2;;;0;"));
+}
+
+#[test]
+fn restart() {
+    let last = State {
+        uptime: 7200,
+        mutex_stall: 10,
+        udp_missmatch: 1000,
+    };
+    let (_, output) = Check::new().evaluate(&data(), Some(last));
+    assert!(output.contains(" mutex_stall=5;;;0; udp_missmatch=0.194;;;0;"));
+}
+
+#[test]
+fn state() {
+    let state = State::from(&data());
+    assert_eq!(State::parse(&state.serialize()), Some(state));
+    assert_eq!(State::parse("garbage"), None);
+}
@@ -0,0 +1,187 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use super::error::Result;
+use super::{scrape, Config, Everything};
+
+// tests as sub module
+#[cfg(test)] // only add when running tests
+pub(crate) mod test;
+
+/// longest time to wait between two failed flushes
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// time between checks whether the collection was stopped
+const TICK: Duration = Duration::from_millis(100);
+
+/// Scrape holds the stats of one tracker at one point in time
+pub struct Scrape {
+    /// human readable name of the tracker
+    pub name: String,
+
+    /// time the scrape finished
+    pub time: SystemTime,
+
+    /// parsed stats of the tracker
+    pub(crate) data: Everything,
+}
+
+/// Sink is a destination the background collection sends scrapes to
+///
+/// Every sink runs in its own thread, so a slow sink does not delay the others.
+pub trait Sink: Send {
+    /// returns the name of the sink for log messages
+    fn name(&self) -> String;
+
+    /// queues a new scrape
+    fn collect(&mut self, scrape: &Scrape);
+
+    /// sends everything queued, retried with a backoff on errors
+    fn flush(&mut self) -> Result<()>;
+}
+
+/// exponential backoff between failed flushes
+struct Backoff {
+    current: Duration,
+    max: Duration,
+}
+
+impl Backoff {
+    fn new(max: Duration) -> Self {
+        Self {
+            current: Duration::from_secs(1),
+            max,
+        }
+    }
+
+    /// returns the time to wait and doubles it for the next failure
+    fn next(&mut self) -> Duration {
+        let ret = self.current;
+        self.current = std::cmp::min(self.current * 2, self.max);
+        ret
+    }
+
+    /// resets the backoff after a successful flush
+    fn reset(&mut self) {
+        self.current = Duration::from_secs(1);
+    }
+}
+
+/// Collector is a running background collection
+pub struct Collector {
+    stopped: Arc<AtomicBool>,
+}
+
+impl Collector {
+    /// stops scraping, the sinks flush what they have queued and finish
+    ///
+    /// Does not wait for the threads, a scrape which is running completes first.
+    pub fn stop(self) {
+        self.stopped.store(true, Ordering::SeqCst);
+    }
+}
+
+/// starts scraping the tracker every `conf.interval` seconds and feeding the sinks
+///
+/// The collection runs until `Collector::stop` is called.
+pub fn start(conf: &Config, sinks: Vec<Box<dyn Sink>>) -> Collector {
+    let mut senders = Vec::with_capacity(sinks.len());
+    let verbose = conf.verbose;
+
+    for sink in sinks {
+        if verbose >= 1 {
+            println!("Debug1: sending metrics to {}", sink.name());
+        }
+        let (sender, receiver) = mpsc::channel();
+        senders.push(sender);
+        thread::spawn(move || feed(sink, receiver, verbose));
+    }
+
+    let upstream = conf.upstream();
+    let name = conf.name.clone();
+    let interval = Duration::from_secs(conf.interval);
+    let stopped = Arc::new(AtomicBool::new(false));
+    let stop = Arc::clone(&stopped);
+    // dropping the senders on return finishes the sinks
+    thread::spawn(move || loop {
+        let started = Instant::now();
+
+        match scrape(&upstream) {
+            Ok(data) => {
+                let scrape = Arc::new(Scrape {
+                    name: name.clone(),
+                    time: SystemTime::now(),
+                    data,
+                });
+                for sender in &senders {
+                    let _ = sender.send(Arc::clone(&scrape));
+                }
+            }
+            Err(err) => eprintln!("failed to scrape {}: {}", upstream.url, err),
+        }
+
+        while started.elapsed() < interval && !stop.load(Ordering::SeqCst) {
+            thread::sleep(std::cmp::min(TICK, interval.saturating_sub(started.elapsed())));
+        }
+        if stop.load(Ordering::SeqCst) {
+            return;
+        }
+    });
+
+    Collector { stopped }
+}
+
+/// hands scrapes from `receiver` to `sink` and retries failed flushes with a backoff
+fn feed(mut sink: Box<dyn Sink>, receiver: mpsc::Receiver<Arc<Scrape>>, verbose: u8) {
+    let mut backoff = Backoff::new(MAX_BACKOFF);
+    let mut retry = None;
+    loop {
+        match retry {
+            Some(wait) => {
+                thread::sleep(wait);
+                // a stopped collector gets one last flush, the sink must not outlive it
+                match receiver.try_recv() {
+                    Ok(scrape) => sink.collect(&scrape),
+                    Err(TryRecvError::Empty) => {}
+                    Err(TryRecvError::Disconnected) => {
+                        if let Err(err) = sink.flush() {
+                            eprintln!("failed to send metrics to {}: {}, giving up", sink.name(), err);
+                        }
+                        return;
+                    }
+                }
+            }
+            None => match receiver.recv() {
+                Ok(scrape) => sink.collect(&scrape),
+                Err(_) => return,
+            },
+        }
+        // scrapes which arrived while waiting
+        for scrape in receiver.try_iter() {
+            sink.collect(&scrape);
+        }
+
+        match sink.flush() {
+            Ok(()) => {
+                if verbose >= 2 {
+                    println!("Debug2: sent metrics to {}", sink.name());
+                }
+                backoff.reset();
+                retry = None;
+            }
+            Err(err) => {
+                let wait = backoff.next();
+                eprintln!(
+                    "failed to send metrics to {}: {}, retrying in {}s",
+                    sink.name(),
+                    err,
+                    wait.as_secs()
+                );
+                retry = Some(wait);
+            }
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! test file for the background collection
+
+use super::{feed, start, Backoff, Scrape, Sink};
+use crate::error::{Error, ErrorKind, Result};
+use crate::Everything;
+use std::io::prelude::*;
+use std::net::TcpListener;
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, UNIX_EPOCH};
+
+/// returns a scrape of `name` taken `secs` after the epoch, `fill` sets the values
+pub(crate) fn scrape(name: &str, secs: u64, fill: impl FnOnce(&mut Everything)) -> Scrape {
+    let mut data = Everything::new();
+    fill(&mut data);
+    Scrape {
+        name: name.to_string(),
+        time: UNIX_EPOCH + Duration::from_secs(secs),
+        data,
+    }
+}
+
+#[test]
+fn backoff() {
+    let mut backoff = Backoff::new(Duration::from_secs(5));
+    assert_eq!(backoff.next(), Duration::from_secs(1));
+    assert_eq!(backoff.next(), Duration::from_secs(2));
+    assert_eq!(backoff.next(), Duration::from_secs(4));
+    assert_eq!(backoff.next(), Duration::from_secs(5));
+    assert_eq!(backoff.next(), Duration::from_secs(5));
+    backoff.reset();
+    assert_eq!(backoff.next(), Duration::from_secs(1));
+}
+
+/// sink reporting every flush, failing the first one
+struct Flaky {
+    failed: bool,
+    queued: Vec<String>,
+    flushed: mpsc::Sender<Vec<String>>,
+}
+
+impl Sink for Flaky {
+    fn name(&self) -> String {
+        String::from("flaky")
+    }
+
+    fn collect(&mut self, scrape: &Scrape) {
+        self.queued.push(scrape.name.clone());
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if !self.failed {
+            self.failed = true;
+            return Err(Error::new(ErrorKind::IoConnectionRefused));
+        }
+        self.flushed.send(self.queued.drain(..).collect()).unwrap();
+        Ok(())
+    }
+}
+
+#[test]
+fn retries_failed_flush() {
+    let (flushed, result) = mpsc::channel();
+    let (sender, receiver) = mpsc::channel();
+    let sink = Box::new(Flaky {
+        failed: false,
+        queued: Vec::new(),
+        flushed,
+    });
+    std::thread::spawn(move || feed(sink, receiver, 0));
+
+    sender
+        .send(Arc::new(scrape("tracker", 1, |_| {})))
+        .unwrap();
+
+    let flushed = result.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(flushed, vec![String::from("tracker")]);
+}
+
+/// sink reporting when it is dropped
+struct Dropped(mpsc::Sender<()>);
+
+impl Sink for Dropped {
+    fn name(&self) -> String {
+        String::from("dropped")
+    }
+
+    fn collect(&mut self, _scrape: &Scrape) {}
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for Dropped {
+    fn drop(&mut self) {
+        let _ = self.0.send(());
+    }
+}
+
+#[test]
+fn stop_finishes_sinks() {
+    let mut conf = crate::Config::new();
+    conf.url = String::from("127.0.0.1:1");
+    conf.interval = 60;
+    let (dropped, result) = mpsc::channel();
+    let collector = start(&conf, vec![Box::new(Dropped(dropped))]);
+
+    std::thread::sleep(Duration::from_millis(200));
+    assert!(result.try_recv().is_err());
+    collector.stop();
+    result.recv_timeout(Duration::from_secs(5)).unwrap();
+}
+
+/// sink failing every flush, reporting its flushes and when it is dropped
+struct Down(mpsc::Sender<&'static str>);
+
+impl Sink for Down {
+    fn name(&self) -> String {
+        String::from("down")
+    }
+
+    fn collect(&mut self, _scrape: &Scrape) {}
+
+    fn flush(&mut self) -> Result<()> {
+        let _ = self.0.send("flush");
+        Err(Error::new(ErrorKind::IoConnectionRefused))
+    }
+}
+
+impl Drop for Down {
+    fn drop(&mut self) {
+        let _ = self.0.send("drop");
+    }
+}
+
+#[test]
+fn stop_finishes_failing_sinks() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut conf = crate::Config::new();
+    conf.url = listener.local_addr().unwrap().to_string();
+    conf.interval = 60;
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let _ = stream.read(&mut [0; 512]).unwrap();
+        let body = crate::test::EVERYTHING;
+        write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+    });
+    let (events, result) = mpsc::channel();
+    let collector = start(&conf, vec![Box::new(Down(events))]);
+
+    // the sink is retrying with a backoff when the collector stops
+    assert_eq!(result.recv_timeout(Duration::from_secs(5)).unwrap(), "flush");
+    collector.stop();
+    assert_eq!(result.recv_timeout(Duration::from_secs(5)).unwrap(), "flush");
+    assert_eq!(result.recv_timeout(Duration::from_secs(5)).unwrap(), "drop");
+}
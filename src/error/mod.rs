@@ -64,7 +64,7 @@ impl Error {
 impl fmt::Display for Error {
     /// standart formater for print! macro
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Error of Kind {}", self.my_kind.to_string()) // TODO: create a more readable output
+        write!(f, "Error of Kind {}", self.my_kind) // TODO: create a more readable output
     }
 }
 
@@ -72,7 +72,7 @@ impl fmt::Display for Error {
 impl fmt::Debug for Error {
     /// formater for `Debug` in print! macro
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "(Error of Kind {})", self.my_kind.to_string())
+        write!(f, "(Error of Kind {})", self.my_kind)
     }
 }
 
@@ -197,6 +197,15 @@ pub enum ErrorKind {
     /// contains true if it wasn't a terminate instruction
     PoolSendError(bool),
 
+    /// Pool Full is returned when the queue of the ThreadPool is full
+    PoolFull,
+
+    /// Job Panicked is returned by a JobHandle if the job panicked, holds the panic message
+    JobPanicked(String),
+
+    /// Job Timed Out is returned by a JobHandle if the job did not finish in time
+    JobTimedOut,
+
     /// SendError raised when mpsc encounters a problem
     /// hold the data send as string
     SendError(String),
@@ -204,6 +213,13 @@ pub enum ErrorKind {
     /// ReceiveError reaise when mpsc cannot receive any data
     ReceiveError,
 
+    /// Http Status error, raised when a http server answers with a non 2xx status
+    HttpStatus(u16),
+
+    /// Unreachable error, raised when the address of a server cannot be resolved or connected to
+    /// holds the address and the reason
+    Unreachable(String),
+
     /// Other error, used for string to error conversion
     Other(String),
 
@@ -239,13 +255,18 @@ impl ErrorKind {
             ErrorKind::NoVersionSupplied => String::from("NoVersionSupplied"),
             ErrorKind::VersionNotParsable(data) => format!("VersionNotParsable({})", data),
             ErrorKind::PoolToSmall => String::from("PoolToSmall"),
+            ErrorKind::PoolFull => String::from("PoolFull"),
+            ErrorKind::JobPanicked(message) => format!("JobPanicked({})", message),
+            ErrorKind::JobTimedOut => String::from("JobTimedOut"),
             ErrorKind::PoolSendError(t) => match t {
                 true => String::from("PoolSendError(Job)"),
                 false => String::from("PoolSendError(Terminate)"),
             },
+            ErrorKind::HttpStatus(status) => format!("HttpStatus({})", status),
+            ErrorKind::Unreachable(data) => format!("Unreachable({})", data),
             ErrorKind::Other(data) => format!("Other({})", data),
             ErrorKind::Unknown(data) => format!("Unknown({})", data),
-            _ => format!("Not covered??!!!\n"),
+            _ => String::from("Not covered??!!!\n"),
         }
     }
 
@@ -274,6 +274,36 @@ mod error_kind_error_string {
         );
     }
 
+    #[test]
+    fn pool_full() {
+        let kind = ErrorKind::PoolFull;
+        assert_eq!(kind.error_string(), String::from("PoolFull"));
+    }
+
+    #[test]
+    fn job_panicked() {
+        let kind = ErrorKind::JobPanicked("boom".to_string());
+        assert_eq!(kind.error_string(), String::from("JobPanicked(boom)"));
+    }
+
+    #[test]
+    fn job_timed_out() {
+        let kind = ErrorKind::JobTimedOut;
+        assert_eq!(kind.error_string(), String::from("JobTimedOut"));
+    }
+
+    #[test]
+    fn http_status() {
+        let kind = ErrorKind::HttpStatus(503);
+        assert_eq!(kind.error_string(), String::from("HttpStatus(503)"));
+    }
+
+    #[test]
+    fn unreachable() {
+        let kind = ErrorKind::Unreachable(String::from("tracker:6969: refused"));
+        assert_eq!(kind.error_string(), String::from("Unreachable(tracker:6969: refused)"));
+    }
+
     #[test]
     fn other() {
         let kind = ErrorKind::Other(String::from("test"));
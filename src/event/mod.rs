@@ -0,0 +1,718 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixStream;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant, SystemTime};
+
+use super::error::{Error, ErrorKind};
+use super::threads::ThreadPool;
+use super::{busy, response};
+
+// event serves the prometheus endpoint from a single thread waiting on poll(2)
+//
+// The clients and the fetches from the trackers are handled here: slow clients and slow
+// trackers only cost a file descriptor. The ThreadPool answers complete requests, an answer
+// needing upstream responses hands its fetches to the loop and continues on the ThreadPool
+// once they are done, e.g. to parse them. Running fetches with the same key are shared.
+
+// tests as sub module
+#[cfg(test)] // only add when running tests
+mod test;
+
+/// most connections kept open at once, further clients wait in the backlog
+const MAX_CONNECTIONS: usize = 4096;
+
+/// longest request head read from a client
+const MAX_REQUEST: usize = 8192;
+
+/// time a client has to send its request and to read the response
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// time between checks for a shutdown and expired connections
+const TICK: Duration = Duration::from_millis(100);
+
+mod sys {
+    use std::os::raw::{c_int, c_short};
+
+    pub const POLLIN: c_short = 0x1;
+    pub const POLLOUT: c_short = 0x4;
+
+    pub const AF_INET: c_int = 2;
+    pub const SOCK_STREAM: c_int = 1;
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub const AF_INET6: c_int = 10;
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    pub const AF_INET6: c_int = 30;
+    #[cfg(any(target_os = "freebsd", target_os = "dragonfly"))]
+    pub const AF_INET6: c_int = 28;
+    #[cfg(any(target_os = "openbsd", target_os = "netbsd"))]
+    pub const AF_INET6: c_int = 24;
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub const SOCK_CLOEXEC: c_int = 0o2_000_000;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub const SOCK_CLOEXEC: c_int = 0;
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub const EINPROGRESS: c_int = 115;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub const EINPROGRESS: c_int = 36;
+
+    #[cfg(target_os = "linux")]
+    pub type Nfds = std::os::raw::c_ulong;
+    #[cfg(not(target_os = "linux"))]
+    pub type Nfds = std::os::raw::c_uint;
+
+    #[repr(C)]
+    pub struct PollFd {
+        pub fd: c_int,
+        pub events: c_short,
+        pub revents: c_short,
+    }
+
+    extern "C" {
+        pub fn poll(fds: *mut PollFd, nfds: Nfds, timeout: c_int) -> c_int;
+        pub fn socket(domain: c_int, kind: c_int, protocol: c_int) -> c_int;
+        pub fn connect(fd: c_int, addr: *const u8, len: u32) -> c_int;
+    }
+}
+
+/// waits until one of `fds` is ready or `timeout` passed
+fn poll(fds: &mut [sys::PollFd], timeout: Duration) -> io::Result<()> {
+    let ret = unsafe { sys::poll(fds.as_mut_ptr(), fds.len() as sys::Nfds, timeout.as_millis() as i32) };
+    if ret < 0 {
+        let err = io::Error::last_os_error();
+        // a signal arrived, the caller checks the flags and polls again
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+/// starts connecting to `addr` without waiting for the connection
+///
+/// The stream is writable once connected, `take_error` tells whether connecting failed.
+/// The deadline of the fetch bounds connecting, `timeout` is only used where the layout of
+/// struct sockaddr is not known.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "openbsd",
+    target_os = "netbsd"
+))]
+fn connect(addr: &SocketAddr, _timeout: Duration) -> io::Result<TcpStream> {
+    use std::os::unix::io::FromRawFd;
+
+    // struct sockaddr_in or sockaddr_in6
+    let mut raw = [0u8; 28];
+    let (family, len) = match addr {
+        SocketAddr::V4(_) => (sys::AF_INET, 16),
+        SocketAddr::V6(_) => (sys::AF_INET6, 28),
+    };
+    // the bsds start with the length and have a one byte family
+    if cfg!(any(target_os = "linux", target_os = "android")) {
+        raw[..2].copy_from_slice(&(family as u16).to_ne_bytes());
+    } else {
+        raw[0] = len as u8;
+        raw[1] = family as u8;
+    }
+    raw[2..4].copy_from_slice(&addr.port().to_be_bytes());
+    match addr {
+        SocketAddr::V4(addr) => raw[4..8].copy_from_slice(&addr.ip().octets()),
+        SocketAddr::V6(addr) => {
+            raw[4..8].copy_from_slice(&addr.flowinfo().to_ne_bytes());
+            raw[8..24].copy_from_slice(&addr.ip().octets());
+            raw[24..28].copy_from_slice(&addr.scope_id().to_ne_bytes());
+        }
+    }
+
+    let fd = unsafe { sys::socket(family, sys::SOCK_STREAM | sys::SOCK_CLOEXEC, 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // owned from here on, closed on every error
+    let stream = unsafe { TcpStream::from_raw_fd(fd) };
+    stream.set_nonblocking(true)?;
+    if unsafe { sys::connect(fd, raw.as_ptr(), len) } < 0 {
+        let err = io::Error::last_os_error();
+        if err.raw_os_error() != Some(sys::EINPROGRESS) {
+            return Err(err);
+        }
+    }
+    Ok(stream)
+}
+
+/// connects to `addr` within `timeout`, blocking as the layout of struct sockaddr is not known here
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "openbsd",
+    target_os = "netbsd"
+)))]
+fn connect(addr: &SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
+    let stream = TcpStream::connect_timeout(addr, timeout)?;
+    stream.set_nonblocking(true)?;
+    Ok(stream)
+}
+
+/// Answer is the response to a request, or the upstream responses it needs first
+pub(crate) enum Answer {
+    /// the complete response
+    Done(Vec<u8>),
+    /// fetches to make, the answer continues with their results on the ThreadPool
+    Fetch(Vec<Fetch>, Then),
+}
+
+/// Then continues an answer with the results of its fetches, in the order they were asked for
+pub(crate) type Then = Box<dyn FnOnce(Vec<Fetched>) -> Answer + Send>;
+
+/// Fetch is a request to an upstream server
+pub(crate) struct Fetch {
+    /// running fetches with the same key are shared, it names the server in errors
+    pub key: String,
+
+    /// address of the server
+    pub addr: SocketAddr,
+
+    /// raw request, the server closes the connection after the response
+    pub request: Vec<u8>,
+
+    /// time the server has to accept the connection and to answer
+    pub timeout: Duration,
+}
+
+/// Fetched is the result of a Fetch
+#[derive(Clone, Debug)]
+pub(crate) struct Fetched {
+    /// local time the request was sent at
+    pub sent: SystemTime,
+
+    /// local time the response was complete or the fetch failed
+    pub received: SystemTime,
+
+    /// true if the fetch was made for another answer
+    pub shared: bool,
+
+    /// the raw response
+    pub response: Result<Vec<u8>, ErrorKind>,
+}
+
+impl Fetched {
+    /// returns the result of a fetch which failed before sending anything
+    fn failed(kind: ErrorKind, shared: bool) -> Self {
+        let now = SystemTime::now();
+        Self {
+            sent: now,
+            received: now,
+            shared,
+            response: Err(kind),
+        }
+    }
+}
+
+/// Step is what a fetch waits for
+enum Step {
+    /// the connection to be established
+    Connecting,
+    /// the request to be written, with how much of it was written
+    Writing(usize),
+    /// the server to close the connection, with the response read so far
+    Reading(Vec<u8>),
+}
+
+/// Upstream is a running fetch and the answers waiting for it
+struct Upstream {
+    key: String,
+    stream: TcpStream,
+    request: Vec<u8>,
+    step: Step,
+    sent: SystemTime,
+    deadline: Instant,
+    /// client, index of the fetch in its answer and whether the fetch was made for another answer
+    waiters: Vec<(usize, usize, bool)>,
+}
+
+impl Upstream {
+    /// returns the events the fetch waits for
+    fn events(&self) -> i16 {
+        match self.step {
+            Step::Connecting | Step::Writing(_) => sys::POLLOUT,
+            Step::Reading(_) => sys::POLLIN,
+        }
+    }
+
+    /// connects, writes or reads as much as possible without blocking, returns the result once done
+    fn progress(&mut self) -> Option<Result<Vec<u8>, ErrorKind>> {
+        loop {
+            match &mut self.step {
+                Step::Connecting => {
+                    match self.stream.take_error() {
+                        Ok(None) => {}
+                        Ok(Some(err)) | Err(err) => {
+                            return Some(Err(ErrorKind::Unreachable(format!("{}: {}", self.key, err))))
+                        }
+                    }
+                    self.sent = SystemTime::now();
+                    self.step = Step::Writing(0);
+                }
+                Step::Writing(written) => {
+                    while *written < self.request.len() {
+                        match self.stream.write(&self.request[*written..]) {
+                            Ok(0) => return Some(Err(ErrorKind::IoWriteZero)),
+                            Ok(amount) => *written += amount,
+                            Err(err) if err.kind() == io::ErrorKind::WouldBlock => return None,
+                            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                            Err(err) => return Some(Err(Error::from(err).kind())),
+                        }
+                    }
+                    self.step = Step::Reading(Vec::new());
+                }
+                Step::Reading(response) => {
+                    let mut chunk = [0; 4096];
+                    loop {
+                        match self.stream.read(&mut chunk) {
+                            Ok(0) => return Some(Ok(std::mem::take(response))),
+                            Ok(amount) => response.extend_from_slice(&chunk[..amount]),
+                            Err(err) if err.kind() == io::ErrorKind::WouldBlock => return None,
+                            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                            Err(err) => return Some(Err(Error::from(err).kind())),
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Waiting is an answer waiting for its fetches
+struct Waiting {
+    results: Vec<Option<Fetched>>,
+    then: Then,
+    deadline: Instant,
+}
+
+/// Fetches are the running fetches and the answers waiting for them, by client
+#[derive(Default)]
+struct Fetches {
+    upstreams: HashMap<usize, Upstream>,
+    /// running fetch by key
+    running: HashMap<String, usize>,
+    waiting: HashMap<usize, Waiting>,
+    next: usize,
+}
+
+impl Fetches {
+    /// starts the `fetches` of the answer for `client`, running fetches with the same key are joined
+    fn start(&mut self, client: usize, fetches: Vec<Fetch>, then: Then) {
+        let timeout = fetches.iter().map(|fetch| fetch.timeout).max().unwrap_or_default();
+        let mut results = vec![None; fetches.len()];
+        for (index, fetch) in fetches.into_iter().enumerate() {
+            if let Some(id) = self.running.get(&fetch.key) {
+                if let Some(upstream) = self.upstreams.get_mut(id) {
+                    upstream.waiters.push((client, index, true));
+                    continue;
+                }
+            }
+            match connect(&fetch.addr, fetch.timeout) {
+                Ok(stream) => {
+                    self.running.insert(fetch.key.clone(), self.next);
+                    self.upstreams.insert(
+                        self.next,
+                        Upstream {
+                            key: fetch.key,
+                            stream,
+                            request: fetch.request,
+                            step: Step::Connecting,
+                            sent: SystemTime::now(),
+                            deadline: Instant::now() + fetch.timeout,
+                            waiters: vec![(client, index, false)],
+                        },
+                    );
+                    self.next = self.next.wrapping_add(1);
+                }
+                Err(err) => {
+                    let kind = ErrorKind::Unreachable(format!("{}: {}", fetch.key, err));
+                    results[index] = Some(Fetched::failed(kind, false));
+                }
+            }
+        }
+        self.waiting.insert(
+            client,
+            Waiting {
+                results,
+                then,
+                deadline: Instant::now() + timeout,
+            },
+        );
+    }
+
+    /// hands the `response` of the fetch `id` to the answers waiting for it
+    fn finish(&mut self, id: usize, response: Result<Vec<u8>, ErrorKind>) {
+        let upstream = match self.upstreams.remove(&id) {
+            Some(upstream) => upstream,
+            None => return,
+        };
+        self.running.remove(&upstream.key);
+        let received = SystemTime::now();
+        for (client, index, shared) in upstream.waiters {
+            if let Some(waiting) = self.waiting.get_mut(&client) {
+                waiting.results[index] = Some(Fetched {
+                    sent: upstream.sent,
+                    received,
+                    shared,
+                    response: response.clone(),
+                });
+            }
+        }
+    }
+
+    /// fails the fetches and the answers waiting longer than their timeout
+    fn expire(&mut self, now: Instant) {
+        let expired: Vec<usize> = self
+            .upstreams
+            .iter()
+            .filter(|(_, upstream)| upstream.deadline <= now)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in expired {
+            self.finish(id, Err(ErrorKind::IoTimedOut));
+        }
+        // answers which joined a fetch started with a longer timeout
+        for waiting in self.waiting.values_mut().filter(|waiting| waiting.deadline <= now) {
+            for result in waiting.results.iter_mut().filter(|result| result.is_none()) {
+                *result = Some(Fetched::failed(ErrorKind::JobTimedOut, true));
+            }
+        }
+    }
+
+    /// removes and returns the answers which have all their results
+    fn done(&mut self) -> Vec<(usize, Then, Vec<Fetched>)> {
+        let clients: Vec<usize> = self
+            .waiting
+            .iter()
+            .filter(|(_, waiting)| waiting.results.iter().all(Option::is_some))
+            .map(|(client, _)| *client)
+            .collect();
+        clients
+            .into_iter()
+            .filter_map(|client| self.waiting.remove(&client).map(|waiting| (client, waiting)))
+            .map(|(client, waiting)| (client, waiting.then, waiting.results.into_iter().flatten().collect()))
+            .collect()
+    }
+}
+
+/// State is what a connection waits for
+enum State {
+    /// the request head read so far
+    Reading(Vec<u8>),
+    /// the request is answered on the ThreadPool or waits for its fetches
+    Answering,
+    /// the response and how much of it was written
+    Writing(Vec<u8>, usize),
+}
+
+/// Connection is a client of the event loop
+struct Connection {
+    stream: TcpStream,
+    state: State,
+    deadline: Instant,
+}
+
+/// Progress is the result of handling a ready connection
+enum Progress {
+    /// wait for the next event
+    Pending,
+    /// the request head is complete
+    Request(String),
+    /// the connection is done or broken
+    Close,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> Self {
+        Self {
+            stream,
+            state: State::Reading(Vec::new()),
+            deadline: Instant::now() + TIMEOUT,
+        }
+    }
+
+    /// returns the events the connection waits for, none while it is answered
+    fn events(&self) -> i16 {
+        match self.state {
+            State::Reading(_) => sys::POLLIN,
+            State::Answering => 0,
+            State::Writing(..) => sys::POLLOUT,
+        }
+    }
+
+    /// starts writing `response`
+    fn respond(&mut self, response: Vec<u8>) {
+        self.state = State::Writing(response, 0);
+        self.deadline = Instant::now() + TIMEOUT;
+    }
+
+    /// reads or writes as much as possible without blocking
+    fn progress(&mut self) -> Progress {
+        match &mut self.state {
+            State::Reading(request) => {
+                let mut chunk = [0; 4096];
+                loop {
+                    match self.stream.read(&mut chunk) {
+                        // the client will not send more
+                        Ok(0) if request.is_empty() => return Progress::Close,
+                        Ok(0) => return Progress::Request(String::from_utf8_lossy(request).to_string()),
+                        Ok(amount) => {
+                            request.extend_from_slice(&chunk[..amount]);
+                            if complete(request) || request.len() >= MAX_REQUEST {
+                                return Progress::Request(String::from_utf8_lossy(request).to_string());
+                            }
+                        }
+                        Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Progress::Pending,
+                        Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                        Err(_) => return Progress::Close,
+                    }
+                }
+            }
+            State::Answering => Progress::Pending,
+            State::Writing(response, written) => {
+                while *written < response.len() {
+                    match self.stream.write(&response[*written..]) {
+                        Ok(0) => return Progress::Close,
+                        Ok(amount) => *written += amount,
+                        Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Progress::Pending,
+                        Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                        Err(_) => return Progress::Close,
+                    }
+                }
+                Progress::Close
+            }
+        }
+    }
+}
+
+/// returns true once the request head is terminated by an empty line
+fn complete(request: &[u8]) -> bool {
+    request.windows(4).any(|window| window == b"\r\n\r\n") || request.windows(2).any(|window| window == b"\n\n")
+}
+
+/// Job is the part of an answer running on the ThreadPool
+type Job = Box<dyn FnOnce() -> Answer + Send>;
+
+/// runs `job` on `pool`, the answer is sent back with `client`
+///
+/// Returns false if the queue of the pool is full.
+fn dispatch(
+    pool: &ThreadPool,
+    client: usize,
+    job: Job,
+    sender: &mpsc::Sender<(usize, Answer)>,
+    wake: &Arc<UnixStream>,
+) -> bool {
+    let (sender, wake) = (sender.clone(), Arc::clone(wake));
+    let queued = pool.execute_with(job, move |job| {
+        let (answer, panic) = match panic::catch_unwind(AssertUnwindSafe(job)) {
+            Ok(answer) => (answer, None),
+            Err(panic) => {
+                let response = response("500 Internal Server Error", &[], "text/plain", "internal error\n");
+                (Answer::Done(response), Some(panic))
+            }
+        };
+        let _ = sender.send((client, answer));
+        let _ = (&*wake).write(&[1]);
+        // let the pool count the panic
+        if let Some(panic) = panic {
+            panic::resume_unwind(panic);
+        }
+    });
+    queued.is_ok()
+}
+
+/// serves clients of `listener` until `stop` returns true
+///
+/// Requests are answered with `answer` on `pool`, a full queue is answered with 503.
+/// After `stop` no new clients are accepted and the open connections get `drain` to finish.
+pub(crate) fn serve<A, S>(
+    listener: TcpListener,
+    pool: &ThreadPool,
+    drain: Duration,
+    verbose: u8,
+    answer: A,
+    stop: S,
+) -> io::Result<()>
+where
+    A: Fn(&str) -> Answer + Send + Sync + 'static,
+    S: Fn() -> bool,
+{
+    listener.set_nonblocking(true)?;
+    let mut listener = Some(listener);
+
+    // answers of the ThreadPool come back over the channel, the socket pair wakes up poll
+    let (waker, wake) = UnixStream::pair()?;
+    waker.set_nonblocking(true)?;
+    wake.set_nonblocking(true)?;
+    let wake = Arc::new(wake);
+    let (sender, answers) = mpsc::channel::<(usize, Answer)>();
+    let answer = Arc::new(answer);
+
+    let mut connections: HashMap<usize, Connection> = HashMap::new();
+    let mut fetches = Fetches::default();
+    let mut next = 0;
+    let mut draining: Option<Instant> = None;
+
+    loop {
+        if draining.is_none() && stop() {
+            if verbose >= 1 {
+                println!("Debug1: stopped accepting, {} connections open", connections.len());
+            }
+            listener = None;
+            draining = Some(Instant::now() + drain);
+        }
+        if let Some(until) = draining {
+            if connections.is_empty() || Instant::now() >= until {
+                return Ok(());
+            }
+        }
+
+        let accepting = listener.as_ref().filter(|_| connections.len() < MAX_CONNECTIONS);
+        let mut fds = vec![sys::PollFd {
+            fd: waker.as_raw_fd(),
+            events: sys::POLLIN,
+            revents: 0,
+        }];
+        if let Some(listener) = accepting {
+            fds.push(sys::PollFd {
+                fd: listener.as_raw_fd(),
+                events: sys::POLLIN,
+                revents: 0,
+            });
+        }
+        let offset = fds.len();
+        let mut tokens = Vec::with_capacity(connections.len());
+        for (token, connection) in &connections {
+            let events = connection.events();
+            if events != 0 {
+                fds.push(sys::PollFd {
+                    fd: connection.stream.as_raw_fd(),
+                    events,
+                    revents: 0,
+                });
+                tokens.push(*token);
+            }
+        }
+        let upstream_offset = fds.len();
+        let mut ids = Vec::with_capacity(fetches.upstreams.len());
+        for (id, upstream) in &fetches.upstreams {
+            fds.push(sys::PollFd {
+                fd: upstream.stream.as_raw_fd(),
+                events: upstream.events(),
+                revents: 0,
+            });
+            ids.push(*id);
+        }
+        poll(&mut fds, TICK)?;
+
+        // answers of the ThreadPool
+        if fds[0].revents != 0 {
+            while (&waker).read(&mut [0; 64]).is_ok_and(|amount| amount > 0) {}
+        }
+        while let Ok((token, answer)) = answers.try_recv() {
+            match answer {
+                Answer::Done(response) => {
+                    if let Some(connection) = connections.get_mut(&token) {
+                        connection.respond(response);
+                    }
+                }
+                Answer::Fetch(requests, then) => fetches.start(token, requests, then),
+            }
+        }
+
+        // new clients
+        if let Some(listener) = accepting.filter(|_| fds[1].revents != 0) {
+            while connections.len() < MAX_CONNECTIONS {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        if stream.set_nonblocking(true).is_err() {
+                            continue;
+                        }
+                        connections.insert(next, Connection::new(stream));
+                        next = next.wrapping_add(1);
+                    }
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(err) => {
+                        eprintln!("error createing stream: {}", err);
+                        break;
+                    }
+                }
+            }
+        }
+
+        // ready clients
+        for (fd, token) in fds[offset..upstream_offset].iter().zip(tokens) {
+            if fd.revents == 0 {
+                continue;
+            }
+            let connection = match connections.get_mut(&token) {
+                Some(connection) => connection,
+                None => continue,
+            };
+            match connection.progress() {
+                Progress::Pending => {}
+                Progress::Close => {
+                    connections.remove(&token);
+                }
+                Progress::Request(request) => {
+                    let answer = Arc::clone(&answer);
+                    if dispatch(pool, token, Box::new(move || answer(&request)), &sender, &wake) {
+                        connection.state = State::Answering;
+                    } else {
+                        if verbose >= 2 {
+                            println!("Debug2: queue is full, rejecting client");
+                        }
+                        connection.respond(busy());
+                    }
+                }
+            }
+        }
+
+        // ready fetches
+        for (fd, id) in fds[upstream_offset..].iter().zip(ids) {
+            if fd.revents == 0 {
+                continue;
+            }
+            if let Some(response) = fetches.upstreams.get_mut(&id).and_then(Upstream::progress) {
+                fetches.finish(id, response);
+            }
+        }
+
+        // answers with all their fetches continue on the ThreadPool
+        fetches.expire(Instant::now());
+        for (token, then, results) in fetches.done() {
+            if !dispatch(pool, token, Box::new(move || then(results)), &sender, &wake) {
+                if verbose >= 2 {
+                    println!("Debug2: queue is full, rejecting client");
+                }
+                if let Some(connection) = connections.get_mut(&token) {
+                    connection.respond(busy());
+                }
+            }
+        }
+
+        // clients which take too long, answers are waited for
+        let now = Instant::now();
+        connections.retain(|_, connection| matches!(connection.state, State::Answering) || connection.deadline > now);
+    }
+}
@@ -0,0 +1,241 @@
+//! test file to test the event loop serving the clients
+
+use super::{complete, serve, Answer, Fetch, Fetched};
+use crate::error::ErrorKind;
+use crate::threads::ThreadPool;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// runs the event loop on a free port with `threads` workers and a queue of `queue`
+fn start<A>(threads: usize, queue: Option<usize>, answer: A) -> (u16, Arc<AtomicBool>, std::thread::JoinHandle<()>)
+where
+    A: Fn(&str) -> Answer + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = Arc::clone(&stop);
+    let handle = std::thread::spawn(move || {
+        let mut pool = ThreadPool::new(threads).unwrap();
+        pool.set_queue_size(queue);
+        serve(listener, &pool, Duration::from_secs(5), 0, answer, || stopped.load(Ordering::SeqCst)).unwrap();
+    });
+    (port, stop, handle)
+}
+
+/// returns the path of the request, after `delay`
+fn echo(delay: Duration) -> impl Fn(&str) -> Answer + Send + Sync + 'static {
+    move |request: &str| {
+        std::thread::sleep(delay);
+        Answer::Done(ok(&path(request)))
+    }
+}
+
+/// fetches `/{path}` from `addr` under the key `path`, and returns the body of the upstream
+fn proxy(addr: SocketAddr, timeout: Duration) -> impl Fn(&str) -> Answer + Send + Sync + 'static {
+    move |request: &str| {
+        let key = path(request);
+        let fetch = Fetch {
+            request: format!("GET {} HTTP/1.0\r\n\r\n", key).into_bytes(),
+            key,
+            addr,
+            timeout,
+        };
+        Answer::Fetch(vec![fetch], Box::new(|fetched: Vec<Fetched>| Answer::Done(ok(&body(&fetched[0])))))
+    }
+}
+
+fn path(request: &str) -> String {
+    request.split_whitespace().nth(1).unwrap_or("/").to_string()
+}
+
+fn ok(content: &str) -> Vec<u8> {
+    format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", content.len(), content).into_bytes()
+}
+
+/// returns the body fetched, or the error, and whether the fetch was shared
+fn body(fetched: &Fetched) -> String {
+    let content = match &fetched.response {
+        Ok(raw) => {
+            let raw = String::from_utf8_lossy(raw).to_string();
+            raw[raw.find("\r\n\r\n").unwrap() + 4..].to_string()
+        }
+        Err(kind) => kind.to_string(),
+    };
+    format!("{} shared={}", content, fetched.shared)
+}
+
+/// starts an upstream answering each connection with its path after `delay`
+///
+/// Returns the address and the number of accepted connections.
+fn upstream(delay: Duration) -> (SocketAddr, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let accepted = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&accepted);
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            counter.fetch_add(1, Ordering::SeqCst);
+            std::thread::spawn(move || {
+                let mut request = Vec::new();
+                let mut chunk = [0; 512];
+                while !complete(&request) {
+                    match stream.read(&mut chunk) {
+                        Ok(0) | Err(_) => return,
+                        Ok(amount) => request.extend_from_slice(&chunk[..amount]),
+                    }
+                }
+                std::thread::sleep(delay);
+                let path = path(&String::from_utf8_lossy(&request));
+                let _ = stream.write_all(format!("HTTP/1.0 200 OK\r\n\r\n{}", path).as_bytes());
+            });
+        }
+    });
+    (addr, accepted)
+}
+
+fn get(port: u16, path: &str) -> String {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    stream.write_all(format!("GET {} HTTP/1.0\r\n\r\n", path).as_bytes()).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn request_head() {
+    assert!(!complete(b"GET / HTTP/1.0\r\n"));
+    assert!(complete(b"GET / HTTP/1.0\r\nHost: a\r\n\r\n"));
+    assert!(complete(b"GET / HTTP/1.0\n\n"));
+}
+
+#[test]
+fn idle_clients_do_not_block() {
+    let (port, stop, handle) = start(1, None, echo(Duration::from_millis(0)));
+
+    // more silent clients than threads
+    let idle: Vec<TcpStream> = (0..32).map(|_| TcpStream::connect(("127.0.0.1", port)).unwrap()).collect();
+    let start = Instant::now();
+    assert!(get(port, "/metrics").ends_with("\r\n\r\n/metrics"));
+    assert!(start.elapsed() < Duration::from_secs(2));
+
+    // a request sent in pieces
+    let mut slow = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    slow.write_all(b"GET /sl").unwrap();
+    std::thread::sleep(Duration::from_millis(150));
+    slow.write_all(b"ow HTTP/1.0\r\n\r\n").unwrap();
+    let mut response = String::new();
+    slow.read_to_string(&mut response).unwrap();
+    assert!(response.ends_with("/slow"));
+
+    drop(idle);
+    stop.store(true, Ordering::SeqCst);
+    handle.join().unwrap();
+}
+
+#[test]
+fn full_queue_is_rejected() {
+    let (port, stop, handle) = start(1, Some(1), echo(Duration::from_millis(300)));
+
+    let clients: Vec<_> = (0..3)
+        .map(|_| {
+            let client = std::thread::spawn(move || get(port, "/"));
+            std::thread::sleep(Duration::from_millis(50));
+            client
+        })
+        .collect();
+    let responses: Vec<String> = clients.into_iter().map(|client| client.join().unwrap()).collect();
+    assert!(responses[0].starts_with("HTTP/1.1 200 OK"));
+    assert!(responses[1].starts_with("HTTP/1.1 200 OK"));
+    assert!(responses[2].starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+    assert!(responses[2].contains("\r\nRetry-After: 1\r\n"));
+
+    stop.store(true, Ordering::SeqCst);
+    handle.join().unwrap();
+}
+
+#[test]
+fn drains_on_stop() {
+    let (port, stop, handle) = start(2, None, echo(Duration::from_millis(300)));
+
+    let client = std::thread::spawn(move || get(port, "/draining"));
+    std::thread::sleep(Duration::from_millis(100));
+    stop.store(true, Ordering::SeqCst);
+
+    assert!(client.join().unwrap().ends_with("/draining"));
+    handle.join().unwrap();
+    assert!(TcpStream::connect(("127.0.0.1", port)).is_err());
+}
+
+#[test]
+fn fetches_upstream() {
+    let (addr, accepted) = upstream(Duration::from_millis(0));
+    let (port, stop, handle) = start(1, None, proxy(addr, Duration::from_secs(5)));
+
+    assert!(get(port, "/stats").ends_with("\r\n\r\n/stats shared=false"));
+    assert_eq!(accepted.load(Ordering::SeqCst), 1);
+
+    stop.store(true, Ordering::SeqCst);
+    handle.join().unwrap();
+}
+
+#[test]
+fn running_fetches_are_shared() {
+    let (addr, accepted) = upstream(Duration::from_millis(300));
+    let (port, stop, handle) = start(2, None, proxy(addr, Duration::from_secs(5)));
+
+    let clients: Vec<_> = (0..2)
+        .map(|_| {
+            let client = std::thread::spawn(move || get(port, "/stats"));
+            std::thread::sleep(Duration::from_millis(50));
+            client
+        })
+        .collect();
+    let responses: Vec<String> = clients.into_iter().map(|client| client.join().unwrap()).collect();
+    assert!(responses[0].ends_with("/stats shared=false"));
+    assert!(responses[1].ends_with("/stats shared=true"));
+    assert_eq!(accepted.load(Ordering::SeqCst), 1);
+
+    // a finished fetch is not shared any more
+    assert!(get(port, "/stats").ends_with("/stats shared=false"));
+    assert_eq!(accepted.load(Ordering::SeqCst), 2);
+
+    stop.store(true, Ordering::SeqCst);
+    handle.join().unwrap();
+}
+
+#[test]
+fn slow_upstreams_do_not_hold_threads() {
+    let (addr, _) = upstream(Duration::from_millis(500));
+    let (port, stop, handle) = start(1, None, proxy(addr, Duration::from_secs(5)));
+
+    // more slow fetches than threads, all waiting at once
+    let start = Instant::now();
+    let clients: Vec<_> = (0..4).map(|i| std::thread::spawn(move || get(port, &format!("/{}", i)))).collect();
+    for (i, client) in clients.into_iter().enumerate() {
+        assert!(client.join().unwrap().ends_with(&format!("/{} shared=false", i)));
+    }
+    assert!(start.elapsed() < Duration::from_millis(1500));
+
+    stop.store(true, Ordering::SeqCst);
+    handle.join().unwrap();
+}
+
+#[test]
+fn failed_fetches() {
+    // accepted by the backlog, but never answered
+    let silent = TcpListener::bind("127.0.0.1:0").unwrap();
+    let (port, stop, handle) = start(1, None, proxy(silent.local_addr().unwrap(), Duration::from_millis(200)));
+    assert!(get(port, "/").ends_with(&format!("{} shared=false", ErrorKind::IoTimedOut)));
+    stop.store(true, Ordering::SeqCst);
+    handle.join().unwrap();
+
+    let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let (port, stop, handle) = start(1, None, proxy(closed, Duration::from_secs(5)));
+    assert!(get(port, "/closed").contains("Unreachable(/closed: "));
+    stop.store(true, Ordering::SeqCst);
+    handle.join().unwrap();
+}
@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use super::error::{Error, ErrorKind, Result};
+
+// flight coalesces concurrent calls for the same key into a single one
+//
+// Callers arriving while a call is running wait for it, up to a timeout, and get a clone of its result.
+// With a max age, results younger than that are handed out without calling again.
+// Callers sharing calls themselves, like an event loop, only use `recent` and `complete`.
+
+// tests as sub module
+#[cfg(test)] // only add when running tests
+mod test;
+
+/// State of the call for one key
+enum State<T> {
+    /// the call is running, callers wait for it
+    Running,
+    /// the result and when it was ready
+    Done(Instant, T),
+    /// the call panicked, waiting callers make their own
+    Abandoned,
+}
+
+/// Slot is the call for one key and the callers waiting for it
+struct Slot<T> {
+    state: Mutex<State<T>>,
+    done: Condvar,
+}
+
+/// Stats are the counters of a Group
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stats {
+    /// calls actually made
+    pub calls: usize,
+
+    /// callers which waited for a running call
+    pub in_flight: usize,
+
+    /// callers which got a result younger than the max age
+    pub recent: usize,
+}
+
+/// Group runs at most one call per key at a time and shares its result
+pub struct Group<T> {
+    slots: Mutex<HashMap<String, Arc<Slot<T>>>>,
+    max_age: Mutex<Duration>,
+    calls: AtomicUsize,
+    in_flight: AtomicUsize,
+    recent: AtomicUsize,
+}
+
+/// locks `mutex`, a panicking caller does not leave the data inconsistent
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+impl<T: Clone> Default for Group<T> {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(0))
+    }
+}
+
+impl<T: Clone> Group<T> {
+    /// creates a Group reusing results for `max_age`, zero only shares running calls
+    pub fn new(max_age: Duration) -> Self {
+        Self {
+            slots: Mutex::new(HashMap::new()),
+            max_age: Mutex::new(max_age),
+            calls: AtomicUsize::new(0),
+            in_flight: AtomicUsize::new(0),
+            recent: AtomicUsize::new(0),
+        }
+    }
+
+    /// changes how long results are reused, applies to the results already there
+    pub fn set_max_age(&self, max_age: Duration) {
+        *lock(&self.max_age) = max_age;
+    }
+
+    /// returns how long results are reused
+    pub fn max_age(&self) -> Duration {
+        *lock(&self.max_age)
+    }
+
+    /// returns the counters of the calls and the coalesced callers
+    pub fn stats(&self) -> Stats {
+        Stats {
+            calls: self.calls.load(Ordering::SeqCst),
+            in_flight: self.in_flight.load(Ordering::SeqCst),
+            recent: self.recent.load(Ordering::SeqCst),
+        }
+    }
+
+    /// returns the result of `f` for `key`, shared with the other callers of `key`
+    ///
+    /// `f` is only called when no call for `key` is running and there is no result
+    /// younger than the max age. Waiting for a running call longer than `timeout`
+    /// returns `ErrorKind::JobTimedOut`, the call itself is not interrupted.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use opentracker_exporter::flight::Group;
+    /// use std::time::Duration;
+    ///
+    /// let group = Group::new(Duration::from_secs(60));
+    /// let timeout = Duration::from_secs(10);
+    /// assert_eq!(group.run("tracker", timeout, || 1).unwrap(), 1);
+    /// // the result is younger than a minute
+    /// assert_eq!(group.run("tracker", timeout, || 2).unwrap(), 1);
+    /// assert_eq!(group.stats().recent, 1);
+    /// ```
+    pub fn run<F: FnOnce() -> T>(&self, key: &str, timeout: Duration, f: F) -> Result<T> {
+        loop {
+            let mut slots = lock(&self.slots);
+            if let Some(slot) = slots.get(key).cloned() {
+                let mut state = lock(&slot.state);
+                if matches!(*state, State::Running) {
+                    drop(slots);
+                    self.in_flight.fetch_add(1, Ordering::SeqCst);
+                    let deadline = Instant::now() + timeout;
+                    while matches!(*state, State::Running) {
+                        let left = deadline.saturating_duration_since(Instant::now());
+                        if left == Duration::from_secs(0) {
+                            return Err(Error::new(ErrorKind::JobTimedOut));
+                        }
+                        state = match slot.done.wait_timeout(state, left) {
+                            Ok((state, _)) => state,
+                            Err(err) => err.into_inner().0,
+                        };
+                    }
+                    match &*state {
+                        State::Done(_, value) => return Ok(value.clone()),
+                        // the call panicked, make another
+                        _ => continue,
+                    }
+                }
+                if let State::Done(at, value) = &*state {
+                    if at.elapsed() < self.max_age() {
+                        self.recent.fetch_add(1, Ordering::SeqCst);
+                        return Ok(value.clone());
+                    }
+                }
+            }
+
+            let slot = Arc::new(Slot {
+                state: Mutex::new(State::Running),
+                done: Condvar::new(),
+            });
+            slots.insert(key.to_string(), Arc::clone(&slot));
+            drop(slots);
+            self.calls.fetch_add(1, Ordering::SeqCst);
+
+            let mut call = Call {
+                group: self,
+                slot,
+                done: false,
+            };
+            let value = f();
+            call.finish(value.clone());
+            return Ok(value);
+        }
+    }
+
+    /// returns a result for `key` younger than the max age without calling
+    pub fn recent(&self, key: &str) -> Option<T> {
+        let slots = lock(&self.slots);
+        let slot = slots.get(key)?;
+        let state = lock(&slot.state);
+        match &*state {
+            State::Done(at, value) if at.elapsed() < self.max_age() => {
+                self.recent.fetch_add(1, Ordering::SeqCst);
+                Some(value.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// counts a call for `key` made without `run`, e.g. by an event loop sharing calls itself
+    ///
+    /// `shared` tells whether the caller got the result of a call made for another caller.
+    /// Only the result of the call itself is kept for the max age, a running call is left alone.
+    pub fn complete(&self, key: &str, value: T, shared: bool) {
+        if shared {
+            self.in_flight.fetch_add(1, Ordering::SeqCst);
+            return;
+        }
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        {
+            let mut slots = lock(&self.slots);
+            let slot = slots.entry(key.to_string()).or_insert_with(|| {
+                Arc::new(Slot {
+                    state: Mutex::new(State::Abandoned),
+                    done: Condvar::new(),
+                })
+            });
+            let mut state = lock(&slot.state);
+            if !matches!(*state, State::Running) {
+                *state = State::Done(Instant::now(), value);
+            }
+        }
+        self.forget();
+    }
+
+    /// removes the finished calls whose results are older than the max age
+    fn forget(&self) {
+        let max_age = self.max_age();
+        let mut slots = lock(&self.slots);
+        slots.retain(|_, slot| match &*lock(&slot.state) {
+            State::Running => true,
+            State::Done(at, _) => at.elapsed() < max_age,
+            State::Abandoned => false,
+        });
+    }
+}
+
+/// Call is a running call, it wakes the waiting callers even if the call panics
+struct Call<'a, T: Clone> {
+    group: &'a Group<T>,
+    slot: Arc<Slot<T>>,
+    done: bool,
+}
+
+impl<'a, T: Clone> Call<'a, T> {
+    /// hands `value` to the waiting callers
+    fn finish(&mut self, value: T) {
+        self.done = true;
+        *lock(&self.slot.state) = State::Done(Instant::now(), value);
+        self.slot.done.notify_all();
+        self.group.forget();
+    }
+}
+
+impl<'a, T: Clone> Drop for Call<'a, T> {
+    fn drop(&mut self) {
+        if !self.done {
+            *lock(&self.slot.state) = State::Abandoned;
+            self.slot.done.notify_all();
+            self.group.forget();
+        }
+    }
+}
@@ -0,0 +1,138 @@
+//! test file to test the coalescing of concurrent calls
+
+use super::{Group, Stats};
+use crate::error::ErrorKind;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[test]
+fn concurrent_calls_are_shared() {
+    let group = Arc::new(Group::default());
+    let calls = Arc::new(AtomicUsize::new(0));
+
+    let callers: Vec<_> = (0..4)
+        .map(|_| {
+            let (group, calls) = (Arc::clone(&group), Arc::clone(&calls));
+            let caller = thread::spawn(move || {
+                group.run("tracker", TIMEOUT, || {
+                    thread::sleep(Duration::from_millis(300));
+                    calls.fetch_add(1, Ordering::SeqCst)
+                })
+            });
+            thread::sleep(Duration::from_millis(20));
+            caller
+        })
+        .collect();
+    for caller in callers {
+        assert_eq!(caller.join().unwrap().unwrap(), 0);
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert_eq!(
+        group.stats(),
+        Stats {
+            calls: 1,
+            in_flight: 3,
+            recent: 0
+        }
+    );
+
+    // nothing is kept without a max age
+    assert_eq!(group.run("tracker", TIMEOUT, || 7).unwrap(), 7);
+    assert!(group.slots.lock().unwrap().is_empty());
+}
+
+#[test]
+fn keys_are_separate() {
+    let group = Group::default();
+    assert_eq!(group.run("a", TIMEOUT, || 1).unwrap(), 1);
+    assert_eq!(group.run("b", TIMEOUT, || 2).unwrap(), 2);
+    assert_eq!(group.stats().calls, 2);
+}
+
+#[test]
+fn recent_results_are_reused() {
+    let group = Group::new(Duration::from_millis(200));
+    assert_eq!(group.run("tracker", TIMEOUT, || 1).unwrap(), 1);
+    assert_eq!(group.run("tracker", TIMEOUT, || 2).unwrap(), 1);
+    assert_eq!(group.stats().recent, 1);
+
+    thread::sleep(Duration::from_millis(250));
+    assert_eq!(group.run("tracker", TIMEOUT, || 3).unwrap(), 3);
+
+    group.set_max_age(Duration::from_secs(0));
+    assert_eq!(group.run("tracker", TIMEOUT, || 4).unwrap(), 4);
+    assert_eq!(group.stats().calls, 3);
+}
+
+#[test]
+fn panicking_call_releases_waiters() {
+    let group = Arc::new(Group::default());
+
+    let leader = {
+        let group = Arc::clone(&group);
+        thread::spawn(move || {
+            group.run("tracker", TIMEOUT, || -> usize {
+                thread::sleep(Duration::from_millis(200));
+                panic!("tracker gone");
+            })
+        })
+    };
+    thread::sleep(Duration::from_millis(50));
+    // the waiter makes its own call once the leader panicked
+    assert_eq!(group.run("tracker", TIMEOUT, || 2).unwrap(), 2);
+    assert!(leader.join().is_err());
+    assert_eq!(group.stats().in_flight, 1);
+    assert_eq!(group.run("tracker", TIMEOUT, || 3).unwrap(), 3);
+}
+
+#[test]
+fn waiting_is_bounded() {
+    let group = Arc::new(Group::default());
+
+    let leader = {
+        let group = Arc::clone(&group);
+        thread::spawn(move || {
+            group.run("tracker", TIMEOUT, || {
+                thread::sleep(Duration::from_millis(500));
+                1
+            })
+        })
+    };
+    thread::sleep(Duration::from_millis(50));
+    let err = group.run("tracker", Duration::from_millis(100), || 2).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::JobTimedOut);
+    assert_eq!(leader.join().unwrap().unwrap(), 1);
+}
+
+#[test]
+fn calls_made_elsewhere() {
+    let group = Group::new(Duration::from_millis(200));
+    assert_eq!(group.recent("tracker"), None);
+
+    group.complete("tracker", 1, false);
+    group.complete("tracker", 1, true);
+    assert_eq!(group.recent("tracker"), Some(1));
+    // the result is shared with run as well
+    assert_eq!(group.run("tracker", TIMEOUT, || 2).unwrap(), 1);
+    assert_eq!(
+        group.stats(),
+        Stats {
+            calls: 1,
+            in_flight: 1,
+            recent: 2
+        }
+    );
+
+    thread::sleep(Duration::from_millis(250));
+    assert_eq!(group.recent("tracker"), None);
+
+    // nothing is kept without a max age
+    group.set_max_age(Duration::from_secs(0));
+    group.complete("tracker", 3, false);
+    assert_eq!(group.recent("tracker"), None);
+    assert!(group.slots.lock().unwrap().is_empty());
+}
@@ -0,0 +1,80 @@
+use std::io::prelude::*;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, UNIX_EPOCH};
+
+use super::collector::{Scrape, Sink};
+use super::error::{Error, ErrorKind, Result};
+use super::sanitize;
+
+// tests as sub module
+#[cfg(test)] // only add when running tests
+mod test;
+
+/// timeout for connecting and writing to the carbon server
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// maximum number of lines kept while the server is unreachable
+const MAX_QUEUE: usize = 100_000;
+
+/// Graphite is a sink sending the plaintext protocol to a carbon server
+pub struct Graphite {
+    addr: String,
+    prefix: String,
+    lines: Vec<String>,
+}
+
+impl Graphite {
+    /// creates a new Graphite for carbon on `addr` and metrics named with `prefix`
+    pub fn new(addr: &str, prefix: &str) -> Self {
+        Self {
+            addr: addr.to_string(),
+            prefix: prefix.to_string(),
+            lines: Vec::new(),
+        }
+    }
+}
+
+impl Sink for Graphite {
+    fn name(&self) -> String {
+        format!("graphite {}", self.addr)
+    }
+
+    fn collect(&mut self, scrape: &Scrape) {
+        let time = match scrape.time.duration_since(UNIX_EPOCH) {
+            Ok(time) => time.as_secs(),
+            Err(_) => 0,
+        };
+        let name = sanitize(&scrape.name);
+
+        for (path, value) in scrape.data.gauges() {
+            self.lines.push(format!("{}.{}.{} {} {}\n", self.prefix, name, path, value, time));
+        }
+        for (path, value) in scrape.data.counters() {
+            self.lines.push(format!("{}.{}.{} {} {}\n", self.prefix, name, path, value, time));
+        }
+
+        if self.lines.len() > MAX_QUEUE {
+            let dropped = self.lines.len() - MAX_QUEUE;
+            self.lines.drain(..dropped);
+            eprintln!("graphite queue full, dropped {} lines", dropped);
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.lines.is_empty() {
+            return Ok(());
+        }
+
+        let addr = match self.addr.to_socket_addrs()?.next() {
+            Some(addr) => addr,
+            None => return Err(Error::new(ErrorKind::IoAddrNotAvailable)),
+        };
+        let mut stream = TcpStream::connect_timeout(&addr, TIMEOUT)?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+
+        stream.write_all(self.lines.concat().as_bytes())?;
+        stream.flush()?;
+        self.lines.clear();
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+//! test file for the graphite sink
+
+use super::Graphite;
+use crate::collector::{test, Scrape, Sink};
+use std::io::prelude::*;
+use std::net::TcpListener;
+
+fn scrape() -> Scrape {
+    test::scrape("tracker.example", 1_561_392_000, |data| {
+        data.connections.udp_announce = 3000;
+        data.http_error.insert(String::from("404 Not Found"), 2);
+    })
+}
+
+#[test]
+fn lines() {
+    let mut sink = Graphite::new("localhost:2003", "ot");
+    sink.collect(&scrape());
+    assert!(sink
+        .lines
+        .contains(&String::from("ot.tracker_example.connections.udp.announce 3000 1561392000\n")));
+    assert!(sink
+        .lines
+        .contains(&String::from("ot.tracker_example.http_codes.404_Not_Found 2 1561392000\n")));
+}
+
+#[test]
+fn flush() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+
+    let mut sink = Graphite::new(&addr, "ot");
+    sink.collect(&scrape());
+    let expected = sink.lines.concat();
+    sink.flush().unwrap();
+    assert!(sink.lines.is_empty());
+
+    let (mut stream, _) = listener.accept().unwrap();
+    let mut received = String::new();
+    stream.read_to_string(&mut received).unwrap();
+    assert_eq!(received, expected);
+}
+
+#[test]
+fn keeps_lines_when_unreachable() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    drop(listener);
+
+    let mut sink = Graphite::new(&addr, "ot");
+    sink.collect(&scrape());
+    assert!(sink.flush().is_err());
+    assert!(!sink.lines.is_empty());
+}
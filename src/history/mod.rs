@@ -0,0 +1,170 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::collector::{Scrape, Sink};
+use super::error::Result;
+use super::{json, query_param};
+
+/// html status page with inline svg charts
+mod page;
+pub use page::status;
+
+// tests as sub module
+#[cfg(test)] // only add when running tests
+mod test;
+
+/// Snapshot holds the values of one scrape
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Snapshot {
+    /// unix time of the scrape in seconds
+    pub(crate) time: u64,
+    /// values by path as returned by `gauges` and `counters`
+    pub(crate) values: Vec<(String, f64)>,
+}
+
+impl Snapshot {
+    fn get(&self, field: &str) -> Option<f64> {
+        self.values.iter().find(|(path, _)| path == field).map(|(_, value)| *value)
+    }
+}
+
+/// History is a ring buffer of the last scrapes, shared between the collector and the server
+#[derive(Clone)]
+pub struct History {
+    snapshots: Arc<Mutex<VecDeque<Snapshot>>>,
+    capacity: usize,
+}
+
+impl History {
+    /// creates a History keeping `capacity` snapshots
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(2);
+        Self {
+            snapshots: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    /// creates a History covering `hours` of scrapes made every `interval` seconds
+    pub fn for_hours(hours: u64, interval: u64) -> Self {
+        Self::new((hours * 3600 / interval.max(1)) as usize)
+    }
+
+    /// returns the snapshots, a panic while holding the lock does not corrupt them
+    fn lock(&self) -> MutexGuard<'_, VecDeque<Snapshot>> {
+        self.snapshots.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// adds a snapshot, dropping the oldest if full
+    pub(crate) fn push(&self, snapshot: Snapshot) {
+        let mut snapshots = self.lock();
+        if snapshots.len() == self.capacity {
+            snapshots.pop_front();
+        }
+        snapshots.push_back(snapshot);
+    }
+
+    /// returns the latest snapshot
+    pub(crate) fn latest(&self) -> Option<Snapshot> {
+        self.lock().back().cloned()
+    }
+
+    /// returns the fields of the latest snapshot
+    pub fn fields(&self) -> Vec<String> {
+        self.latest()
+            .map(|snapshot| snapshot.values.into_iter().map(|(path, _)| path).collect())
+            .unwrap_or_default()
+    }
+
+    /// returns the values of `field` recorded at or after `since`
+    pub fn series(&self, field: &str, since: u64) -> Vec<(u64, f64)> {
+        self.lock()
+            .iter()
+            .filter(|snapshot| snapshot.time >= since)
+            .filter_map(|snapshot| snapshot.get(field).map(|value| (snapshot.time, value)))
+            .collect()
+    }
+
+    /// returns the per second rate of the counter `field` since `since`
+    /// pairs where the counter went backwards because of a restart are skipped
+    pub fn rate(&self, field: &str, since: u64) -> Vec<(u64, f64)> {
+        self.series(field, since)
+            .windows(2)
+            .filter_map(|pair| {
+                let ((last_time, last), (time, value)) = (pair[0], pair[1]);
+                if value < last || time <= last_time {
+                    return None;
+                }
+                Some((time, (value - last) / (time - last_time) as f64))
+            })
+            .collect()
+    }
+}
+
+impl Sink for History {
+    fn name(&self) -> String {
+        format!("history of {} scrapes", self.capacity)
+    }
+
+    fn collect(&mut self, scrape: &Scrape) {
+        let mut values = scrape.data.gauges();
+        values.extend(
+            scrape
+                .data
+                .counters()
+                .into_iter()
+                .map(|(path, value)| (path, value as f64)),
+        );
+        self.push(Snapshot {
+            time: scrape.time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+            values,
+        });
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// answers `/api/history?field=...&since=...`, returns the http status and json body
+///
+/// `since` is a unix time in seconds, negative values are relative to now.
+pub fn api(history: &History, query: &str) -> (&'static str, String) {
+    let field = match query_param(query, "field") {
+        Some(field) => field,
+        None => {
+            let fields: Vec<String> = history.fields().iter().map(|field| json::string(field)).collect();
+            return (
+                "400 Bad Request",
+                format!(r#"{{"error":"missing field","fields":[{}]}}"#, fields.join(",")),
+            );
+        }
+    };
+    if !history.fields().contains(&field) {
+        return (
+            "404 Not Found",
+            format!(r#"{{"error":{}}}"#, json::string(&format!("unknown field {}", field))),
+        );
+    }
+
+    let since = match query_param(query, "since").map(|since| since.parse::<i64>()) {
+        None => 0,
+        Some(Ok(since)) if since < 0 => {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+            now.saturating_sub(since.unsigned_abs())
+        }
+        Some(Ok(since)) => since as u64,
+        Some(Err(_)) => return ("400 Bad Request", String::from(r#"{"error":"invalid since"}"#)),
+    };
+
+    let points: Vec<String> = history
+        .series(&field, since)
+        .iter()
+        .map(|(time, value)| format!("[{},{}]", time, json::number(*value)))
+        .collect();
+    (
+        "200 OK",
+        format!(r#"{{"field":{},"points":[{}]}}"#, json::string(&field), points.join(",")),
+    )
+}
@@ -0,0 +1,120 @@
+// page renders the html status page, everything is inline so no assets are needed
+
+use std::fmt::Write;
+
+use super::History;
+
+/// width of a chart in pixels
+const WIDTH: f64 = 600.0;
+
+/// height of a chart in pixels
+const HEIGHT: f64 = 120.0;
+
+/// gauges charted with their values
+const GAUGES: [&str; 3] = ["peers", "seeds", "torrents.mutex"];
+
+/// counters charted as per second rates
+const RATES: [&str; 5] = [
+    "connections.tcp.announce",
+    "connections.tcp.scrape",
+    "connections.udp.announce",
+    "connections.udp.scrape",
+    "mutex_stall",
+];
+
+/// returns the status page for the tracker `name` at `url`
+pub fn status(history: Option<&History>, name: &str, url: &str, refresh: u64) -> String {
+    let mut body = String::new();
+    match history {
+        None => body.push_str("<p>history is disabled, start the exporter with <code>--history HOURS</code></p>\n"),
+        Some(history) => match history.latest() {
+            None => body.push_str("<p>waiting for the first scrape</p>\n"),
+            Some(latest) => {
+                for field in GAUGES.iter() {
+                    chart(&mut body, field, &history.series(field, 0));
+                }
+                for field in RATES.iter() {
+                    chart(&mut body, &format!("{} per second", field), &history.rate(field, 0));
+                }
+
+                body.push_str("<table>\n");
+                for (path, value) in &latest.values {
+                    let _ = writeln!(body, "<tr><td>{}</td><td>{}</td></tr>", escape(path), value);
+                }
+                body.push_str("</table>\n");
+            }
+        },
+    }
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta http-equiv="refresh" content="{refresh}">
+<title>opentracker {name}</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em; }}
+svg {{ display: block; margin-bottom: 1.5em; background: #f8f8f8; }}
+polyline {{ fill: none; stroke: #1f77b4; stroke-width: 1.5; }}
+td {{ padding: 0 1em 0 0; }}
+</style>
+</head>
+<body>
+<h1>opentracker {name}</h1>
+<p>{url} &middot; opentracker_exporter {version}</p>
+{body}</body>
+</html>
+"#,
+        refresh = refresh,
+        name = escape(name),
+        url = escape(url),
+        version = env!("CARGO_PKG_VERSION"),
+        body = body
+    )
+}
+
+/// appends an svg line chart of `points` scaled from zero to the maximum
+pub(crate) fn chart(ret: &mut String, title: &str, points: &[(u64, f64)]) {
+    let max = points.iter().map(|(_, value)| *value).fold(0.0, f64::max);
+    // not first and last, the wall clock can step back between scrapes
+    let first = points.iter().map(|(time, _)| *time).min().unwrap_or(0);
+    let last = points.iter().map(|(time, _)| *time).max().unwrap_or(0);
+    let span = (last - first).max(1) as f64;
+
+    let line: Vec<String> = points
+        .iter()
+        .map(|(time, value)| {
+            let x = (time - first) as f64 / span * WIDTH;
+            let y = if max > 0.0 { HEIGHT - value / max * HEIGHT } else { HEIGHT };
+            format!("{:.1},{:.1}", x, y)
+        })
+        .collect();
+
+    let _ = writeln!(
+        ret,
+        r#"<svg width="{w}" height="{h}" viewBox="0 -20 {w} {h2}" role="img"><title>{t}</title><text x="4" y="-6" font-size="12">{t} (max {m})</text><polyline points="{p}"/></svg>"#,
+        w = WIDTH,
+        h = HEIGHT + 20.0,
+        h2 = HEIGHT + 20.0,
+        t = escape(title),
+        m = (max * 100.0).round() / 100.0,
+        p = line.join(" ")
+    );
+}
+
+/// escapes text for html
+pub(crate) fn escape(text: &str) -> String {
+    let mut ret = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => ret.push_str("&amp;"),
+            '<' => ret.push_str("&lt;"),
+            '>' => ret.push_str("&gt;"),
+            '"' => ret.push_str("&quot;"),
+            '\'' => ret.push_str("&#39;"),
+            c => ret.push(c),
+        }
+    }
+    ret
+}
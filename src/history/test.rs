@@ -0,0 +1,86 @@
+//! test file to test the history buffer, api and status page
+
+use super::page::{chart, escape};
+use super::{api, status, History, Snapshot};
+
+fn snapshot(time: u64, peers: f64, announce: f64) -> Snapshot {
+    Snapshot {
+        time,
+        values: vec![
+            (String::from("peers"), peers),
+            (String::from("connections.tcp.announce"), announce),
+        ],
+    }
+}
+
+fn history() -> History {
+    let history = History::new(3);
+    history.push(snapshot(100, 10.0, 1000.0));
+    history.push(snapshot(110, 12.0, 1100.0));
+    history.push(snapshot(120, 14.0, 50.0));
+    history.push(snapshot(130, 16.0, 150.0));
+    history
+}
+
+#[test]
+fn ring_buffer() {
+    let history = history();
+    assert_eq!(history.series("peers", 0), vec![(110, 12.0), (120, 14.0), (130, 16.0)]);
+    assert_eq!(history.series("peers", 120), vec![(120, 14.0), (130, 16.0)]);
+    assert_eq!(history.series("seeds", 0), vec![]);
+    assert_eq!(History::for_hours(1, 15).capacity, 240);
+}
+
+#[test]
+fn rate_skips_restart() {
+    assert_eq!(history().rate("connections.tcp.announce", 0), vec![(130, 10.0)]);
+}
+
+#[test]
+fn api_points() {
+    let (status, body) = api(&history(), "field=peers&since=120");
+    assert_eq!(status, "200 OK");
+    assert_eq!(body, r#"{"field":"peers","points":[[120,14],[130,16]]}"#);
+}
+
+#[test]
+fn api_errors() {
+    let (status, body) = api(&history(), "");
+    assert_eq!(status, "400 Bad Request");
+    assert_eq!(
+        body,
+        r#"{"error":"missing field","fields":["peers","connections.tcp.announce"]}"#
+    );
+
+    assert_eq!(api(&history(), "field=seeds").0, "404 Not Found");
+    assert_eq!(api(&history(), "field=peers&since=yesterday").0, "400 Bad Request");
+    // relative since only returns recent points
+    assert_eq!(api(&history(), "field=peers&since=-60").1, r#"{"field":"peers","points":[]}"#);
+}
+
+#[test]
+fn svg() {
+    let mut svg = String::new();
+    chart(&mut svg, "peers", &[(100, 0.0), (110, 5.0), (120, 10.0)]);
+    assert!(svg.contains(r#"<polyline points="0.0,120.0 300.0,60.0 600.0,0.0"/>"#));
+    assert!(svg.contains("peers (max 10)"));
+}
+
+#[test]
+fn svg_clock_stepped_back() {
+    let mut svg = String::new();
+    chart(&mut svg, "peers", &[(110, 0.0), (100, 5.0), (120, 10.0)]);
+    assert!(svg.contains(r#"<polyline points="300.0,120.0 0.0,60.0 600.0,0.0"/>"#));
+}
+
+#[test]
+fn page() {
+    let page = status(Some(&history()), "<tracker>", "localhost", 15);
+    assert!(page.contains("<h1>opentracker &lt;tracker&gt;</h1>"));
+    assert!(page.contains("connections.tcp.announce per second (max 10)"));
+    assert!(page.contains("<tr><td>peers</td><td>16</td></tr>"));
+
+    assert!(status(None, "t", "localhost", 15).contains("history is disabled"));
+    assert!(status(Some(&History::new(2)), "t", "localhost", 15).contains("waiting for the first scrape"));
+    assert_eq!(escape(r#"a&"b'"#), "a&amp;&quot;b&#39;");
+}
@@ -0,0 +1,158 @@
+use std::io::prelude::*;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use super::error::{Error, ErrorKind, Result};
+use super::{parse_response, Response};
+
+// tests as sub module
+#[cfg(test)] // only add when running tests
+pub(crate) mod test;
+
+/// timeout for connecting, reading and writing
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// a parsed `http://` url
+#[derive(Clone, Debug, PartialEq)]
+pub struct Url {
+    /// host and port to connect to
+    pub host: String,
+
+    /// path and query, always starts with `/`
+    pub path: String,
+
+    /// user and password for basic auth
+    pub auth: Option<(String, String)>,
+}
+
+impl Url {
+    /// parses an url of the form `http://[user:password@]host[:port][/path]`
+    ///
+    /// The scheme can be omitted, the port defaults to 80.
+    pub fn parse(url: &str) -> Result<Self> {
+        let rest = if let Some(rest) = url.strip_prefix("http://") {
+            rest
+        } else if url.contains("://") {
+            return Err(Error::new(ErrorKind::FormatNotSupported));
+        } else {
+            url
+        };
+
+        let (authority, path) = match rest.find('/') {
+            Some(split) => rest.split_at(split),
+            None => (rest, "/"),
+        };
+
+        let (auth, host) = match authority.rfind('@') {
+            Some(split) => {
+                let (userinfo, host) = authority.split_at(split);
+                let auth = match userinfo.find(':') {
+                    Some(colon) => (userinfo[..colon].to_string(), userinfo[colon + 1..].to_string()),
+                    None => (userinfo.to_string(), String::new()),
+                };
+                (Some(auth), &host[1..])
+            }
+            None => (None, authority),
+        };
+
+        if host.is_empty() {
+            return Err(Error::new(ErrorKind::NotParsable(format!("no host in url {}", url))));
+        }
+
+        let host = if host.contains(':') && !host.ends_with(']') {
+            host.to_string()
+        } else {
+            format!("{}:80", host)
+        };
+
+        Ok(Url {
+            host,
+            path: path.to_string(),
+            auth,
+        })
+    }
+
+    /// returns a copy of the url with `segment` appended to the path
+    pub fn join(&self, segment: &str) -> Self {
+        let mut url = self.clone();
+        if !url.path.ends_with('/') {
+            url.path.push('/');
+        }
+        url.path.push_str(segment.trim_start_matches('/'));
+        url
+    }
+}
+
+/// sends a http request and returns the response
+///
+/// Responses with a status other than 2xx are returned as `ErrorKind::HttpStatus`.
+pub(crate) fn request(method: &str, url: &Url, headers: &[(&str, &str)], body: &[u8]) -> Result<Response> {
+    let addr = match url.host.to_socket_addrs()?.next() {
+        Some(addr) => addr,
+        None => return Err(Error::new(ErrorKind::IoAddrNotAvailable)),
+    };
+    let mut stream = TcpStream::connect_timeout(&addr, TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    let mut head = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: opentracker-exporter/{}\r\nConnection: close\r\nContent-Length: {}\r\n",
+        method,
+        url.path,
+        url.host,
+        env!("CARGO_PKG_VERSION"),
+        body.len()
+    );
+    if let Some((user, password)) = &url.auth {
+        head.push_str(&format!(
+            "Authorization: Basic {}\r\n",
+            base64(format!("{}:{}", user, password).as_bytes())
+        ));
+    }
+    for (key, value) in headers {
+        head.push_str(&format!("{}: {}\r\n", key, value));
+    }
+    head.push_str("\r\n");
+
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(body)?;
+    stream.flush()?;
+
+    let mut buffer = Vec::new();
+    stream.read_to_end(&mut buffer)?;
+
+    let response = parse_response(&String::from_utf8_lossy(buffer.as_slice()))?;
+    if response.status < 200 || response.status >= 300 {
+        return Err(Error::new(ErrorKind::HttpStatus(response.status)));
+    }
+    Ok(response)
+}
+
+/// encodes `data` as base64 with padding
+pub fn base64(data: &[u8]) -> String {
+    const TABLE: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut ret = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+
+        ret.push(TABLE[(n >> 18) as usize & 63] as char);
+        ret.push(TABLE[(n >> 12) as usize & 63] as char);
+        ret.push(if chunk.len() > 1 { TABLE[(n >> 6) as usize & 63] as char } else { '=' });
+        ret.push(if chunk.len() > 2 { TABLE[n as usize & 63] as char } else { '=' });
+    }
+    ret
+}
+
+/// percent encodes everything except unreserved characters, for use in a path segment
+pub fn encode_segment(segment: &str) -> String {
+    let mut ret = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => ret.push(byte as char),
+            _ => ret.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    ret
+}
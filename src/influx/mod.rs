@@ -0,0 +1,151 @@
+use std::net::UdpSocket;
+use std::time::UNIX_EPOCH;
+
+use super::collector::{Scrape, Sink};
+use super::error::Result;
+use super::http::{self, Url};
+
+// tests as sub module
+#[cfg(test)] // only add when running tests
+mod test;
+
+/// maximum payload of one datagram, fits into an ethernet frame
+const MAX_DATAGRAM: usize = 1432;
+
+/// maximum number of lines kept while the server is unreachable
+const MAX_QUEUE: usize = 100_000;
+
+/// Endpoint describes the influxdb to write to
+#[derive(Clone, Debug)]
+pub struct Endpoint {
+    /// write url, e.g. `http://influx:8086/write?db=tracker` or `udp://influx:8089`
+    pub url: String,
+
+    /// api token, sent as `Authorization: Token`
+    pub token: Option<String>,
+}
+
+impl Endpoint {
+    /// creates a new Endpoint for `url` without a token
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            token: None,
+        }
+    }
+}
+
+/// transport to the influxdb
+enum Transport {
+    Http(Url),
+    Udp(UdpSocket),
+}
+
+/// Influx is a sink sending the influxdb line protocol over http or udp
+///
+/// Every metric family becomes a measurement with the prometheus labels as tags.
+pub struct Influx {
+    endpoint: Endpoint,
+    transport: Transport,
+    prefix: String,
+    lines: Vec<String>,
+}
+
+impl Influx {
+    /// creates a new Influx for metrics named with `prefix`
+    pub fn new(endpoint: Endpoint, prefix: &str) -> Result<Self> {
+        let transport = match endpoint.url.strip_prefix("udp://") {
+            Some(addr) => {
+                let socket = UdpSocket::bind("0.0.0.0:0")?;
+                socket.connect(addr.trim_end_matches('/'))?;
+                Transport::Udp(socket)
+            }
+            None => Transport::Http(Url::parse(&endpoint.url)?),
+        };
+        Ok(Self {
+            endpoint,
+            transport,
+            prefix: prefix.to_string(),
+            lines: Vec::new(),
+        })
+    }
+}
+
+impl Sink for Influx {
+    fn name(&self) -> String {
+        format!("influxdb {}", self.endpoint.url)
+    }
+
+    fn collect(&mut self, scrape: &Scrape) {
+        let time = match scrape.time.duration_since(UNIX_EPOCH) {
+            Ok(time) => time.as_nanos(),
+            Err(_) => 0,
+        };
+
+        for family in scrape.data.families(&self.prefix, &scrape.name) {
+            let measurement = escape(&family.name, &[',', ' ']);
+            for sample in family.samples {
+                let mut line = measurement.clone();
+                for (key, value) in &sample.labels {
+                    if !value.is_empty() {
+                        line.push_str(&format!(
+                            ",{}={}",
+                            escape(key, &[',', '=', ' ']),
+                            escape(value, &[',', '=', ' '])
+                        ));
+                    }
+                }
+                line.push_str(&format!(" value={} {}\n", sample.value, time));
+                self.lines.push(line);
+            }
+        }
+
+        if self.lines.len() > MAX_QUEUE {
+            let dropped = self.lines.len() - MAX_QUEUE;
+            self.lines.drain(..dropped);
+            eprintln!("influxdb queue full, dropped {} lines", dropped);
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.lines.is_empty() {
+            return Ok(());
+        }
+
+        match &self.transport {
+            Transport::Http(url) => {
+                let authorization = self.endpoint.token.as_ref().map(|token| format!("Token {}", token));
+                let mut headers = vec![("Content-Type", "text/plain; charset=utf-8")];
+                if let Some(authorization) = &authorization {
+                    headers.push(("Authorization", authorization.as_str()));
+                }
+                http::request("POST", url, &headers, self.lines.concat().as_bytes())?;
+            }
+            Transport::Udp(socket) => {
+                let mut datagram = String::new();
+                for line in &self.lines {
+                    if !datagram.is_empty() && datagram.len() + line.len() > MAX_DATAGRAM {
+                        socket.send(datagram.as_bytes())?;
+                        datagram.clear();
+                    }
+                    datagram.push_str(line);
+                }
+                socket.send(datagram.as_bytes())?;
+            }
+        }
+        self.lines.clear();
+        Ok(())
+    }
+}
+
+/// escapes `special` characters with a backslash
+fn escape(value: &str, special: &[char]) -> String {
+    let mut ret = String::with_capacity(value.len());
+    for c in value.chars() {
+        if special.contains(&c) || c == '\\' {
+            ret.push('\\');
+        }
+        ret.push(c);
+    }
+    ret
+}
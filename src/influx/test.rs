@@ -0,0 +1,58 @@
+//! test file for the influxdb sink
+
+use super::{Endpoint, Influx};
+use crate::collector::{test, Scrape, Sink};
+use crate::http::test::stand_in;
+use std::net::UdpSocket;
+use std::time::Duration;
+
+fn scrape() -> Scrape {
+    test::scrape("tracker", 1, |data| {
+        data.tracker_id = 1337;
+        data.peers = 300;
+        data.http_error.insert(String::from("404 Not Found"), 2);
+    })
+}
+
+#[test]
+fn lines() {
+    let mut sink = Influx::new(Endpoint::new("http://localhost:8086/write?db=ot"), "ot").unwrap();
+    sink.collect(&scrape());
+    assert!(sink.lines.contains(&String::from(
+        "ot_count,tracker=1337,name=tracker,type=peers value=300 1000000000\n"
+    )));
+    assert!(sink.lines.contains(&String::from(
+        "ot_http_codes,tracker=1337,name=tracker,code=404\\ Not\\ Found value=2 1000000000\n"
+    )));
+}
+
+#[test]
+fn flush_http() {
+    let (addr, request) = stand_in("204 No Content");
+    let mut endpoint = Endpoint::new(&format!("http://{}/api/v2/write?bucket=ot", addr));
+    endpoint.token = Some(String::from("secret"));
+    let mut sink = Influx::new(endpoint, "ot").unwrap();
+    sink.collect(&scrape());
+    sink.flush().unwrap();
+
+    let request = request.recv_timeout(Duration::from_secs(5)).unwrap();
+    let request = String::from_utf8_lossy(&request);
+    assert!(request.starts_with("POST /api/v2/write?bucket=ot HTTP/1.1\r\n"));
+    assert!(request.contains("\r\nAuthorization: Token secret\r\n"));
+    assert!(request.contains("\r\n\r\not_uptime,tracker=1337,name=tracker value=0 1000000000\n"));
+}
+
+#[test]
+fn flush_udp() {
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+    server.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let url = format!("udp://{}", server.local_addr().unwrap());
+
+    let mut sink = Influx::new(Endpoint::new(&url), "ot").unwrap();
+    sink.collect(&scrape());
+    sink.flush().unwrap();
+
+    let mut buffer = [0; 1500];
+    let len = server.recv(&mut buffer).unwrap();
+    assert!(String::from_utf8_lossy(&buffer[..len]).starts_with("ot_uptime,tracker=1337,name=tracker value=0 1000000000\n"));
+}
@@ -1,10 +1,14 @@
 use std::io::prelude::*;
 use std::net::TcpListener;
-use std::net::TcpStream;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
 use std::process::exit;
 use std::vec::Vec;
 use std::collections::HashMap;
-use error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use error::{Error, ErrorKind};
+use metrics::{Family, Kind};
 
 /// thread library containing a thread pool
 pub mod threads;
@@ -12,6 +16,92 @@ pub mod threads;
 /// error library for error handling
 pub mod error;
 
+/// nagios check plugin with thresholds
+pub mod check;
+
+/// background collection feeding the sinks
+pub mod collector;
+
+/// non-blocking event loop for the clients of the prometheus endpoint
+#[cfg(unix)]
+mod event;
+
+/// coalescing of concurrent calls for the same key
+pub mod flight;
+
+/// graphite plaintext sink
+pub mod graphite;
+
+/// short term history and html status page
+pub mod history;
+
+/// minimal http client
+pub mod http;
+
+/// influxdb line protocol sink
+pub mod influx;
+
+/// helpers to write json
+pub mod json;
+
+/// sample model shared by all outputs
+pub mod metrics;
+
+/// mqtt sink with home assistant discovery
+pub mod mqtt;
+
+/// munin multigraph plugin mode
+pub mod munin;
+
+/// opentelemetry OTLP/HTTP sink
+pub mod otlp;
+
+/// minimal protobuf encoder
+pub mod protobuf;
+
+/// push mode for the prometheus pushgateway
+pub mod push;
+
+/// json and table output of a single scrape
+pub mod query;
+
+/// csv recording of the scrapes and export
+pub mod record;
+
+/// prometheus remote write sink
+pub mod remote_write;
+
+/// config file and validation for reloads
+pub mod reload;
+
+/// recording and replay of raw upstream responses
+pub mod replay;
+
+/// signal handling for graceful shutdown
+pub mod signal;
+
+/// simulated opentracker stats server
+pub mod simulate;
+
+/// snappy compression
+pub mod snappy;
+
+/// statsd and dogstatsd sink
+pub mod statsd;
+
+/// one shot output for the node_exporter textfile collector
+pub mod textfile;
+
+/// interactive terminal dashboard
+pub mod top;
+
+/// zabbix sender and low level discovery
+pub mod zabbix;
+
+// tests as sub module
+#[cfg(test)] // only add when running tests
+mod test;
+
 pub struct Config {
     /// verbosity level
     pub verbose: u8,
@@ -31,8 +121,89 @@ pub struct Config {
     /// number of thread in threadpool
     pub threads: usize,
 
+    /// grow the thread pool up to this many threads while requests wait
+    pub max_threads: Option<usize>,
+
+    /// seconds without waiting requests before an added thread is retired
+    pub scale_down: u64,
+
+    /// connections waiting for a thread before new ones are answered with 503, `None` is unlimited
+    pub queue: Option<usize>,
+
     /// human readable name of tracker
     pub name: String,
+
+    /// listen for prometheus, disabled in push mode
+    pub listen: bool,
+
+    /// seconds between two scrapes of the background collection
+    pub interval: u64,
+
+    /// pushgateway to push to
+    pub push: Option<push::Gateway>,
+
+    /// remote write receiver to send samples to
+    pub remote_write: Option<remote_write::Endpoint>,
+
+    /// statsd server to send metrics to
+    pub statsd: Option<statsd::Server>,
+
+    /// address of the carbon server to send graphite plaintext to
+    pub graphite: Option<String>,
+
+    /// influxdb to write the line protocol to
+    pub influx: Option<influx::Endpoint>,
+
+    /// opentelemetry collector to export to
+    pub otlp: Option<otlp::Endpoint>,
+
+    /// zabbix trapper to send items to
+    pub zabbix: Option<zabbix::Server>,
+
+    /// mqtt broker to publish stats to
+    pub mqtt: Option<mqtt::Broker>,
+
+    /// hours of scrapes to keep for `/status` and `/api/history`
+    pub history: Option<u64>,
+
+    /// directory to record every scrape to as csv
+    pub record: Option<std::path::PathBuf>,
+
+    /// days to keep recorded files for
+    pub record_keep: Option<u64>,
+
+    /// directory to store the raw upstream responses in
+    pub record_dir: Option<std::path::PathBuf>,
+
+    /// recorded responses used instead of the tracker
+    pub replay: Option<replay::Replay>,
+
+    /// seconds to wait for running requests on shutdown
+    pub drain: u64,
+
+    /// seconds to wait for the trackers of a `/probe` request
+    pub probe_timeout: u64,
+
+    /// trackers `/probe` may scrape, `/probe` is disabled if empty
+    pub probe_targets: Vec<String>,
+
+    /// seconds to wait for the tracker to connect and answer a scrape
+    pub scrape_timeout: u64,
+
+    /// seconds a scrape of a tracker is reused for other requests, 0 only shares running scrapes
+    pub min_scrape_interval: u64,
+
+    /// loads the config again on SIGHUP and `POST /-/reload`
+    pub reload: Option<reload::Loader>,
+
+    /// accept `POST /-/reload` from any client which can reach the exporter
+    pub reload_endpoint: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Config {
@@ -45,7 +216,54 @@ impl Config {
             interface: String::from("0.0.0.0"),
             prefix: String::from("opentracker"),
             threads: 8,
+            max_threads: None,
+            scale_down: 60,
+            queue: Some(128),
             name: String::from("tracker"),  //FIXME: set to hostname
+            listen: true,
+            interval: 15,
+            push: None,
+            remote_write: None,
+            statsd: None,
+            graphite: None,
+            influx: None,
+            otlp: None,
+            zabbix: None,
+            mqtt: None,
+            history: None,
+            record: None,
+            record_keep: None,
+            record_dir: None,
+            replay: None,
+            drain: 10,
+            probe_timeout: 10,
+            probe_targets: Vec::new(),
+            scrape_timeout: 10,
+            min_scrape_interval: 0,
+            reload: None,
+            reload_endpoint: false,
+        }
+    }
+
+    /// returns the autoscale policy of the thread pool, `None` for a fixed size
+    pub fn autoscale(&self) -> Option<threads::Autoscale> {
+        match self.max_threads {
+            Some(max) if max > self.threads => Some(threads::Autoscale {
+                min: self.threads,
+                max,
+                idle: Duration::from_secs(self.scale_down),
+            }),
+            _ => None,
+        }
+    }
+
+    /// returns where the stats are read from
+    pub fn upstream(&self) -> Upstream {
+        Upstream {
+            url: self.url.clone(),
+            record_dir: self.record_dir.clone(),
+            replay: self.replay.clone(),
+            timeout: Duration::from_secs(self.scrape_timeout),
         }
     }
 
@@ -60,9 +278,38 @@ impl Config {
             println!("Debug1: metrics are calle {}_*", self.prefix);
         }
 
+        signal::install();
+
+        let mut sinks = self.sinks().map_err(|err| err.to_string())?;
+        let history = self.history.map(|hours| history::History::for_hours(hours, self.interval));
+        if let Some(history) = &history {
+            sinks.push(Box::new(history.clone()));
+        }
+        // the collection is restarted with the sinks of the new config on reload
+        let collector = if sinks.is_empty() {
+            None
+        } else {
+            Some(collector::start(&self, sinks))
+        };
+        if !self.listen {
+            let fixed = reload::Fixed::of(&self);
+            let mut context = Arc::new(Context::new(&self, history));
+            let mut collector = collector;
+            while !signal::shutdown_requested() {
+                if signal::take_reload() {
+                    let _ = reload_collection(&self.reload, &fixed, &mut context, &mut collector);
+                }
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            if let Some(collector) = collector {
+                collector.stop();
+            }
+            return Ok(());
+        }
+
         // create threadPool
         let mut thread_pool = threads::ThreadPool::new(self.threads).unwrap_or_else(|err| {
-            eprintln!("Error creating threadPool: {}", err.to_string());
+            eprintln!("Error creating threadPool: {}", err);
             exit(-2);
         });
 
@@ -70,6 +317,13 @@ impl Config {
             println!("Debug2: enabling threadPool verbose mode");
             thread_pool.set_verbose_mode(true);
         }
+        thread_pool.set_queue_size(self.queue);
+        thread_pool.set_autoscale(self.autoscale());
+        let thread_pool = Arc::new(thread_pool);
+
+        // a separate pool for the scrapes of the trackers, waiting on the request pool could deadlock
+        #[cfg(not(unix))]
+        let scrapes = threads::ThreadPool::new(self.threads).map_err(|err| err.to_string())?;
 
         // open port
         let listener = TcpListener::bind(format!("{}:{}", self.interface, self.port))
@@ -78,59 +332,676 @@ impl Config {
                 exit(-3);
             });
 
-        // handle connection
-        for stream in listener.incoming() {
-            let stream = match stream {
-                Ok(stream) => stream,
-                Err(err) => {
-                    eprintln!("error createing stream: {}", err);
-                    continue;
+        let server = Arc::new(Server {
+            context: Mutex::new(Arc::new(Context::new(&self, history))),
+            loader: self.reload.clone(),
+            fixed: reload::Fixed::of(&self),
+            reloaded: AtomicBool::new(true),
+            collector: Mutex::new(collector),
+            pool: Arc::clone(&thread_pool),
+            #[cfg(not(unix))]
+            scrapes,
+            flights: Arc::new(flight::Group::new(Duration::from_secs(self.min_scrape_interval))),
+        });
+
+        watch_signals(Arc::clone(&server));
+
+        // handle connections until a shutdown was requested
+        #[cfg(unix)]
+        {
+            let answer_server = Arc::clone(&server);
+            event::serve(
+                listener,
+                &thread_pool,
+                Duration::from_secs(self.drain),
+                self.verbose,
+                move |request| evented(answer(request, &answer_server), &answer_server),
+                signal::shutdown_requested,
+            )
+            .map_err(|err| err.to_string())?;
+        }
+        #[cfg(not(unix))]
+        accept(listener, &thread_pool, &server, self.verbose);
+
+        // let the running requests finish
+        if self.verbose >= 1 {
+            println!("Debug1: shutting down, waiting up to {}s for running requests", self.drain);
+        }
+        let stuck = thread_pool.shutdown(Duration::from_secs(self.drain));
+        if stuck > 0 {
+            eprintln!("{} requests still running after {}s, exiting anyway", stuck, self.drain);
+        }
+        Ok(())
+    }
+
+    /// creates the sinks for the background collection
+    fn sinks(&self) -> Result<Vec<Box<dyn collector::Sink>>, Error> {
+        let mut sinks: Vec<Box<dyn collector::Sink>> = Vec::new();
+        if let Some(gateway) = &self.push {
+            sinks.push(Box::new(push::Pusher::new(gateway.clone(), &self.prefix)?));
+        }
+        if let Some(endpoint) = &self.remote_write {
+            sinks.push(Box::new(remote_write::RemoteWrite::new(endpoint.clone(), &self.prefix)?));
+        }
+        if let Some(server) = &self.statsd {
+            sinks.push(Box::new(statsd::Statsd::new(server.clone(), &self.prefix)?));
+        }
+        if let Some(addr) = &self.graphite {
+            sinks.push(Box::new(graphite::Graphite::new(addr, &self.prefix)));
+        }
+        if let Some(endpoint) = &self.influx {
+            sinks.push(Box::new(influx::Influx::new(endpoint.clone(), &self.prefix)?));
+        }
+        if let Some(endpoint) = &self.otlp {
+            sinks.push(Box::new(otlp::Otlp::new(endpoint.clone(), &self.prefix)?));
+        }
+        if let Some(server) = &self.zabbix {
+            sinks.push(Box::new(zabbix::Zabbix::new(server.clone(), &self.prefix)));
+        }
+        if let Some(broker) = &self.mqtt {
+            sinks.push(Box::new(mqtt::Mqtt::new(broker.clone())));
+        }
+        if let Some(dir) = &self.record {
+            sinks.push(Box::new(record::Recorder::new(dir, self.record_keep)?));
+        }
+        Ok(sinks)
+    }
+}
+
+/// loads and validates the config again and restarts the background collection with it
+///
+/// `context` and `collector` are only replaced if the config is valid and all its sinks
+/// could be created, the old ones stay in use on error. Returns the new config.
+fn reload_collection(
+    loader: &Option<reload::Loader>,
+    fixed: &reload::Fixed,
+    context: &mut Arc<Context>,
+    collector: &mut Option<collector::Collector>,
+) -> Result<Config, String> {
+    let result = match loader {
+        Some(loader) => loader().and_then(|conf| reload::validate(&conf).map(|_| conf)),
+        None => Err(String::from("no configuration to reload")),
+    };
+    // the sinks are created first, an invalid sink keeps the old config
+    let (conf, mut sinks) = result
+        .and_then(|conf| conf.sinks().map(|sinks| (conf, sinks)).map_err(|err| err.to_string()))
+        .map_err(|err| {
+            eprintln!("reload failed, keeping the old configuration: {}", err);
+            err
+        })?;
+    for setting in fixed.changes(&conf) {
+        eprintln!("changing the {} requires a restart", setting);
+    }
+
+    // the scrapes in the history only fit if the same tracker is collected the same way
+    let history = if context.collects_like(&conf) {
+        context.history.clone()
+    } else {
+        conf.history.map(|hours| history::History::for_hours(hours, conf.interval))
+    };
+    if let Some(history) = &history {
+        sinks.push(Box::new(history.clone()));
+    }
+    if let Some(old) = collector.take() {
+        old.stop();
+    }
+    if !sinks.is_empty() {
+        *collector = Some(collector::start(&conf, sinks));
+    }
+    *context = Arc::new(Context::new(&conf, history));
+    if conf.verbose >= 1 {
+        println!("Debug1: configuration reloaded");
+    }
+    Ok(conf)
+}
+
+/// reloads `server` on SIGHUP until a shutdown was requested
+fn watch_signals(server: Arc<Server>) {
+    std::thread::spawn(move || {
+        while !signal::shutdown_requested() {
+            if signal::take_reload() {
+                let _ = server.reload();
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    });
+}
+
+/// accepts connections and handles each on the thread pool until a shutdown was requested
+///
+/// Used where the event loop is not available.
+#[cfg(not(unix))]
+fn accept(listener: TcpListener, thread_pool: &threads::ThreadPool, server: &Arc<Server>, verbose: u8) {
+    wake_on_shutdown(&listener);
+    for stream in listener.incoming() {
+        if signal::shutdown_requested() {
+            break;
+        }
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                eprintln!("error createing stream: {}", err);
+                continue;
+            }
+        };
+
+        let server = Arc::clone(server);
+
+        // move stream to thread
+        let queued = thread_pool.execute_with(stream, move |stream| {
+            handle(stream, &server).unwrap_or_else(|err| {
+                if verbose >= 2 {
+                    println!("Debug2: error hanling client: {}", err);
                 }
-            };
-
-            let verbose = self.verbose;
-            let url = self.url.clone();
-            let prefix = self.prefix.clone();
-            let name = self.name.clone();
-
-            // move stream to thread
-            thread_pool.execute(move || {
-                handle(stream, verbose, url, &prefix, &name).unwrap_or_else(|err| {
-                    if verbose >= 2 {
-                        println!("Debug2: error hanling client: {}", err);
-                        ()
-                    }
-                });
-            }).unwrap_or_else(|err| {
-                eprintln!("faild to execute thread: {}", err);
-                ()
             });
+        });
+        if let Err(mut stream) = queued {
+            if verbose >= 2 {
+                println!("Debug2: queue is full, rejecting client");
+            }
+            // take what already arrived, closing with unread data would reset the connection
+            let _ = stream.set_nonblocking(true);
+            let _ = stream.read(&mut [0; 512]);
+            let _ = stream.write_all(&busy());
+        }
+    }
+}
+
+/// wakes the blocking accept of `listener` by connecting to it once a shutdown was requested
+#[cfg(not(unix))]
+fn wake_on_shutdown(listener: &TcpListener) {
+    let mut addr = match listener.local_addr() {
+        Ok(addr) => addr,
+        Err(_) => return,
+    };
+    if addr.ip().is_unspecified() {
+        let loopback = if addr.is_ipv4() {
+            std::net::Ipv4Addr::LOCALHOST.into()
+        } else {
+            std::net::Ipv6Addr::LOCALHOST.into()
+        };
+        addr.set_ip(loopback);
+    }
+    std::thread::spawn(move || {
+        while !signal::shutdown_requested() {
+            std::thread::sleep(Duration::from_millis(100));
         }
+        let _ = TcpStream::connect_timeout(&addr, Duration::from_secs(1));
+    });
+}
+
+/// Server holds the context used for new requests and replaces it on reload
+struct Server {
+    context: Mutex<Arc<Context>>,
+    loader: Option<reload::Loader>,
+    fixed: reload::Fixed,
+    reloaded: AtomicBool,
+    collector: Mutex<Option<collector::Collector>>,
+    pool: Arc<threads::ThreadPool>,
+    #[cfg(not(unix))]
+    scrapes: threads::ThreadPool,
+    flights: Arc<flight::Group<Arc<Result<Everything, Error>>>>,
+}
+
+impl Server {
+    /// returns the current context, requests keep theirs across a reload
+    fn context(&self) -> Arc<Context> {
+        let context = self.context.lock().unwrap_or_else(|err| err.into_inner());
+        Arc::clone(&context)
+    }
+
+    /// loads and validates the config again, the old one stays in use on error
+    fn reload(&self) -> Result<(), String> {
+        let mut context = self.context.lock().unwrap_or_else(|err| err.into_inner());
+        let mut collector = self.collector.lock().unwrap_or_else(|err| err.into_inner());
+        let result = reload_collection(&self.loader, &self.fixed, &mut context, &mut collector);
+        self.reloaded.store(result.is_ok(), Ordering::SeqCst);
+
+        let conf = result?;
+        self.pool.resize(conf.threads).map_err(|err| err.to_string())?;
+        self.pool.set_autoscale(conf.autoscale());
+        #[cfg(not(unix))]
+        self.scrapes.resize(conf.threads).map_err(|err| err.to_string())?;
+        self.flights.set_max_age(Duration::from_secs(conf.min_scrape_interval));
         Ok(())
     }
+
+    /// scrapes all `upstreams` in parallel, trackers not answering within their timeout are reported as failed
+    #[cfg(not(unix))]
+    fn scrape_all(&self, upstreams: &[Upstream]) -> Vec<Arc<Result<Everything, Error>>> {
+        let timeout = upstreams.iter().map(|upstream| upstream.timeout).max().unwrap_or_default();
+        let deadline = Instant::now() + timeout;
+        let handles: Vec<_> = upstreams
+            .iter()
+            .map(|upstream| {
+                let (flights, upstream) = (Arc::clone(&self.flights), upstream.clone());
+                self.scrapes.execute_with_handle(move || coalesced_scrape(&flights, &upstream))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .and_then(|handle| handle.join_timeout(deadline.saturating_duration_since(Instant::now())))
+                    .unwrap_or_else(|err| Arc::new(Err(err)))
+            })
+            .collect()
+    }
+
+    /// returns the metrics about the exporter itself
+    fn families(&self, prefix: &str) -> Vec<Family> {
+        let mut reload = Family::new(
+            format!("{}_exporter_config_last_reload_successful", prefix),
+            "whether the last configuration reload succeeded",
+            Kind::Gauge,
+        );
+        reload.push(&[], if self.reloaded.load(Ordering::SeqCst) { 1.0 } else { 0.0 });
+
+        let mut families = vec![reload];
+        families.append(&mut pool_families(&self.pool.stats(), prefix));
+        families.append(&mut flight_families(&self.flights.stats(), prefix));
+        families
+    }
+}
+
+/// returns the metrics of the probed `targets` from their `scrapes`, in the same order
+fn probe(context: &Context, targets: &[String], scrapes: &[Arc<Result<Everything, Error>>]) -> String {
+    let mut families = Vec::new();
+    let mut success = Family::new(
+        format!("{}_probe_success", context.prefix),
+        "whether the tracker answered within the probe timeout",
+        Kind::Gauge,
+    );
+    for (target, data) in targets.iter().zip(scrapes) {
+        match data.as_ref() {
+            Ok(data) => {
+                merge_families(&mut families, data.families(&context.prefix, target));
+                success.push(&[("name", target)], 1.0);
+            }
+            Err(err) => {
+                if context.verbose >= 2 {
+                    println!("Debug2: probing {} failed: {}", target, err.kind());
+                }
+                success.push(&[("name", target)], 0.0);
+            }
+        }
+    }
+    families.push(success);
+    metrics::render(&families)
+}
+
+/// adds the samples of `new` to the families of the same name in `families`
+fn merge_families(families: &mut Vec<Family>, new: Vec<Family>) {
+    for family in new {
+        match families.iter_mut().find(|existing| existing.name == family.name) {
+            Some(existing) => existing.samples.extend(family.samples),
+            None => families.push(family),
+        }
+    }
+}
+
+/// returns the metrics of the thread pool handling the requests
+fn pool_families(stats: &threads::Stats, prefix: &str) -> Vec<Family> {
+    let name = |metric| format!("{}_exporter_pool_{}", prefix, metric);
+
+    let mut workers = Family::new(name("workers"), "threads handling requests by state", Kind::Gauge);
+    workers.push(&[("state", "busy")], stats.busy as f64);
+    workers.push(&[("state", "idle")], stats.idle() as f64);
+
+    let mut queued = Family::new(name("queue_depth"), "requests waiting for a free thread", Kind::Gauge);
+    queued.push(&[], stats.queued as f64);
+
+    let mut executed = Family::new(name("jobs_total"), "requests handled by the threads", Kind::Counter);
+    executed.push(&[], stats.executed as f64);
+
+    let mut duration = Family::new(
+        name("job_duration_seconds"),
+        "time the threads spent on a request",
+        Kind::Histogram,
+    );
+    duration.push_histogram(&[], &stats.durations, stats.duration_sum, stats.executed as u64);
+
+    let mut panics = Family::new(name("panics_total"), "requests whose handler panicked", Kind::Counter);
+    panics.push(&[], stats.panics as f64);
+
+    let mut rejected = Family::new(
+        name("rejected_total"),
+        "requests answered with 503 because the queue was full",
+        Kind::Counter,
+    );
+    rejected.push(&[], stats.rejected as f64);
+
+    vec![workers, queued, executed, duration, panics, rejected]
+}
+
+/// returns the metrics of the scrapes shared between requests
+fn flight_families(stats: &flight::Stats, prefix: &str) -> Vec<Family> {
+    let mut scrapes = Family::new(
+        format!("{}_exporter_upstream_scrapes_total", prefix),
+        "scrapes of the trackers made for requests",
+        Kind::Counter,
+    );
+    scrapes.push(&[], stats.calls as f64);
+
+    let mut coalesced = Family::new(
+        format!("{}_exporter_scrapes_coalesced_total", prefix),
+        "requests answered with the scrape of another request",
+        Kind::Counter,
+    );
+    coalesced.push(&[("reason", "in_flight")], stats.in_flight as f64);
+    coalesced.push(&[("reason", "recent")], stats.recent as f64);
+
+    vec![scrapes, coalesced]
+}
+
+/// Context is the state shared by all connection handlers
+struct Context {
+    verbose: u8,
+    upstream: Upstream,
+    prefix: String,
+    name: String,
+    interval: u64,
+    probe_timeout: u64,
+    probe_targets: Vec<String>,
+    reload_endpoint: bool,
+    history_hours: Option<u64>,
+    history: Option<history::History>,
+}
+
+impl Context {
+    fn new(conf: &Config, history: Option<history::History>) -> Self {
+        Self {
+            verbose: conf.verbose,
+            upstream: conf.upstream(),
+            prefix: conf.prefix.clone(),
+            name: conf.name.clone(),
+            interval: conf.interval,
+            probe_timeout: conf.probe_timeout,
+            probe_targets: conf.probe_targets.clone(),
+            reload_endpoint: conf.reload_endpoint,
+            history_hours: conf.history,
+            history,
+        }
+    }
+
+    /// returns true if `conf` scrapes the same tracker under the same name into the same history
+    fn collects_like(&self, conf: &Config) -> bool {
+        self.upstream.url == conf.url
+            && self.name == conf.name
+            && self.interval == conf.interval
+            && self.history_hours == conf.history
+    }
 }
 
 /// function for processing of prometheus client
-fn handle(mut stream: TcpStream, verbose: u8, url: String, prefix: &str, name: &str) -> Result<(), Error> {
-    if verbose >= 3 {
+///
+/// Used where the event loop is not available.
+#[cfg(not(unix))]
+fn handle(mut stream: TcpStream, server: &Arc<Server>) -> Result<(), Error> {
+    // a silent client must not hold up the shutdown forever
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let mut buffer = [0; 512];
+
+    let amount = stream.read(&mut buffer)?;
+
+    let response = match answer(&String::from_utf8_lossy(&buffer[..amount]), server) {
+        Answer::Done(response) => response,
+        Answer::Scrape(upstreams, then) => then(server.scrape_all(&upstreams)),
+    };
+    stream.write_all(&response)?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// Answer is the response to a request, or the scrapes of the trackers it needs first
+enum Answer {
+    /// the complete response
+    Done(Vec<u8>),
+
+    /// the trackers to scrape and how to build the response from their scrapes
+    Scrape(Vec<Upstream>, Respond),
+}
+
+/// Respond builds a response from the scrapes of the trackers, in the order they were asked for
+type Respond = Box<dyn FnOnce(Vec<Arc<Result<Everything, Error>>>) -> Vec<u8> + Send>;
+
+/// answers a request to the exporter
+fn answer(request: &str, server: &Arc<Server>) -> Answer {
+    let context = server.context();
+    if context.verbose >= 3 {
         println!("Debug3: Connection established!");
     }
-    let mut buffer = [0; 512];
 
-    stream.read(&mut buffer)?;
+    //println!("Request: {}", request);
+
+    let (path, query) = request_target(request);
+    match path.as_str() {
+        "/-/reload" => {
+            if !context.reload_endpoint {
+                return Answer::Done(response(
+                    "403 Forbidden",
+                    &[],
+                    "text/plain",
+                    "reloading over http is disabled, use --web-enable-reload or SIGHUP\n",
+                ));
+            }
+            if !request.starts_with("POST ") {
+                return Answer::Done(response(
+                    "405 Method Not Allowed",
+                    &[("Allow", "POST")],
+                    "text/plain",
+                    "only POST is allowed\n",
+                ));
+            }
+            Answer::Done(match server.reload() {
+                Ok(()) => response("200 OK", &[], "text/plain", "configuration reloaded\n"),
+                Err(err) => response("500 Internal Server Error", &[], "text/plain", &format!("{}\n", err)),
+            })
+        }
+        "/probe" => {
+            let targets = query_params(&query, "target");
+            if targets.is_empty() {
+                return Answer::Done(response("400 Bad Request", &[], "text/plain", "target parameter is missing\n"));
+            }
+            if let Some(target) = forbidden_target(&targets, &context.probe_targets) {
+                let content = format!("probing {} is not allowed, see --probe-target\n", target);
+                return Answer::Done(response("403 Forbidden", &[], "text/plain", &content));
+            }
+            let upstreams = targets
+                .iter()
+                .map(|target| Upstream {
+                    url: target.clone(),
+                    record_dir: None,
+                    replay: None,
+                    timeout: Duration::from_secs(context.probe_timeout),
+                })
+                .collect();
+            Answer::Scrape(
+                upstreams,
+                Box::new(move |scrapes| {
+                    let content = probe(&context, &targets, &scrapes);
+                    response("200 OK", &[], "text/plain; version=0.0.4", &content)
+                }),
+            )
+        }
+        "/stats.json" => {
+            let (time, start) = (SystemTime::now(), Instant::now());
+            Answer::Scrape(
+                vec![context.upstream.clone()],
+                Box::new(move |mut scrapes| {
+                    let data = scrapes.remove(0);
+                    let report = query::Report::scraped(&context.upstream, &context.name, time, start.elapsed(), data);
+                    let status = if report.success() { "200 OK" } else { "502 Bad Gateway" };
+                    response(status, &[], "application/json", &report.json())
+                }),
+            )
+        }
+        "/status" => {
+            let page = history::status(context.history.as_ref(), &context.name, &context.upstream.url, context.interval);
+            Answer::Done(response("200 OK", &[], "text/html; charset=utf-8", &page))
+        }
+        "/api/history" => Answer::Done(match &context.history {
+            Some(history) => {
+                let (status, body) = history::api(history, &query);
+                response(status, &[], "application/json", &body)
+            }
+            None => response("404 Not Found", &[], "application/json", r#"{"error":"history is disabled"}"#),
+        }),
+        _ => {
+            let server = Arc::clone(server);
+            Answer::Scrape(
+                vec![context.upstream.clone()],
+                Box::new(move |mut scrapes| match get_content(&scrapes.remove(0), &context.prefix, &context.name) {
+                    Ok(mut content) => {
+                        content.push_str(&metrics::render(&server.families(&context.prefix)));
+                        response("200 OK", &[], "text/plain; version=0.0.4", &content)
+                    }
+                    Err(err) => response("500 Internal Server Error", &[], "text/plain", &format!("{}\n", err)),
+                }),
+            )
+        }
+    }
+}
+
+/// hands the scrapes `answer` needs to the event loop, only parsing them is left for the ThreadPool
+///
+/// Scrapes younger than the min scrape interval and recorded responses are used without a fetch.
+#[cfg(unix)]
+fn evented(answer: Answer, server: &Arc<Server>) -> event::Answer {
+    let (upstreams, then) = match answer {
+        Answer::Done(response) => return event::Answer::Done(response),
+        Answer::Scrape(upstreams, then) => (upstreams, then),
+    };
+    let mut known = Vec::with_capacity(upstreams.len());
+    let mut fetches = Vec::new();
+    for upstream in &upstreams {
+        known.push(match server.flights.recent(&upstream.url) {
+            Some(data) => Some(data),
+            None if upstream.replay.is_some() => Some(Arc::new(scrape(upstream))),
+            None => match resolve(&upstream.url) {
+                Ok(addr) => {
+                    fetches.push(event::Fetch {
+                        key: upstream.url.clone(),
+                        addr,
+                        request: request(&upstream.url, "everything"),
+                        timeout: upstream.timeout,
+                    });
+                    None
+                }
+                Err(err) => Some(Arc::new(Err(err))),
+            },
+        });
+    }
+    if fetches.is_empty() {
+        return event::Answer::Done(then(known.into_iter().flatten().collect()));
+    }
+
+    let flights = Arc::clone(&server.flights);
+    event::Answer::Fetch(
+        fetches,
+        Box::new(move |fetched| {
+            let mut fetched = fetched.into_iter();
+            let scrapes = known
+                .into_iter()
+                .zip(&upstreams)
+                .map(|(data, upstream)| match (data, fetched.next()) {
+                    (Some(data), _) => data,
+                    (None, Some(fetched)) => {
+                        let data = Arc::new(parse_fetched(upstream, &fetched));
+                        flights.complete(&upstream.url, Arc::clone(&data), fetched.shared);
+                        data
+                    }
+                    (None, None) => Arc::new(Err(Error::new(ErrorKind::JobTimedOut))),
+                })
+                .collect();
+            event::Answer::Done(then(scrapes))
+        }),
+    )
+}
 
-    //println!("Request: {}", String::from_utf8_lossy(&buffer[..]));
+/// returns the first of `targets` which is not in `allowed`
+///
+/// Probing makes the exporter connect to the target, so only configured trackers are allowed.
+fn forbidden_target<'a>(targets: &'a [String], allowed: &[String]) -> Option<&'a String> {
+    targets.iter().find(|target| !allowed.contains(target))
+}
 
-    let content = get_content(url, prefix, name)?;
+/// returns the response for clients which cannot be handled because all threads are busy
+fn busy() -> Vec<u8> {
+    response(
+        "503 Service Unavailable",
+        &[("Retry-After", "1")],
+        "text/plain",
+        "all threads are busy, try again later\n",
+    )
+}
 
-    stream.write(format!(
-        "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: {}\r\nContent-Type: text/plain; version=0.0.4\r\nDate: {}\r\n\r\n{}",
+/// returns a complete response, the connection is closed afterwards
+fn response(status: &str, headers: &[(&str, &str)], content_type: &str, content: &str) -> Vec<u8> {
+    let headers: String = headers.iter().map(|(name, value)| format!("{}: {}\r\n", name, value)).collect();
+    format!(
+        "HTTP/1.1 {}\r\nConnection: close\r\n{}Content-Length: {}\r\nContent-Type: {}\r\nDate: {}\r\n\r\n{}",
+        status,
+        headers,
         content.len(),
+        content_type,
         httpdate::fmt_http_date(std::time::SystemTime::now()),
         content
-    ).as_bytes())?;
-    stream.flush()?;
-    Ok(())
+    )
+    .into_bytes()
+}
+
+/// returns the path and the query of the request line
+fn request_target(request: &str) -> (String, String) {
+    let target = request.split_whitespace().nth(1).unwrap_or("/");
+    match target.find('?') {
+        Some(split) => (target[..split].to_string(), target[split + 1..].to_string()),
+        None => (target.to_string(), String::new()),
+    }
+}
+
+/// returns the percent decoded value of `key` in a query string
+fn query_param(query: &str, key: &str) -> Option<String> {
+    query_params(query, key).into_iter().next()
+}
+
+/// returns all percent decoded values of `key` in a query string
+fn query_params(query: &str, key: &str) -> Vec<String> {
+    query
+        .split('&')
+        .filter_map(|pair| {
+            let mut split = pair.splitn(2, '=');
+            if split.next()? != key {
+                return None;
+            }
+            Some(percent_decode(split.next().unwrap_or("")))
+        })
+        .collect()
+}
+
+/// decodes `%XX` escapes and `+` of a query value
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut ret = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => ret.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(byte) => {
+                        ret.push(byte);
+                        i += 2;
+                    }
+                    None => ret.push(b'%'),
+                }
+            }
+            byte => ret.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&ret).to_string()
 }
 
 // HTTP/1.1 200 OK
@@ -194,6 +1065,8 @@ struct Everything {
     connections: Connections,
     http_error: HashMap<String, usize>,
     mutex_stall: usize,
+    /// seconds the tracker clock is ahead of ours, None if no Date was sent
+    clock_skew: Option<i64>,
 }
 
 impl Everything {
@@ -208,63 +1081,116 @@ impl Everything {
             connections: Connections::new(),
             http_error: HashMap::new(),
             mutex_stall: 0,
+            clock_skew: None,
         }
     }
-    pub fn get_string(&self, prefix: &str, name: &str) -> String {
-        let mut ret = String::new();
-        ret.push_str(&format!(r#"# HELP {}_uptime uptime of the tracker
-# TYPE {}_uptime gauge
-{}_uptime{{tracker="{}",name="{}"}} {}"#, prefix, prefix, prefix, self.tracker_id, name, self.uptime));
-        ret.push_str(&format!(r#"
-# HELP {}_torrents counts torrents on server
-# TYPE {}_torrents gauge
-{}_torrents{{tracker="{}",type="mutex",name="{}"}} {}
-{}_torrents{{tracker="{}",type="iterator",name="{}"}} {}"#, prefix, prefix, prefix, self.tracker_id, name,
-        self.torrents.mutex, prefix, self.tracker_id, name, self.torrents.iterator));
-        ret.push_str(&format!(r#"
-# HELP {}_count count for varios things
-# TYPE {}_count gauge
-{}_count{{tracker="{}",name="{}",type="peers"}} {}
-{}_count{{tracker="{}",name="{}",type="seeds"}} {}
-{}_count{{tracker="{}",name="{}",type="completed"}} {}
-{}_count{{tracker="{}",name="{}",type="mutex_stall"}} {}"#, prefix, prefix,
-        prefix, self.tracker_id, name, self.peers,
-        prefix, self.tracker_id, name, self.seeds,
-        prefix, self.tracker_id, name, self.completed,
-        prefix, self.tracker_id, name, self.mutex_stall));
-        ret.push_str(&format!(r#"
-# HELP {}_connections to the tracker
-# TYPE {}_connections gauge
-{}_connections{{tracker="{}",name="{}",protocol="tcp",type="accept"}} {}
-{}_connections{{tracker="{}",name="{}",protocol="tcp",type="announce"}} {}
-{}_connections{{tracker="{}",name="{}",protocol="tcp",type="scrape"}} {}
-{}_connections{{tracker="{}",name="{}",protocol="udp",type="overall"}} {}
-{}_connections{{tracker="{}",name="{}",protocol="udp",type="connect"}} {}
-{}_connections{{tracker="{}",name="{}",protocol="udp",type="announce"}} {}
-{}_connections{{tracker="{}",name="{}",protocol="udp",type="scrape"}} {}
-{}_connections{{tracker="{}",name="{}",protocol="udp",type="missmatch"}} {}
-{}_connections{{tracker="{}",name="{}",type="livesync"}} {}"#, prefix, prefix,
-        prefix, self.tracker_id, name, self.connections.tcp_accept,
-        prefix, self.tracker_id, name, self.connections.tcp_announce,
-        prefix, self.tracker_id, name, self.connections.tcp_scrape,
-        prefix, self.tracker_id, name, self.connections.udp_overall,
-        prefix, self.tracker_id, name, self.connections.udp_connect,
-        prefix, self.tracker_id, name, self.connections.udp_announce,
-        prefix, self.tracker_id, name, self.connections.udp_scrape,
-        prefix, self.tracker_id, name, self.connections.udp_missmatch,
-        prefix, self.tracker_id, name, self.connections.livesync));
+    /// returns the stats as metric families named with `prefix` and labeled with `name`
+    pub fn families(&self, prefix: &str, name: &str) -> Vec<Family> {
+        let tracker = self.tracker_id.to_string();
+        let tracker = tracker.as_str();
+        let mut ret = Vec::new();
 
-        // http codes
-        ret.push_str(&format!(r#"
-# HELP {}_http_codes http error code count
-# TYPE {}_http_codes gauge
-"#, prefix, prefix));
+        let mut uptime = Family::new(format!("{}_uptime", prefix), "uptime of the tracker", Kind::Gauge);
+        uptime.push(&[("tracker", tracker), ("name", name)], self.uptime as f64);
+        ret.push(uptime);
 
+        if let Some(skew) = self.clock_skew {
+            let mut clock_skew = Family::new(
+                format!("{}_clock_skew_seconds", prefix),
+                "seconds the tracker clock is ahead of the exporter clock",
+                Kind::Gauge,
+            );
+            clock_skew.push(&[("tracker", tracker), ("name", name)], skew as f64);
+            ret.push(clock_skew);
+        }
+
+        let mut torrents = Family::new(format!("{}_torrents", prefix), "counts torrents on server", Kind::Gauge);
+        torrents.push(&[("tracker", tracker), ("type", "mutex"), ("name", name)], self.torrents.mutex as f64);
+        torrents.push(&[("tracker", tracker), ("type", "iterator"), ("name", name)], self.torrents.iterator as f64);
+        ret.push(torrents);
+
+        let mut count = Family::new(format!("{}_count", prefix), "count for varios things", Kind::Gauge);
+        for (kind, value) in &[
+            ("peers", self.peers),
+            ("seeds", self.seeds),
+            ("completed", self.completed),
+            ("mutex_stall", self.mutex_stall),
+        ] {
+            count.push(&[("tracker", tracker), ("name", name), ("type", kind)], *value as f64);
+        }
+        ret.push(count);
+
+        let mut connections = Family::new(format!("{}_connections", prefix), "to the tracker", Kind::Gauge);
+        for (protocol, kind, value) in &[
+            ("tcp", "accept", self.connections.tcp_accept),
+            ("tcp", "announce", self.connections.tcp_announce),
+            ("tcp", "scrape", self.connections.tcp_scrape),
+            ("udp", "overall", self.connections.udp_overall),
+            ("udp", "connect", self.connections.udp_connect),
+            ("udp", "announce", self.connections.udp_announce),
+            ("udp", "scrape", self.connections.udp_scrape),
+            ("udp", "missmatch", self.connections.udp_missmatch),
+        ] {
+            connections.push(
+                &[("tracker", tracker), ("name", name), ("protocol", protocol), ("type", kind)],
+                *value as f64,
+            );
+        }
+        connections.push(
+            &[("tracker", tracker), ("name", name), ("type", "livesync")],
+            self.connections.livesync as f64,
+        );
+        ret.push(connections);
+
+        // http codes
+        let mut http_codes = Family::new(format!("{}_http_codes", prefix), "http error code count", Kind::Gauge);
         for (key, value) in &self.http_error {
-            ret.push_str(&format!(r#"{}_http_codes{{tracker="{}",name="{}",code="{}"}} {}
-"#, prefix, self.tracker_id, name, key, value));
+            http_codes.push(&[("tracker", tracker), ("name", name), ("code", key)], *value as f64);
         }
+        ret.push(http_codes);
+
+        ret
+    }
+
+    /// returns the current values by dotted path, for outputs without labels
+    pub(crate) fn gauges(&self) -> Vec<(String, f64)> {
+        let mut ret = vec![
+            (String::from("uptime"), self.uptime as f64),
+            (String::from("torrents.mutex"), self.torrents.mutex as f64),
+            (String::from("torrents.iterator"), self.torrents.iterator as f64),
+            (String::from("peers"), self.peers as f64),
+            (String::from("seeds"), self.seeds as f64),
+            (String::from("connections.livesync"), self.connections.livesync as f64),
+        ];
+        if let Some(skew) = self.clock_skew {
+            ret.push((String::from("clock_skew_seconds"), skew as f64));
+        }
+        ret
+    }
+
+    /// returns the values counting up since the tracker started by dotted path
+    pub(crate) fn counters(&self) -> Vec<(String, u64)> {
+        let mut ret = vec![
+            (String::from("completed"), self.completed as u64),
+            (String::from("mutex_stall"), self.mutex_stall as u64),
+            (String::from("connections.tcp.accept"), self.connections.tcp_accept as u64),
+            (String::from("connections.tcp.announce"), self.connections.tcp_announce as u64),
+            (String::from("connections.tcp.scrape"), self.connections.tcp_scrape as u64),
+            (String::from("connections.udp.overall"), self.connections.udp_overall as u64),
+            (String::from("connections.udp.connect"), self.connections.udp_connect as u64),
+            (String::from("connections.udp.announce"), self.connections.udp_announce as u64),
+            (String::from("connections.udp.scrape"), self.connections.udp_scrape as u64),
+            (String::from("connections.udp.missmatch"), self.connections.udp_missmatch as u64),
+        ];
+        for (code, count) in &self.http_error {
+            ret.push((format!("http_codes.{}", sanitize(code)), *count as u64));
+        }
+        ret
+    }
 
+    /// renders the stats in the prometheus text format
+    pub fn get_string(&self, prefix: &str, name: &str) -> String {
+        let mut ret = metrics::render(&self.families(prefix, name));
         ret.push_str(&format!(r#"
 # opentracker/export_prometheus {}
 "#, env!("CARGO_PKG_VERSION") ));
@@ -272,100 +1198,318 @@ impl Everything {
     }
 }
 
-fn get_content(url: String, prefix: &str, name: &str) -> Result<String, Error> {
-    let mut tracker_data = Everything::new();
-    // get mode=everything
-    if let Ok(mut stream) = TcpStream::connect(&url) {
-        stream.write(format!(
-            "GET /stats?mode=everything HTTP/1.1\r\nHost: {}\r\nUser-Agent: opentracker-exporter/{}\r\nAccept: text/plain\r\n\r\n",
-            url,
-            env!("CARGO_PKG_VERSION")
-        ).as_bytes())?;
-        stream.flush().unwrap_or_else(|_| ()); // discard errors
-
-        let mut buffer = Vec::new();
-        stream.read_to_end(&mut buffer)?;
-
-        let buffer = String::from_utf8_lossy(buffer.as_slice());
-        let (_, buffer) = buffer.split_at(buffer.find("\r\n\r\n").unwrap()); // fixme
-        let buffer = buffer.trim().to_string();
-
-        use xml::reader::{XmlEvent};
-
-        let parser = xml::reader::EventReader::from_str(&buffer);
-        let mut outer_name = String::new();
-        let mut inner_name = String::new();
-        let mut http_code = String::new();
-
-        //let mut depth = 0;
-        for e in parser {
-            match e {
-                Ok(XmlEvent::StartElement { name, attributes, .. }) => {
-                    //println!("{}+{}", indent(depth), name);
-                    //depth += 1;
-
-                    let name = name.to_string();
-
-                    if name == "count" && outer_name == "http_error" {
-                        if attributes.len() == 1 {
-                            http_code = attributes[0].value.to_string();
-                        }
-                    }
+fn get_content(tracker_data: &Result<Everything, Error>, prefix: &str, name: &str) -> Result<String, Error> {
+    match tracker_data {
+        Ok(tracker_data) => Ok(tracker_data.get_string(prefix, name)),
+        Err(_) => Ok(Everything::new().get_string(prefix, name)),
+    }
+}
 
-                    if name == "count" || name == "accept" || name == "announce" || name == "scrape" || name == "overall" || name == "connect" || name == "missmatch" {
-                        inner_name = name;
-                    } else {
-                        outer_name = name;
-                    }
-                },
-                Ok(XmlEvent::EndElement { name: _ }) => {
-                    //depth -= 1;
-                    //println!("{}-{}", indent(depth), name);
-                },
-                Ok(XmlEvent::Characters(data)) => {
-                    //println!("{}chars: {}", indent(depth), data);
-                    if outer_name == "tracker_id" {
-                        tracker_data.tracker_id = data.parse().unwrap_or_else(|_| 0);
-                    } else if outer_name == "uptime" {
-                        tracker_data.uptime = data.parse().unwrap_or_else(|_| 0);
-                    } else if outer_name == "count_mutex" {
-                        tracker_data.torrents.mutex = data.parse().unwrap_or_else(|_| 0);
-                    } else if outer_name == "count_iterator" {
-                        tracker_data.torrents.iterator = data.parse().unwrap_or_else(|_| 0);
-                    } else if outer_name == "peers" && inner_name == "count" {
-                        tracker_data.peers = data.parse().unwrap_or_else(|_| 0);
-                    } else if outer_name == "seeds" && inner_name == "count" {
-                        tracker_data.seeds = data.parse().unwrap_or_else(|_| 0);
-                    } else if outer_name == "completed" && inner_name == "count" {
-                        tracker_data.completed = data.parse().unwrap_or_else(|_| 0);
-                    } else if outer_name == "mutex_stall" && inner_name == "count" {
-                        tracker_data.mutex_stall = data.parse().unwrap_or_else(|_| 0);
-                    } else if outer_name == "tcp" && inner_name == "accept" {
-                        tracker_data.connections.tcp_accept = data.parse().unwrap_or_else(|_| 0);
-                    } else if outer_name == "tcp" && inner_name == "announce" {
-                        tracker_data.connections.tcp_announce = data.parse().unwrap_or_else(|_| 0);
-                    } else if outer_name == "tcp" && inner_name == "scrape" {
-                        tracker_data.connections.tcp_scrape = data.parse().unwrap_or_else(|_| 0);
-                    } else if outer_name == "udp" && inner_name == "overall" {
-                        tracker_data.connections.udp_overall = data.parse().unwrap_or_else(|_| 0);
-                    } else if outer_name == "udp" && inner_name == "connect" {
-                        tracker_data.connections.udp_connect = data.parse().unwrap_or_else(|_| 0);
-                    } else if outer_name == "udp" && inner_name == "announce" {
-                        tracker_data.connections.udp_announce = data.parse().unwrap_or_else(|_| 0);
-                    } else if outer_name == "udp" && inner_name == "scrape" {
-                        tracker_data.connections.udp_scrape = data.parse().unwrap_or_else(|_| 0);
-                    } else if outer_name == "udp" && inner_name == "missmatch" {
-                        tracker_data.connections.udp_missmatch = data.parse().unwrap_or_else(|_| 0);
-                    } else if outer_name == "http_error" && inner_name == "count" {
-                        tracker_data.http_error.insert(http_code.clone(), data.parse().unwrap_or_else(|_| 0));
-                    }
-                },
-                _ => {},
-            }
+/// returns the name of the host the exporter runs on
+pub(crate) fn hostname() -> Option<String> {
+    #[cfg(unix)]
+    {
+        extern "C" {
+            fn gethostname(name: *mut std::os::raw::c_char, len: usize) -> std::os::raw::c_int;
+        }
+        let mut buffer = [0u8; 256];
+        if unsafe { gethostname(buffer.as_mut_ptr() as *mut std::os::raw::c_char, buffer.len()) } != 0 {
+            return None;
+        }
+        let len = buffer.iter().position(|byte| *byte == 0).unwrap_or(buffer.len());
+        String::from_utf8(buffer[..len].to_vec()).ok().filter(|name| !name.is_empty())
+    }
+    #[cfg(not(unix))]
+    std::env::var("COMPUTERNAME").ok()
+}
+
+/// replaces everything except alphanumerics, `-` and `_` with `_`
+///
+/// Used for values which become part of a dotted metric path.
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+/// Upstream describes where the stats of the tracker are read from
+#[derive(Clone, Debug)]
+pub struct Upstream {
+    /// address of the tracker
+    pub url: String,
+
+    /// directory to store every raw response in
+    pub record_dir: Option<std::path::PathBuf>,
+
+    /// recorded responses to read instead of asking the tracker
+    pub replay: Option<replay::Replay>,
+
+    /// time the tracker has to accept the connection and to answer
+    pub timeout: Duration,
+}
+
+/// fetches and parses `/stats?mode=everything` of the tracker
+fn scrape(upstream: &Upstream) -> Result<Everything, Error> {
+    everything(fetch(upstream, "everything")?)
+}
+
+/// parses the response of the tracker fetched by the event loop to `/stats?mode=everything`
+#[cfg(unix)]
+fn parse_fetched(upstream: &Upstream, fetched: &event::Fetched) -> Result<Everything, Error> {
+    let raw = fetched.response.clone().map_err(Error::new)?;
+    everything(checked(upstream, "everything", fetched.sent, fetched.received, &raw)?)
+}
+
+/// parses the response to `/stats?mode=everything`
+fn everything(response: Response) -> Result<Everything, Error> {
+    let mut tracker_data = parse_everything(&response.body)?;
+
+    if let Some(date) = response.headers.get("date") {
+        tracker_data.clock_skew = clock_skew(date, response.time);
+    }
+    Ok(tracker_data)
+}
+
+/// scrapes `upstream` through `flights`, concurrent scrapes of the same tracker share one fetch
+///
+/// Waiting for the scrape of another request is bounded by the timeout of `upstream` as well.
+#[cfg(not(unix))]
+fn coalesced_scrape(
+    flights: &flight::Group<Arc<Result<Everything, Error>>>,
+    upstream: &Upstream,
+) -> Arc<Result<Everything, Error>> {
+    flights
+        .run(&upstream.url, upstream.timeout, || Arc::new(scrape(upstream)))
+        .unwrap_or_else(|err| Arc::new(Err(err)))
+}
+
+/// response of the opentracker stats page
+struct Response {
+    /// http status code
+    status: u16,
+
+    /// header fields, names are stored in lowercase
+    headers: HashMap<String, String>,
+
+    /// body of the response
+    body: String,
+
+    /// local time the response was received at
+    time: SystemTime,
+}
+
+/// requests `/stats?mode={mode}` from the tracker, or reads the next recorded response
+fn fetch(upstream: &Upstream, mode: &str) -> Result<Response, Error> {
+    if let Some(replay) = &upstream.replay {
+        let (time, raw) = replay.next(mode)?;
+        let mut response = parse_response(&raw)?;
+        response.time = time;
+        return Ok(response);
+    }
+
+    let deadline = Instant::now() + upstream.timeout;
+    let addr = resolve(&upstream.url)?;
+    let mut stream = TcpStream::connect_timeout(&addr, upstream.timeout)
+        .map_err(|err| Error::new(ErrorKind::Unreachable(format!("{}: {}", upstream.url, err))))?;
+    stream.set_write_timeout(Some(upstream.timeout))?;
+    let sent = SystemTime::now();
+    stream.write_all(&request(&upstream.url, mode))?;
+    let _ = stream.flush(); // discard errors
+
+    let buffer = read_until(&mut stream, deadline)?;
+    checked(upstream, mode, sent, SystemTime::now(), &buffer)
+}
+
+/// returns the address of the tracker on `url`
+fn resolve(url: &str) -> Result<SocketAddr, Error> {
+    let unreachable = |reason: String| Error::new(ErrorKind::Unreachable(format!("{}: {}", url, reason)));
+    match url.to_socket_addrs() {
+        Ok(mut addrs) => addrs.next().ok_or_else(|| unreachable(String::from("no address"))),
+        // a missing port is a mistake in the config, not a tracker down
+        Err(err) if err.kind() == std::io::ErrorKind::InvalidInput => Err(err.into()),
+        Err(err) => Err(unreachable(err.to_string())),
+    }
+}
+
+/// returns the request for `/stats?mode={mode}` of the tracker on `url`
+fn request(url: &str, mode: &str) -> Vec<u8> {
+    format!(
+        "GET /stats?mode={} HTTP/1.1\r\nHost: {}\r\nUser-Agent: opentracker-exporter/{}\r\nAccept: text/plain\r\n\r\n",
+        mode,
+        url,
+        env!("CARGO_PKG_VERSION")
+    )
+    .into_bytes()
+}
+
+/// records and parses the raw response of the tracker, sent and received at the local times given
+fn checked(upstream: &Upstream, mode: &str, sent: SystemTime, received: SystemTime, buffer: &[u8]) -> Result<Response, Error> {
+    // the tracker generated its Date somewhere between sending and receiving
+    let time = match received.duration_since(sent) {
+        Ok(rtt) => sent + rtt / 2,
+        Err(_) => received,
+    };
+
+    // recorded before parsing, broken responses are the interesting ones
+    if let Some(dir) = &upstream.record_dir {
+        if let Err(err) = replay::record(dir, mode, time, buffer) {
+            eprintln!("failed to record response to {}: {}", dir.display(), err);
+        }
+    }
+
+    let mut response = parse_response(&String::from_utf8_lossy(buffer))?;
+    if response.status < 200 || response.status >= 300 {
+        return Err(Error::new(ErrorKind::HttpStatus(response.status)));
+    }
+    response.time = time;
+    Ok(response)
+}
+
+/// reads `stream` to the end, a tracker sending slowly must finish before `deadline`
+fn read_until(stream: &mut TcpStream, deadline: Instant) -> Result<Vec<u8>, Error> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0; 4096];
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left == Duration::from_secs(0) {
+            return Err(Error::new(ErrorKind::IoTimedOut));
+        }
+        stream.set_read_timeout(Some(left))?;
+        match stream.read(&mut chunk) {
+            Ok(0) => return Ok(buffer),
+            Ok(amount) => buffer.extend_from_slice(&chunk[..amount]),
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            // timeouts are reported as WouldBlock on some platforms
+            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => return Err(Error::new(ErrorKind::IoTimedOut)),
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+/// splits a raw http response into header fields and body
+fn parse_response(raw: &str) -> Result<Response, Error> {
+    let split = match raw.find("\r\n\r\n") {
+        Some(split) => split,
+        None => return Err(Error::new(ErrorKind::NotParsable(String::from("missing end of http header")))),
+    };
+    let (head, body) = raw.split_at(split);
+
+    // HTTP/1.1 200 OK
+    let status = match head.split_whitespace().nth(1).map(str::parse) {
+        Some(Ok(status)) => status,
+        _ => return Err(Error::new(ErrorKind::NotParsable(String::from("invalid http status line")))),
+    };
+
+    let mut headers = HashMap::new();
+    for line in head.lines().skip(1) {
+        if let Some(colon) = line.find(':') {
+            let (key, value) = line.split_at(colon);
+            headers.insert(key.trim().to_lowercase(), value[1..].trim().to_string());
         }
+    }
+
+    Ok(Response {
+        status,
+        headers,
+        body: body.trim().to_string(),
+        time: SystemTime::now(),
+    })
+}
+
+/// returns how many seconds the clock of the tracker is ahead of the local clock
+///
+/// `date` is the http `Date` header of the tracker, `local` the local time it
+/// was generated at. Returns None if the header cannot be parsed.
+fn clock_skew(date: &str, local: SystemTime) -> Option<i64> {
+    let remote = httpdate::parse_http_date(date).ok()?;
+    match remote.duration_since(local) {
+        Ok(ahead) => Some(ahead.as_secs() as i64),
+        Err(behind) => Some(-(behind.duration().as_secs() as i64)),
+    }
+}
+
+/// parses the xml of `/stats?mode=everything`
+fn parse_everything(buffer: &str) -> Result<Everything, Error> {
+    let mut tracker_data = Everything::new();
+
+    use xml::reader::{XmlEvent};
 
+    let parser = xml::reader::EventReader::from_str(buffer);
+    let mut outer_name = String::new();
+    let mut inner_name = String::new();
+    let mut http_code = String::new();
+
+    //let mut depth = 0;
+    for e in parser {
+        match e {
+            Ok(XmlEvent::StartElement { name, attributes, .. }) => {
+                //println!("{}+{}", indent(depth), name);
+                //depth += 1;
+
+                let name = name.to_string();
+
+                if outer_name.is_empty() && name != "stats" {
+                    return Err(Error::new(ErrorKind::NotParsable(format!("unexpected root element {}", name))));
+                }
+
+                if name == "count" && outer_name == "http_error" && attributes.len() == 1 {
+                    http_code = attributes[0].value.to_string();
+                }
+
+                if name == "count" || name == "accept" || name == "announce" || name == "scrape" || name == "overall" || name == "connect" || name == "missmatch" {
+                    inner_name = name;
+                } else {
+                    outer_name = name;
+                }
+            },
+            Ok(XmlEvent::EndElement { name: _ }) => {
+                //depth -= 1;
+                //println!("{}-{}", indent(depth), name);
+            },
+            Ok(XmlEvent::Characters(data)) => {
+                //println!("{}chars: {}", indent(depth), data);
+                if outer_name == "tracker_id" {
+                    tracker_data.tracker_id = data.parse().unwrap_or(0);
+                } else if outer_name == "uptime" {
+                    tracker_data.uptime = data.parse().unwrap_or(0);
+                } else if outer_name == "count_mutex" {
+                    tracker_data.torrents.mutex = data.parse().unwrap_or(0);
+                } else if outer_name == "count_iterator" {
+                    tracker_data.torrents.iterator = data.parse().unwrap_or(0);
+                } else if outer_name == "peers" && inner_name == "count" {
+                    tracker_data.peers = data.parse().unwrap_or(0);
+                } else if outer_name == "seeds" && inner_name == "count" {
+                    tracker_data.seeds = data.parse().unwrap_or(0);
+                } else if outer_name == "completed" && inner_name == "count" {
+                    tracker_data.completed = data.parse().unwrap_or(0);
+                } else if outer_name == "mutex_stall" && inner_name == "count" {
+                    tracker_data.mutex_stall = data.parse().unwrap_or(0);
+                } else if outer_name == "tcp" && inner_name == "accept" {
+                    tracker_data.connections.tcp_accept = data.parse().unwrap_or(0);
+                } else if outer_name == "tcp" && inner_name == "announce" {
+                    tracker_data.connections.tcp_announce = data.parse().unwrap_or(0);
+                } else if outer_name == "tcp" && inner_name == "scrape" {
+                    tracker_data.connections.tcp_scrape = data.parse().unwrap_or(0);
+                } else if outer_name == "udp" && inner_name == "overall" {
+                    tracker_data.connections.udp_overall = data.parse().unwrap_or(0);
+                } else if outer_name == "udp" && inner_name == "connect" {
+                    tracker_data.connections.udp_connect = data.parse().unwrap_or(0);
+                } else if outer_name == "udp" && inner_name == "announce" {
+                    tracker_data.connections.udp_announce = data.parse().unwrap_or(0);
+                } else if outer_name == "udp" && inner_name == "scrape" {
+                    tracker_data.connections.udp_scrape = data.parse().unwrap_or(0);
+                } else if outer_name == "udp" && inner_name == "missmatch" {
+                    tracker_data.connections.udp_missmatch = data.parse().unwrap_or(0);
+                } else if outer_name == "http_error" && inner_name == "count" {
+                    tracker_data.http_error.insert(http_code.clone(), data.parse().unwrap_or(0));
+                }
+            },
+            Err(err) => return Err(Error::new(ErrorKind::NotParsable(err.to_string()))),
+            _ => {},
+        }
     }
-    Ok(tracker_data.get_string(prefix, name))
+    Ok(tracker_data)
 }
 
 /*
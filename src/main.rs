@@ -1,7 +1,175 @@
-use clap::{App, Arg, SubCommand};
+use clap::{App, Arg, ArgMatches, SubCommand};
 
 fn main() {
-    let mut app = App::new("opentracker exporter")
+    // behave as munin plugin when linked into the munin plugin directory
+    let args: Vec<String> = std::env::args().collect();
+    if opentracker_exporter::munin::invoked_as(&args[0]) {
+        munin(args.get(1).map(String::as_str), |conf| {
+            opentracker_exporter::munin::configure(conf)
+        });
+    }
+
+    let mut command = app();
+
+    let matches = command.clone().get_matches();
+
+    // run subcommands
+    if let Some(matches) = matches.subcommand_matches("completion") {
+        completion(matches, &mut command);
+        std::process::exit(0);
+    }
+    drop(command);
+
+    let mut conf = configure(&matches).unwrap_or_else(|err| {
+        eprintln!("invalid configuration: {}", err);
+        std::process::exit(1);
+    });
+
+    // reload parses the same flags and the config file again
+    conf.reload = Some(std::sync::Arc::new(move || {
+        let matches = app().get_matches_from_safe(&args).map_err(clap_error)?;
+        configure(&matches)
+    }));
+
+    if matches.is_present("once") {
+        let result = match matches.value_of("output") {
+            Some(path) => opentracker_exporter::textfile::write(&conf, std::path::Path::new(path)),
+            None => opentracker_exporter::textfile::print(&conf),
+        };
+        if let Err(err) = result {
+            eprintln!("{}", err.kind());
+            std::process::exit(1);
+        }
+        std::process::exit(0);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("query") {
+        use opentracker_exporter::query::{Format, Report};
+        let format: Format = matches.value_of("format").unwrap_or("json").parse().unwrap_or(Format::Json);
+        let report = Report::new(&conf.upstream(), &conf.name);
+        print!("{}", report.format(format));
+        if format == Format::Json {
+            println!();
+        }
+        std::process::exit(if report.success() { 0 } else { 1 });
+    }
+
+    if let Some(matches) = matches.subcommand_matches("top") {
+        let delay = match matches.value_of("delay").unwrap_or("1").parse::<f64>() {
+            Ok(delay) if delay >= 0.0 => std::time::Duration::try_from_secs_f64(delay.max(0.1)).ok(),
+            _ => None,
+        };
+        let delay = delay.unwrap_or_else(|| {
+            eprintln!("invalid delay, expected a positive number of seconds");
+            std::process::exit(1);
+        });
+        opentracker_exporter::top::run(&conf, delay);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("export") {
+        use opentracker_exporter::record::{export, now, parse_time};
+        let time = |arg, default| match matches.value_of(arg) {
+            Some(time) => parse_time(time).unwrap_or_else(|err| {
+                eprintln!("{}", err.kind());
+                std::process::exit(1);
+            }),
+            None => default,
+        };
+        let (from, to) = (time("from", 0), time("to", now()));
+        let dir = std::path::Path::new(matches.value_of("dir").unwrap_or("."));
+        if let Err(err) = export(dir, from, to, matches.value_of("tracker"), &mut std::io::stdout()) {
+            eprintln!("{}", err.kind());
+            std::process::exit(1);
+        }
+        std::process::exit(0);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("simulate") {
+        use opentracker_exporter::simulate::{serve, Options};
+        let seconds = |arg| matches.value_of(arg).and_then(|value| value.parse().ok());
+        let options = Options {
+            restart: seconds("restart"),
+            spikes: seconds("spikes"),
+            stalls: seconds("stalls"),
+            seed: seconds("seed").unwrap_or_else(|| {
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|time| time.as_nanos() as u64)
+                    .unwrap_or(1)
+            }),
+        };
+        let addr = matches.value_of("listen").unwrap_or("127.0.0.1:6969");
+        if let Err(err) = serve(addr, options, conf.verbose) {
+            eprintln!("cannot simulate on {}: {}", addr, err.kind());
+            std::process::exit(1);
+        }
+        std::process::exit(0);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("check") {
+        use opentracker_exporter::check::{Check, Status};
+        let mut check = Check::new();
+        for (arg, critical) in &[("warning", false), ("critical", true)] {
+            for value in matches.values_of(arg).into_iter().flatten() {
+                let (field, range) = key_value(value);
+                let (field, range) = match (field.parse(), range.parse()) {
+                    (Ok(field), Ok(range)) => (field, range),
+                    _ => {
+                        println!("OPENTRACKER UNKNOWN - invalid threshold {}", value);
+                        std::process::exit(Status::Unknown.code());
+                    }
+                };
+                let threshold = check.threshold(field);
+                if *critical {
+                    threshold.critical = Some(range);
+                } else {
+                    threshold.warning = Some(range);
+                }
+            }
+        }
+        check.state = matches.value_of("state").map(std::path::PathBuf::from);
+        match matches.value_of("timeout").unwrap_or("10").parse() {
+            Ok(timeout) if timeout > 0 => conf.scrape_timeout = timeout,
+            _ => {
+                println!("OPENTRACKER UNKNOWN - invalid timeout");
+                std::process::exit(Status::Unknown.code());
+            }
+        }
+
+        let (status, output) = check.run(&conf);
+        println!("{}", output);
+        std::process::exit(status.code());
+    }
+
+    if let Some(matches) = matches.subcommand_matches("munin") {
+        munin(matches.value_of("mode"), move |c| *c = conf);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("zabbix-discovery") {
+        use opentracker_exporter::zabbix::{discovery, Discovery};
+        let what = match matches.value_of("what") {
+            Some("http-codes") => Discovery::HttpCodes,
+            _ => Discovery::Trackers,
+        };
+        match discovery(&conf, what) {
+            Ok(json) => println!("{}", json),
+            Err(err) => {
+                eprintln!("discovery failed: {}", err);
+                std::process::exit(1);
+            }
+        }
+        std::process::exit(0);
+    }
+
+    if conf.verbose >= 1 {
+        println!("Debug{}: enabled", conf.verbose);
+    }
+
+    conf.run().unwrap();
+}
+
+fn app() -> App<'static, 'static> {
+    App::new("opentracker exporter")
         .version(env!("CARGO_PKG_VERSION")) // load version from cargo
         .author("Finn Behrens <me@kloenk.de>")
         .about("Exporter for opentracker stats to a prometheus stats endpoint")
@@ -10,14 +178,28 @@ fn main() {
                 .short("v")
                 .long("verbose")
                 .multiple(true)
-                .help("sets the level of verbosity"),
+                .help("sets the level of verbosity")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .help("read flags from FILE, one NAME = VALUE per line, reloaded on SIGHUP")
+                .value_name("FILE")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("web-enable-reload")
+                .long("web-enable-reload")
+                .help("reload the configuration on POST /-/reload, any client reaching the exporter can trigger it"),
         )
         .arg(
             Arg::with_name("url")
                 .short("u")
                 .long("url")
                 .help("set opentracker stats host")
-                .value_name("URL"),
+                .value_name("URL")
+                .global(true),
         )
         .arg(
             Arg::with_name("port")
@@ -40,12 +222,31 @@ fn main() {
                 .help("set how many threads should be alocated for http workers")
                 .value_name("THREADS"),
         )
+        .arg(
+            Arg::with_name("max-threads")
+                .long("max-threads")
+                .help("grow the http workers up to THREADS while requests have to wait")
+                .value_name("THREADS"),
+        )
+        .arg(
+            Arg::with_name("scale-down-after")
+                .long("scale-down-after")
+                .help("retire an added http worker after SECONDS without waiting requests")
+                .value_name("SECONDS"),
+        )
+        .arg(
+            Arg::with_name("queue")
+                .long("queue")
+                .help("set how many requests may wait for a thread before answering 503, 0 for unlimited")
+                .value_name("SIZE"),
+        )
         .arg(
             Arg::with_name("name")
                 .short("n")
                 .long("name")
                 .help("no to prefix metrics")
-                .value_name("NAME"),
+                .value_name("NAME")
+                .global(true),
         )
         .arg(
             Arg::with_name("host")
@@ -53,6 +254,445 @@ fn main() {
                 .long("hostname")
                 .help("set name attribute for prometheus tag")
                 .value_name("NAME")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("push")
+                .long("push")
+                .help("push metrics to a prometheus pushgateway instead of listening")
+                .value_name("URL"),
+        )
+        .arg(
+            Arg::with_name("interval")
+                .long("interval")
+                .help("set seconds between two scrapes in the background")
+                .value_name("SECONDS"),
+        )
+        .arg(
+            Arg::with_name("drain-timeout")
+                .long("drain-timeout")
+                .help("set seconds to wait for running requests on SIGTERM or SIGINT")
+                .value_name("SECONDS"),
+        )
+        .arg(
+            Arg::with_name("probe-timeout")
+                .long("probe-timeout")
+                .help("set seconds to wait for the trackers of /probe?target=URL")
+                .value_name("SECONDS"),
+        )
+        .arg(
+            Arg::with_name("probe-target")
+                .long("probe-target")
+                .help("allow /probe?target=HOST:PORT to scrape the tracker, /probe is disabled without")
+                .value_name("HOST:PORT")
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("scrape-timeout")
+                .long("scrape-timeout")
+                .help("set seconds the tracker has to answer a scrape")
+                .value_name("SECONDS")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("min-scrape-interval")
+                .long("min-scrape-interval")
+                .help("set seconds a scrape of the tracker is reused for other requests, 0 only shares running scrapes")
+                .value_name("SECONDS"),
+        )
+        .arg(
+            Arg::with_name("job")
+                .long("job")
+                .help("set job label of the pushgateway grouping key")
+                .value_name("JOB")
+                .requires("push"),
+        )
+        .arg(
+            Arg::with_name("instance")
+                .long("instance")
+                .help("set instance label of the pushgateway grouping key, defaults to the hostname")
+                .value_name("INSTANCE")
+                .requires("push"),
+        )
+        .arg(
+            Arg::with_name("push-method")
+                .long("push-method")
+                .help("set http method used to push")
+                .value_name("METHOD")
+                .possible_value("put")
+                .possible_value("post")
+                .requires("push"),
+        )
+        .arg(
+            Arg::with_name("push-user")
+                .long("push-user")
+                .help("set user for basic auth at the pushgateway")
+                .value_name("USER")
+                .requires("push"),
+        )
+        .arg(
+            Arg::with_name("push-password")
+                .long("push-password")
+                .help("set password for basic auth at the pushgateway")
+                .value_name("PASSWORD")
+                .requires("push-user"),
+        )
+        .arg(
+            Arg::with_name("remote-write")
+                .long("remote-write")
+                .help("send metrics to a prometheus remote write receiver instead of listening")
+                .value_name("URL"),
+        )
+        .arg(
+            Arg::with_name("remote-write-user")
+                .long("remote-write-user")
+                .help("set user for basic auth at the remote write receiver")
+                .value_name("USER")
+                .requires("remote-write"),
+        )
+        .arg(
+            Arg::with_name("remote-write-password")
+                .long("remote-write-password")
+                .help("set password for basic auth at the remote write receiver")
+                .value_name("PASSWORD")
+                .requires("remote-write-user"),
+        )
+        .arg(
+            Arg::with_name("external-label")
+                .long("external-label")
+                .help("add a label to all remote written series")
+                .value_name("NAME=VALUE")
+                .multiple(true)
+                .number_of_values(1)
+                .requires("remote-write"),
+        )
+        .arg(
+            Arg::with_name("remote-write-batch")
+                .long("remote-write-batch")
+                .help("set maximum number of samples per remote write request")
+                .value_name("SAMPLES")
+                .requires("remote-write"),
+        )
+        .arg(
+            Arg::with_name("remote-write-queue")
+                .long("remote-write-queue")
+                .help("set maximum number of samples queued for remote write")
+                .value_name("SAMPLES")
+                .requires("remote-write"),
+        )
+        .arg(
+            Arg::with_name("statsd")
+                .long("statsd")
+                .help("send metrics to a statsd server")
+                .value_name("ADDRESS"),
+        )
+        .arg(
+            Arg::with_name("dogstatsd")
+                .long("dogstatsd")
+                .help("add dogstatsd tags to statsd metrics")
+                .requires("statsd"),
+        )
+        .arg(
+            Arg::with_name("graphite")
+                .long("graphite")
+                .help("send metrics to a graphite carbon server")
+                .value_name("ADDRESS"),
+        )
+        .arg(
+            Arg::with_name("influx")
+                .long("influx")
+                .help("send metrics to influxdb, http://HOST:PORT/write?db=DB or udp://HOST:PORT")
+                .value_name("URL"),
+        )
+        .arg(
+            Arg::with_name("influx-token")
+                .long("influx-token")
+                .help("set api token for influxdb")
+                .value_name("TOKEN")
+                .requires("influx"),
+        )
+        .arg(
+            Arg::with_name("otlp")
+                .long("otlp")
+                .help("export metrics to an opentelemetry collector via OTLP/HTTP")
+                .value_name("URL"),
+        )
+        .arg(
+            Arg::with_name("otlp-header")
+                .long("otlp-header")
+                .help("add a http header to OTLP requests")
+                .value_name("NAME=VALUE")
+                .multiple(true)
+                .number_of_values(1)
+                .requires("otlp"),
+        )
+        .arg(
+            Arg::with_name("otlp-service-name")
+                .long("otlp-service-name")
+                .help("set service.name resource attribute for OTLP")
+                .value_name("NAME")
+                .requires("otlp"),
+        )
+        .arg(
+            Arg::with_name("zabbix")
+                .long("zabbix")
+                .help("send items to a zabbix server or proxy")
+                .value_name("ADDRESS"),
+        )
+        .arg(
+            Arg::with_name("zabbix-host")
+                .long("zabbix-host")
+                .help("set zabbix host the items belong to, defaults to the hostname")
+                .value_name("HOST")
+                .requires("zabbix"),
+        )
+        .arg(
+            Arg::with_name("mqtt")
+                .long("mqtt")
+                .help("publish stats to a mqtt broker")
+                .value_name("ADDRESS"),
+        )
+        .arg(
+            Arg::with_name("mqtt-topic")
+                .long("mqtt-topic")
+                .help("set first level of the mqtt topics")
+                .value_name("TOPIC")
+                .requires("mqtt"),
+        )
+        .arg(
+            Arg::with_name("mqtt-user")
+                .long("mqtt-user")
+                .help("set user for the mqtt broker")
+                .value_name("USER")
+                .requires("mqtt"),
+        )
+        .arg(
+            Arg::with_name("mqtt-password")
+                .long("mqtt-password")
+                .help("set password for the mqtt broker")
+                .value_name("PASSWORD")
+                .requires("mqtt-user"),
+        )
+        .arg(
+            Arg::with_name("mqtt-discovery")
+                .long("mqtt-discovery")
+                .help("publish home assistant discovery messages")
+                .requires("mqtt"),
+        )
+        .arg(
+            Arg::with_name("history")
+                .long("history")
+                .help("keep HOURS of scrapes for /status and /api/history")
+                .value_name("HOURS"),
+        )
+        .arg(
+            Arg::with_name("record")
+                .long("record")
+                .help("append every scrape to daily csv files in DIR")
+                .value_name("DIR"),
+        )
+        .arg(
+            Arg::with_name("record-keep")
+                .long("record-keep")
+                .help("remove recorded files older than DAYS")
+                .value_name("DAYS")
+                .requires("record"),
+        )
+        .arg(
+            Arg::with_name("record-dir")
+                .long("record-dir")
+                .help("store every raw response of the tracker in DIR")
+                .value_name("DIR")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("replay")
+                .long("replay")
+                .help("read the responses recorded with --record-dir from DIR instead of the tracker")
+                .value_name("DIR")
+                .conflicts_with("record-dir")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("once")
+                .long("once")
+                .help("scrape once and print the metrics instead of serving them"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .help("write the metrics atomically to FILE, e.g. for the node_exporter textfile collector")
+                .value_name("FILE")
+                .requires("once"),
+        )
+        .subcommand(
+            SubCommand::with_name("query")
+                .about("scrape the tracker once and print the stats")
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .help("set the output format")
+                        .value_name("FORMAT")
+                        .possible_value("json")
+                        .possible_value("table")
+                        .default_value("json"),
+                )
+                .setting(clap::AppSettings::ColorAuto)
+                .setting(clap::AppSettings::ColoredHelp),
+        )
+        .subcommand(
+            SubCommand::with_name("top")
+                .about("show a live dashboard of the tracker")
+                .arg(
+                    Arg::with_name("delay")
+                        .short("d")
+                        .long("delay")
+                        .help("set seconds between polls")
+                        .value_name("SECONDS")
+                        .default_value("1"),
+                )
+                .setting(clap::AppSettings::ColorAuto)
+                .setting(clap::AppSettings::ColoredHelp),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("print recorded scrapes of a time range as csv")
+                .arg(
+                    Arg::with_name("dir")
+                        .help("set directory the scrapes were recorded to")
+                        .index(1)
+                        .required(true)
+                        .value_name("DIR"),
+                )
+                .arg(
+                    Arg::with_name("from")
+                        .long("from")
+                        .help("set start as unix time, YYYY-MM-DD or YYYY-MM-DDTHH:MM:SSZ")
+                        .value_name("TIME"),
+                )
+                .arg(
+                    Arg::with_name("to")
+                        .long("to")
+                        .help("set exclusive end, defaults to now")
+                        .value_name("TIME"),
+                )
+                .arg(
+                    Arg::with_name("tracker")
+                        .long("tracker")
+                        .help("only export the tracker named NAME")
+                        .value_name("NAME"),
+                )
+                .setting(clap::AppSettings::ColorAuto)
+                .setting(clap::AppSettings::ColoredHelp),
+        )
+        .subcommand(
+            SubCommand::with_name("simulate")
+                .about("serve evolving stats of a fake opentracker")
+                .arg(
+                    Arg::with_name("listen")
+                        .short("l")
+                        .long("listen")
+                        .help("set address to serve the stats on")
+                        .value_name("ADDRESS")
+                        .default_value("127.0.0.1:6969"),
+                )
+                .arg(
+                    Arg::with_name("restart")
+                        .long("restart")
+                        .help("restart the tracker every SECONDS")
+                        .value_name("SECONDS"),
+                )
+                .arg(
+                    Arg::with_name("spikes")
+                        .long("spikes")
+                        .help("raise http errors for 30 seconds every SECONDS")
+                        .value_name("SECONDS"),
+                )
+                .arg(
+                    Arg::with_name("stalls")
+                        .long("stalls")
+                        .help("hang for 3 seconds with a mutex stall every SECONDS")
+                        .value_name("SECONDS"),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .help("set seed of the random numbers")
+                        .value_name("SEED"),
+                )
+                .setting(clap::AppSettings::ColorAuto)
+                .setting(clap::AppSettings::ColoredHelp),
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("check the tracker once as nagios plugin")
+                .arg(
+                    Arg::with_name("warning")
+                        .short("w")
+                        .long("warning")
+                        .help("set warning range as FIELD=RANGE (peers, seeds, torrents, uptime, mutex_stall, udp_missmatch)")
+                        .value_name("FIELD=RANGE")
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("critical")
+                        .short("c")
+                        .long("critical")
+                        .help("set critical range as FIELD=RANGE")
+                        .value_name("FIELD=RANGE")
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("timeout")
+                        .short("t")
+                        .long("timeout")
+                        .help("set seconds the tracker has to answer, keep below the timeout of the monitoring")
+                        .value_name("SECONDS")
+                        .default_value("10"),
+                )
+                .arg(
+                    Arg::with_name("state")
+                        .long("state")
+                        .help("set file to keep values for mutex_stall and udp_missmatch between checks")
+                        .value_name("FILE"),
+                )
+                .setting(clap::AppSettings::ColorAuto)
+                .setting(clap::AppSettings::ColoredHelp),
+        )
+        .subcommand(
+            SubCommand::with_name("munin")
+                .about("run as munin multigraph plugin")
+                .arg(
+                    Arg::with_name("mode")
+                        .help("set what munin asks for, fetches the values if not given")
+                        .index(1)
+                        .value_name("MODE")
+                        .possible_value("config")
+                        .possible_value("autoconf")
+                        .possible_value("fetch"),
+                )
+                .setting(clap::AppSettings::ColorAuto)
+                .setting(clap::AppSettings::ColoredHelp),
+        )
+        .subcommand(
+            SubCommand::with_name("zabbix-discovery")
+                .about("print zabbix low level discovery json")
+                .arg(
+                    Arg::with_name("what")
+                        .help("set what to discover")
+                        .index(1)
+                        .required(true)
+                        .value_name("WHAT")
+                        .possible_value("trackers")
+                        .possible_value("http-codes"),
+                )
+                .setting(clap::AppSettings::ColorAuto)
+                .setting(clap::AppSettings::ColoredHelp),
         )
         .subcommand(
             SubCommand::with_name("completion")
@@ -82,53 +722,278 @@ fn main() {
                 .setting(clap::AppSettings::ColoredHelp),
         )
         .setting(clap::AppSettings::ColorAuto)
-        .setting(clap::AppSettings::ColoredHelp);
-
-    let matches = app.clone().get_matches();
-
-    // run subcommands
-    if let Some(matches) = matches.subcommand_matches("completion") {
-        completion(&matches, &mut app);
-        std::process::exit(0);
-    }
-    drop(app);
+        .setting(clap::AppSettings::ColoredHelp)
+}
 
+// builds the config from the flags, flags on the command line win over the config file
+fn configure(cli: &ArgMatches<'static>) -> Result<opentracker_exporter::Config, String> {
     let mut conf = opentracker_exporter::Config::new();
 
+    let file = match cli.value_of("config") {
+        Some(path) => {
+            let args = opentracker_exporter::reload::read(std::path::Path::new(path))
+                .map_err(|err| format!("cannot read {}: {}", path, err.kind()))?;
+            let args = std::iter::once(String::from("opentracker_exporter")).chain(args);
+            app().get_matches_from_safe(args).map_err(clap_error)?
+        }
+        None => clap::ArgMatches::default(),
+    };
+    let flags = Flags { cli, file: &file };
+
     // read verbose value
-    conf.verbose = matches.occurrences_of("verbose") as u8;
+    conf.verbose = flags.occurrences_of("verbose") as u8;
 
-    if let Some(url) = &matches.value_of("url") {
+    if let Some(url) = &flags.value_of("url") {
         conf.url = url.to_string();
     }
 
-    if let Some(port) = &matches.value_of("port") {
-        conf.port = port.parse().unwrap_or_else(|_| conf.port);
+    conf.reload_endpoint = flags.is_present("web-enable-reload");
+
+    if let Some(port) = &flags.value_of("port") {
+        conf.port = port.parse().unwrap_or(conf.port);
     }
 
-    if let Some(interface) = &matches.value_of("interface") {
+    if let Some(interface) = &flags.value_of("interface") {
         conf.interface = interface.to_string();
     }
 
-    if let Some(threads) = &matches.value_of("threads") {
-        conf.threads = threads.parse().unwrap_or_else(|_| conf.threads);
+    if let Some(threads) = &flags.value_of("threads") {
+        conf.threads = threads.parse().unwrap_or(conf.threads);
+    }
+
+    if let Some(max) = &flags.value_of("max-threads") {
+        conf.max_threads = max.parse().ok();
+    }
+
+    if let Some(seconds) = &flags.value_of("scale-down-after") {
+        conf.scale_down = seconds.parse().unwrap_or(conf.scale_down);
     }
 
-    if let Some(name) = &matches.value_of("host") {
+    if let Some(queue) = &flags.value_of("queue") {
+        conf.queue = match queue.parse() {
+            Ok(0) => None,
+            Ok(queue) => Some(queue),
+            Err(_) => conf.queue,
+        };
+    }
+
+    if let Some(name) = &flags.value_of("host") {
         conf.name = name.to_string();
     } else {
         conf.name = conf.url.clone();
     }
 
-    if let Some(name) = &matches.value_of("name") {
+    if let Some(name) = &flags.value_of("name") {
         conf.prefix = name.to_string();
     }
 
-    if conf.verbose >= 1 {
-        println!("Debug{}: enabled", conf.verbose);
+    if let Some(interval) = &flags.value_of("interval") {
+        conf.interval = interval.parse().unwrap_or(conf.interval);
     }
 
-    conf.run().unwrap();
+    if let Some(drain) = &flags.value_of("drain-timeout") {
+        conf.drain = drain.parse().unwrap_or(conf.drain);
+    }
+
+    if let Some(timeout) = &flags.value_of("probe-timeout") {
+        conf.probe_timeout = timeout.parse().unwrap_or(conf.probe_timeout);
+    }
+
+    if let Some(targets) = flags.values_of("probe-target") {
+        conf.probe_targets = targets.map(str::to_string).collect();
+    }
+
+    if let Some(timeout) = &flags.value_of("scrape-timeout") {
+        conf.scrape_timeout = timeout.parse().unwrap_or(conf.scrape_timeout);
+    }
+
+    if let Some(interval) = &flags.value_of("min-scrape-interval") {
+        conf.min_scrape_interval = interval.parse().unwrap_or(conf.min_scrape_interval);
+    }
+
+    if let Some(url) = &flags.value_of("push") {
+        let mut gateway = opentracker_exporter::push::Gateway::new(url);
+        gateway.instance = conf.name.clone();
+
+        if let Some(job) = &flags.value_of("job") {
+            gateway.job = job.to_string();
+        }
+
+        if let Some(instance) = &flags.value_of("instance") {
+            gateway.instance = instance.to_string();
+        }
+
+        if let Some(method) = &flags.value_of("push-method") {
+            gateway.method = method.parse().unwrap_or(gateway.method);
+        }
+
+        if let Some(user) = &flags.value_of("push-user") {
+            let password = flags.value_of("push-password").unwrap_or("");
+            gateway.auth = Some((user.to_string(), password.to_string()));
+        }
+
+        conf.push = Some(gateway);
+        conf.listen = false;
+    }
+
+    if let Some(url) = &flags.value_of("remote-write") {
+        let mut endpoint = opentracker_exporter::remote_write::Endpoint::new(url);
+
+        if let Some(user) = &flags.value_of("remote-write-user") {
+            let password = flags.value_of("remote-write-password").unwrap_or("");
+            endpoint.auth = Some((user.to_string(), password.to_string()));
+        }
+
+        if let Some(labels) = flags.values_of("external-label") {
+            endpoint.external_labels = labels.map(parse_key_value).collect::<Result<_, _>>()?;
+        }
+
+        if let Some(batch) = &flags.value_of("remote-write-batch") {
+            endpoint.batch_size = batch.parse().unwrap_or(endpoint.batch_size);
+        }
+
+        if let Some(queue) = &flags.value_of("remote-write-queue") {
+            endpoint.queue_size = queue.parse().unwrap_or(endpoint.queue_size);
+        }
+
+        conf.remote_write = Some(endpoint);
+        conf.listen = false;
+    }
+
+    if let Some(addr) = &flags.value_of("statsd") {
+        let mut server = opentracker_exporter::statsd::Server::new(addr);
+        server.dogstatsd = flags.is_present("dogstatsd");
+        conf.statsd = Some(server);
+    }
+
+    if let Some(addr) = &flags.value_of("graphite") {
+        conf.graphite = Some(addr.to_string());
+    }
+
+    if let Some(url) = &flags.value_of("influx") {
+        let mut endpoint = opentracker_exporter::influx::Endpoint::new(url);
+        endpoint.token = flags.value_of("influx-token").map(str::to_string);
+        conf.influx = Some(endpoint);
+    }
+
+    if let Some(url) = &flags.value_of("otlp") {
+        let mut endpoint = opentracker_exporter::otlp::Endpoint::new(url);
+
+        if let Some(headers) = flags.values_of("otlp-header") {
+            endpoint.headers = headers.map(parse_key_value).collect::<Result<_, _>>()?;
+        }
+
+        if let Some(name) = &flags.value_of("otlp-service-name") {
+            endpoint.service_name = name.to_string();
+        }
+
+        conf.otlp = Some(endpoint);
+    }
+
+    if let Some(addr) = &flags.value_of("zabbix") {
+        let host = flags.value_of("zabbix-host").unwrap_or(&conf.name);
+        conf.zabbix = Some(opentracker_exporter::zabbix::Server::new(addr, host));
+    }
+
+    if let Some(addr) = &flags.value_of("mqtt") {
+        let mut broker = opentracker_exporter::mqtt::Broker::new(addr);
+
+        if let Some(topic) = &flags.value_of("mqtt-topic") {
+            broker.topic = topic.to_string();
+        }
+
+        if let Some(user) = &flags.value_of("mqtt-user") {
+            let password = flags.value_of("mqtt-password").unwrap_or("");
+            broker.auth = Some((user.to_string(), password.to_string()));
+        }
+
+        broker.discovery = flags.is_present("mqtt-discovery");
+        conf.mqtt = Some(broker);
+    }
+
+    if let Some(hours) = &flags.value_of("history") {
+        conf.history = hours.parse().ok();
+    }
+
+    if let Some(dir) = &flags.value_of("record-dir") {
+        conf.record_dir = Some(std::path::PathBuf::from(dir));
+    }
+
+    if let Some(dir) = &flags.value_of("replay") {
+        match opentracker_exporter::replay::Replay::open(std::path::Path::new(dir)) {
+            Ok(replay) => conf.replay = Some(replay),
+            Err(err) => return Err(format!("cannot replay {}: {}", dir, err.kind())),
+        }
+    }
+
+    if let Some(dir) = &flags.value_of("record") {
+        conf.record = Some(std::path::PathBuf::from(dir));
+        conf.record_keep = flags.value_of("record-keep").and_then(|days| days.parse().ok());
+    }
+
+    opentracker_exporter::reload::validate(&conf)?;
+    Ok(conf)
+}
+
+// returns the first line of a clap error without the usage
+fn clap_error(err: clap::Error) -> String {
+    err.message.lines().next().unwrap_or_default().to_string()
+}
+
+// Flags looks up the command line first and the config file second
+struct Flags<'a> {
+    cli: &'a ArgMatches<'static>,
+    file: &'a ArgMatches<'static>,
+}
+
+impl<'a> Flags<'a> {
+    fn value_of(&self, name: &str) -> Option<&'a str> {
+        self.cli.value_of(name).or_else(|| self.file.value_of(name))
+    }
+
+    fn values_of(&self, name: &str) -> Option<clap::Values<'a>> {
+        self.cli.values_of(name).or_else(|| self.file.values_of(name))
+    }
+
+    fn is_present(&self, name: &str) -> bool {
+        self.cli.is_present(name) || self.file.is_present(name)
+    }
+
+    fn occurrences_of(&self, name: &str) -> u64 {
+        self.cli.occurrences_of(name).max(self.file.occurrences_of(name))
+    }
+}
+
+// run as munin plugin with the config set by `configure`, exits afterwards
+fn munin<F: FnOnce(&mut opentracker_exporter::Config)>(mode: Option<&str>, configure: F) -> ! {
+    use opentracker_exporter::munin::{run, Mode};
+    let mode = match Mode::from_arg(mode) {
+        Some(mode) => mode,
+        None => {
+            eprintln!("unknown munin mode {}", mode.unwrap_or(""));
+            std::process::exit(1);
+        }
+    };
+    let mut conf = opentracker_exporter::Config::new();
+    configure(&mut conf);
+    print!("{}", run(&conf, mode));
+    std::process::exit(0);
+}
+
+// split a NAME=VALUE argument, exits on invalid input
+fn key_value(arg: &str) -> (String, String) {
+    parse_key_value(arg).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    })
+}
+
+// split a NAME=VALUE argument
+fn parse_key_value(arg: &str) -> Result<(String, String), String> {
+    match arg.find('=') {
+        Some(split) => Ok((arg[..split].to_string(), arg[split + 1..].to_string())),
+        None => Err(format!("invalid argument {}, expected NAME=VALUE", arg)),
+    }
 }
 
 // create completion
@@ -151,13 +1016,13 @@ fn completion(args: &clap::ArgMatches, app: &mut App) {
     use clap::Shell;
     let shell_l = shell.to_lowercase();
     let shell: Shell;
-    if shell_l == "fish".to_string() {
+    if shell_l == "fish" {
         shell = Shell::Fish;
-    } else if shell_l == "zsh".to_string() {
+    } else if shell_l == "zsh" {
         shell = Shell::Zsh;
-    } else if shell_l == "powershell".to_string() {
+    } else if shell_l == "powershell" {
         shell = Shell::PowerShell;
-    } else if shell_l == "elvish".to_string() {
+    } else if shell_l == "elvish" {
         shell = Shell::Elvish;
     } else {
         shell = Shell::Bash;
@@ -169,12 +1034,12 @@ fn completion(args: &clap::ArgMatches, app: &mut App) {
 
     let mut path = BufWriter::new(match args.value_of("out") {
         Some(x) => Box::new(
-            File::create(&std::path::Path::new(x)).unwrap_or_else(|err| {
+            File::create(std::path::Path::new(x)).unwrap_or_else(|err| {
                 eprintln!("Error opening file: {}", err);
                 std::process::exit(1);
             }),
-        ) as Box<Write>,
-        None => Box::new(std::io::stdout()) as Box<Write>,
+        ) as Box<dyn Write>,
+        None => Box::new(std::io::stdout()) as Box<dyn Write>,
     });
 
     app.gen_completions_to("raspi_firmware", shell, &mut path);
@@ -0,0 +1,140 @@
+// metrics is the sample model shared by all outputs
+
+/// type of a metric family
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    /// value that can go up and down
+    Gauge,
+
+    /// value that only goes up, except on restarts
+    Counter,
+
+    /// observations counted in buckets, rendered as `_bucket`, `_sum` and `_count`
+    Histogram,
+}
+
+impl Kind {
+    /// returns the name used in the prometheus text format
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Kind::Gauge => "gauge",
+            Kind::Counter => "counter",
+            Kind::Histogram => "histogram",
+        }
+    }
+}
+
+/// a single value of a metric family
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    /// labels in the order they are rendered
+    pub labels: Vec<(String, String)>,
+
+    /// value of the sample
+    pub value: f64,
+
+    /// appended to the family name, used for the series of a histogram
+    pub suffix: &'static str,
+}
+
+impl Sample {
+    /// returns the value of the label `name`
+    pub fn label(&self, name: &str) -> Option<&str> {
+        self.labels
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// a named group of samples with help text and type
+#[derive(Clone, Debug, PartialEq)]
+pub struct Family {
+    /// metric name including the prefix
+    pub name: String,
+
+    /// help text
+    pub help: &'static str,
+
+    /// type of the metric
+    pub kind: Kind,
+
+    /// samples of the family
+    pub samples: Vec<Sample>,
+}
+
+impl Family {
+    /// creates a new Family without samples
+    pub fn new(name: String, help: &'static str, kind: Kind) -> Self {
+        Self {
+            name,
+            help,
+            kind,
+            samples: Vec::new(),
+        }
+    }
+
+    /// adds a sample with the given labels
+    pub fn push(&mut self, labels: &[(&str, &str)], value: f64) {
+        self.samples.push(Sample {
+            labels: labels
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            value,
+            suffix: "",
+        });
+    }
+
+    /// adds the series of a histogram, `buckets` are the cumulative counts per upper bound
+    pub fn push_histogram(&mut self, labels: &[(&str, &str)], buckets: &[(f64, u64)], sum: f64, count: u64) {
+        let sample = |suffix, extra: Option<String>, value| {
+            let mut labels: Vec<(String, String)> = labels
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect();
+            if let Some(le) = extra {
+                labels.push((String::from("le"), le));
+            }
+            Sample { labels, value, suffix }
+        };
+        for (bound, cumulative) in buckets {
+            self.samples.push(sample("_bucket", Some(bound.to_string()), *cumulative as f64));
+        }
+        self.samples.push(sample("_bucket", Some(String::from("+Inf")), count as f64));
+        self.samples.push(sample("_sum", None, sum));
+        self.samples.push(sample("_count", None, count as f64));
+    }
+}
+
+/// renders families in the prometheus text exposition format
+pub fn render(families: &[Family]) -> String {
+    let mut ret = String::new();
+    for family in families {
+        ret.push_str(&format!("# HELP {} {}\n", family.name, family.help));
+        ret.push_str(&format!("# TYPE {} {}\n", family.name, family.kind.as_str()));
+        for sample in &family.samples {
+            let labels: Vec<String> = sample
+                .labels
+                .iter()
+                .map(|(key, value)| format!("{}=\"{}\"", key, escape(value)))
+                .collect();
+            ret.push_str(&format!(
+                "{}{}{{{}}} {}\n",
+                family.name,
+                sample.suffix,
+                labels.join(","),
+                sample.value
+            ));
+        }
+    }
+    ret
+}
+
+/// escapes a label value for the text exposition format
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,213 @@
+use std::collections::{BTreeMap, HashSet};
+use std::io::prelude::*;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use super::collector::{Scrape, Sink};
+use super::error::{Error, ErrorKind, Result};
+use super::{json, sanitize};
+
+// tests as sub module
+#[cfg(test)] // only add when running tests
+mod test;
+
+/// timeout for connecting, reading and writing to the broker
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Broker describes the mqtt broker to publish to
+#[derive(Clone, Debug)]
+pub struct Broker {
+    /// address of the broker, the port defaults to 1883
+    pub addr: String,
+
+    /// first level of the topics, stats are published to `{topic}/{name}/{stat}`
+    pub topic: String,
+
+    /// user and password to connect with
+    pub auth: Option<(String, String)>,
+
+    /// publish home assistant discovery messages
+    pub discovery: bool,
+
+    /// topic prefix home assistant listens for discovery messages on
+    pub discovery_prefix: String,
+}
+
+impl Broker {
+    /// creates a new Broker for `addr` with default values
+    pub fn new(addr: &str) -> Self {
+        let addr = if addr.contains(':') && !addr.ends_with(']') {
+            addr.to_string()
+        } else {
+            format!("{}:1883", addr)
+        };
+        Self {
+            addr,
+            topic: String::from("opentracker"),
+            auth: None,
+            discovery: false,
+            discovery_prefix: String::from("homeassistant"),
+        }
+    }
+}
+
+/// Mqtt is a sink publishing every stat as retained message with mqtt 3.1.1
+pub struct Mqtt {
+    broker: Broker,
+    /// latest payload by topic
+    messages: BTreeMap<String, String>,
+    /// topics discovery messages were already published for
+    announced: HashSet<String>,
+}
+
+impl Mqtt {
+    /// creates a new Mqtt publishing to `broker`
+    pub fn new(broker: Broker) -> Self {
+        Self {
+            broker,
+            messages: BTreeMap::new(),
+            announced: HashSet::new(),
+        }
+    }
+
+    /// queues the home assistant discovery message for a stat
+    fn announce(&mut self, scrape: &Scrape, path: &str, state_topic: &str, state_class: &str) {
+        let node = sanitize(&scrape.name);
+        let object = path.replace('.', "_");
+        let config = format!(
+            r#"{{"name":{},"state_topic":{},"unique_id":{},"state_class":{},"device":{{"identifiers":[{}],"name":{},"manufacturer":"opentracker","sw_version":{}}}}}"#,
+            json::string(&path.replace('.', " ")),
+            json::string(state_topic),
+            json::string(&format!("{}_{}_{}", self.broker.topic, node, object)),
+            json::string(state_class),
+            json::string(&format!("{}_{}", self.broker.topic, node)),
+            json::string(&scrape.name),
+            json::string(env!("CARGO_PKG_VERSION"))
+        );
+        self.messages.insert(
+            format!(
+                "{}/sensor/{}/{}/config",
+                self.broker.discovery_prefix, node, object
+            ),
+            config,
+        );
+        self.announced.insert(state_topic.to_string());
+    }
+}
+
+impl Sink for Mqtt {
+    fn name(&self) -> String {
+        format!("mqtt {}", self.broker.addr)
+    }
+
+    fn collect(&mut self, scrape: &Scrape) {
+        let base = format!("{}/{}", self.broker.topic, sanitize(&scrape.name));
+
+        let mut stats: Vec<(String, String, &str)> = Vec::new();
+        for (path, value) in scrape.data.gauges() {
+            stats.push((path, value.to_string(), "measurement"));
+        }
+        for (path, value) in scrape.data.counters() {
+            stats.push((path, value.to_string(), "total_increasing"));
+        }
+
+        for (path, value, state_class) in stats {
+            let topic = format!("{}/{}", base, path.replace('.', "/"));
+            if self.broker.discovery && !self.announced.contains(&topic) {
+                self.announce(scrape, &path, &topic, state_class);
+            }
+            self.messages.insert(topic, value);
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.messages.is_empty() {
+            return Ok(());
+        }
+
+        let addr = match self.broker.addr.to_socket_addrs()?.next() {
+            Some(addr) => addr,
+            None => return Err(Error::new(ErrorKind::IoAddrNotAvailable)),
+        };
+        let mut stream = TcpStream::connect_timeout(&addr, TIMEOUT)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+
+        stream.write_all(&connect(
+            &format!("opentracker-exporter-{}", std::process::id()),
+            &self.broker.auth,
+        ))?;
+        let mut connack = [0; 4];
+        stream.read_exact(&mut connack)?;
+        if connack[0] != 0x20 || connack[3] != 0 {
+            return Err(Error::new(ErrorKind::Other(format!(
+                "mqtt broker refused connection with code {}",
+                connack[3]
+            ))));
+        }
+
+        for (topic, payload) in &self.messages {
+            stream.write_all(&publish(topic, payload.as_bytes()))?;
+        }
+        // DISCONNECT
+        stream.write_all(&[0xe0, 0x00])?;
+        stream.flush()?;
+
+        self.messages.clear();
+        Ok(())
+    }
+}
+
+/// builds a packet from the first header byte and the rest of the packet
+fn packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut ret = vec![header];
+    let mut length = body.len();
+    loop {
+        let mut byte = (length % 128) as u8;
+        length /= 128;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        ret.push(byte);
+        if length == 0 {
+            break;
+        }
+    }
+    ret.extend_from_slice(body);
+    ret
+}
+
+/// appends a length prefixed string
+fn push_string(buffer: &mut Vec<u8>, value: &str) {
+    buffer.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    buffer.extend_from_slice(value.as_bytes());
+}
+
+/// builds a CONNECT packet with a clean session
+fn connect(client_id: &str, auth: &Option<(String, String)>) -> Vec<u8> {
+    let mut body = Vec::new();
+    push_string(&mut body, "MQTT");
+    body.push(4); // protocol level 3.1.1
+
+    let mut flags = 0x02; // clean session
+    if auth.is_some() {
+        flags |= 0xc0; // user and password
+    }
+    body.push(flags);
+    body.extend_from_slice(&60u16.to_be_bytes()); // keep alive
+
+    push_string(&mut body, client_id);
+    if let Some((user, password)) = auth {
+        push_string(&mut body, user);
+        push_string(&mut body, password);
+    }
+    packet(0x10, &body)
+}
+
+/// builds a retained PUBLISH packet with qos 0
+fn publish(topic: &str, payload: &[u8]) -> Vec<u8> {
+    let mut body = Vec::new();
+    push_string(&mut body, topic);
+    body.extend_from_slice(payload);
+    packet(0x31, &body)
+}
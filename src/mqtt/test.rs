@@ -0,0 +1,138 @@
+//! test file to test publishing against a local broker stand-in
+
+use super::{connect, packet, publish, Broker, Mqtt};
+use crate::collector::{test, Scrape, Sink};
+use std::io::prelude::*;
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+fn scrape() -> Scrape {
+    test::scrape("tracker.example", 100, |data| {
+        data.peers = 300;
+        data.connections.udp_announce = 3000;
+    })
+}
+
+/// reads one packet, returns the first header byte and the rest
+fn read_packet(stream: &mut TcpStream) -> (u8, Vec<u8>) {
+    let mut header = [0; 1];
+    stream.read_exact(&mut header).unwrap();
+    let mut length = 0;
+    let mut shift = 0;
+    loop {
+        let mut byte = [0; 1];
+        stream.read_exact(&mut byte).unwrap();
+        length |= ((byte[0] & 0x7f) as usize) << shift;
+        shift += 7;
+        if byte[0] < 0x80 {
+            break;
+        }
+    }
+    let mut body = vec![0; length];
+    stream.read_exact(&mut body).unwrap();
+    (header[0], body)
+}
+
+/// header, topic and payload of a published message
+type Message = (u8, String, String);
+
+/// starts a broker accepting one connection, returns the published messages
+fn broker() -> (String, mpsc::Receiver<Vec<Message>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let (header, _) = read_packet(&mut stream);
+        assert_eq!(header, 0x10);
+        stream.write_all(&[0x20, 0x02, 0x00, 0x00]).unwrap();
+
+        let mut messages = Vec::new();
+        loop {
+            let (header, body) = read_packet(&mut stream);
+            if header == 0xe0 {
+                break;
+            }
+            let length = u16::from_be_bytes([body[0], body[1]]) as usize;
+            messages.push((
+                header,
+                String::from_utf8_lossy(&body[2..2 + length]).to_string(),
+                String::from_utf8_lossy(&body[2 + length..]).to_string(),
+            ));
+        }
+        sender.send(messages).unwrap();
+    });
+    (addr, receiver)
+}
+
+#[test]
+fn remaining_length() {
+    assert_eq!(packet(0xe0, &[]), vec![0xe0, 0x00]);
+    assert_eq!(&packet(0x31, &[0; 200])[..3], &[0x31, 0xc8, 0x01]);
+}
+
+#[test]
+fn connect_packet() {
+    let auth = Some((String::from("u"), String::from("p")));
+    assert_eq!(
+        connect("id", &auth),
+        vec![
+            0x10, 0x14, 0x00, 0x04, b'M', b'Q', b'T', b'T', 0x04, 0xc2, 0x00, 0x3c, 0x00, 0x02,
+            b'i', b'd', 0x00, 0x01, b'u', 0x00, 0x01, b'p'
+        ]
+    );
+}
+
+#[test]
+fn publish_packet() {
+    assert_eq!(
+        publish("a/b", b"1"),
+        vec![0x31, 0x06, 0x00, 0x03, b'a', b'/', b'b', b'1']
+    );
+}
+
+#[test]
+fn flush() {
+    let (addr, messages) = broker();
+    let mut sink = Mqtt::new(Broker::new(&addr));
+    sink.collect(&scrape());
+    sink.flush().unwrap();
+    assert!(sink.messages.is_empty());
+
+    let messages = messages.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(messages.contains(&(
+        0x31,
+        String::from("opentracker/tracker_example/peers"),
+        String::from("300")
+    )));
+    assert!(messages.contains(&(
+        0x31,
+        String::from("opentracker/tracker_example/connections/udp/announce"),
+        String::from("3000")
+    )));
+}
+
+#[test]
+fn home_assistant_discovery() {
+    let mut broker = Broker::new("localhost");
+    broker.discovery = true;
+    let mut sink = Mqtt::new(broker);
+    sink.collect(&scrape());
+
+    let config = sink
+        .messages
+        .get("homeassistant/sensor/tracker_example/peers/config")
+        .unwrap();
+    assert!(config.contains(r#""state_topic":"opentracker/tracker_example/peers""#));
+    assert!(config.contains(r#""state_class":"measurement""#));
+
+    // discovery is only published once
+    sink.messages.clear();
+    sink.collect(&scrape());
+    assert!(!sink
+        .messages
+        .keys()
+        .any(|topic| topic.starts_with("homeassistant/")));
+}
@@ -0,0 +1,195 @@
+use std::fmt::Write;
+
+use super::{scrape, Config, Everything};
+
+// tests as sub module
+#[cfg(test)] // only add when running tests
+mod test;
+
+/// Mode is the command munin-node runs the plugin with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    /// print the graph configuration
+    Config,
+    /// print if the plugin can run on this host
+    Autoconf,
+    /// print the current values
+    Fetch,
+}
+
+impl Mode {
+    /// parses the first argument of the plugin, no argument means fetch
+    pub fn from_arg(arg: Option<&str>) -> Option<Self> {
+        match arg {
+            None | Some("fetch") => Some(Mode::Fetch),
+            Some("config") => Some(Mode::Config),
+            Some("autoconf") => Some(Mode::Autoconf),
+            _ => None,
+        }
+    }
+}
+
+/// returns true if the binary was invoked as munin plugin, e.g. `/etc/munin/plugins/opentracker_munin`
+pub fn invoked_as(arg0: &str) -> bool {
+    std::path::Path::new(arg0)
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().contains("munin"))
+}
+
+/// applies the plugin environment set with `env.url`, `env.name` and `env.prefix`
+pub fn configure(conf: &mut Config) {
+    if let Ok(url) = std::env::var("url") {
+        conf.url = url;
+    }
+    conf.name = std::env::var("name").unwrap_or_else(|_| conf.url.clone());
+    if let Ok(prefix) = std::env::var("prefix") {
+        conf.prefix = prefix;
+    }
+}
+
+/// scrapes the tracker and returns the plugin output for `mode`
+pub fn run(conf: &Config, mode: Mode) -> String {
+    let data = scrape(&conf.upstream());
+    match mode {
+        Mode::Autoconf => match data {
+            Ok(_) => String::from("yes\n"),
+            Err(err) => format!("no ({}: {})\n", conf.url, err.kind().error_string()),
+        },
+        Mode::Config => config(data.as_ref().ok(), &conf.prefix, &conf.name),
+        Mode::Fetch => fetch(data.as_ref().ok(), &conf.prefix, &conf.name),
+    }
+}
+
+/// returns a munin field or graph name, only `[A-Za-z0-9_]` is allowed
+fn field(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Graph describes one graph of the multigraph plugin
+struct Graph {
+    name: &'static str,
+    title: &'static str,
+    vlabel: &'static str,
+    derive: bool,
+    values: Vec<(String, String, Option<f64>)>,
+}
+
+/// returns the graphs with labels and values, all values are unknown without data
+fn graphs(data: Option<&Everything>) -> Vec<Graph> {
+    let value = |get: &dyn Fn(&Everything) -> f64| data.map(get);
+    let connection = |name: &str, get: &dyn Fn(&Everything) -> usize| {
+        (field(name), name.replace('_', " "), value(&|data| get(data) as f64))
+    };
+
+    let mut http_codes = Vec::new();
+    if let Some(data) = data {
+        let mut codes: Vec<(&String, &usize)> = data.http_error.iter().collect();
+        codes.sort();
+        for (code, count) in codes {
+            http_codes.push((format!("code_{}", field(code)), code.to_string(), Some(*count as f64)));
+        }
+    }
+
+    vec![
+        Graph {
+            name: "connections",
+            title: "connections",
+            vlabel: "requests per ${graph_period}",
+            derive: true,
+            values: vec![
+                connection("tcp_accept", &|data| data.connections.tcp_accept),
+                connection("tcp_announce", &|data| data.connections.tcp_announce),
+                connection("tcp_scrape", &|data| data.connections.tcp_scrape),
+                connection("udp_overall", &|data| data.connections.udp_overall),
+                connection("udp_connect", &|data| data.connections.udp_connect),
+                connection("udp_announce", &|data| data.connections.udp_announce),
+                connection("udp_scrape", &|data| data.connections.udp_scrape),
+                connection("udp_missmatch", &|data| data.connections.udp_missmatch),
+            ],
+        },
+        Graph {
+            name: "peers",
+            title: "peers and seeds",
+            vlabel: "peers",
+            derive: false,
+            values: vec![
+                (String::from("peers"), String::from("peers"), value(&|data| data.peers as f64)),
+                (String::from("seeds"), String::from("seeds"), value(&|data| data.seeds as f64)),
+            ],
+        },
+        Graph {
+            name: "torrents",
+            title: "torrents",
+            vlabel: "torrents",
+            derive: false,
+            values: vec![
+                (
+                    String::from("mutex"),
+                    String::from("mutex"),
+                    value(&|data| data.torrents.mutex as f64),
+                ),
+                (
+                    String::from("iterator"),
+                    String::from("iterator"),
+                    value(&|data| data.torrents.iterator as f64),
+                ),
+            ],
+        },
+        Graph {
+            name: "http_codes",
+            title: "http errors",
+            vlabel: "errors per ${graph_period}",
+            derive: true,
+            values: http_codes,
+        },
+    ]
+}
+
+/// returns the name of the multigraph
+fn multigraph(prefix: &str, name: &str, graph: &Graph) -> String {
+    format!("multigraph {}_{}_{}\n", field(prefix), field(name), graph.name)
+}
+
+/// returns the configuration of all graphs
+pub(crate) fn config(data: Option<&Everything>, prefix: &str, name: &str) -> String {
+    let mut ret = String::new();
+    for graph in graphs(data) {
+        ret.push_str(&multigraph(prefix, name, &graph));
+        let _ = writeln!(ret, "graph_title opentracker {} {}", graph.title, name);
+        let _ = writeln!(ret, "graph_vlabel {}", graph.vlabel);
+        ret.push_str("graph_category network\n");
+        if graph.derive {
+            ret.push_str("graph_args --base 1000 -l 0\n");
+        }
+        for (field, label, _) in &graph.values {
+            let _ = writeln!(ret, "{}.label {}", field, label);
+            if graph.derive {
+                let _ = writeln!(ret, "{}.type DERIVE", field);
+                let _ = writeln!(ret, "{}.min 0", field);
+            }
+        }
+    }
+    ret
+}
+
+/// returns the values of all graphs, `U` marks unknown values
+pub(crate) fn fetch(data: Option<&Everything>, prefix: &str, name: &str) -> String {
+    let mut ret = String::new();
+    for graph in graphs(data) {
+        ret.push_str(&multigraph(prefix, name, &graph));
+        for (field, _, value) in &graph.values {
+            match value {
+                Some(value) => {
+                    let _ = writeln!(ret, "{}.value {}", field, value);
+                }
+                None => {
+                    let _ = writeln!(ret, "{}.value U", field);
+                }
+            }
+        }
+    }
+    ret
+}
@@ -0,0 +1,54 @@
+//! test file to test the munin plugin output
+
+use super::{config, fetch, invoked_as, Mode};
+use crate::{parse_everything, test::EVERYTHING};
+
+#[test]
+fn mode() {
+    assert_eq!(Mode::from_arg(None), Some(Mode::Fetch));
+    assert_eq!(Mode::from_arg(Some("config")), Some(Mode::Config));
+    assert_eq!(Mode::from_arg(Some("autoconf")), Some(Mode::Autoconf));
+    assert_eq!(Mode::from_arg(Some("suggest")), None);
+}
+
+#[test]
+fn invoked() {
+    assert!(invoked_as("/etc/munin/plugins/opentracker_munin"));
+    assert!(!invoked_as("/usr/bin/opentracker_exporter"));
+}
+
+#[test]
+fn config_output() {
+    let data = parse_everything(EVERYTHING).unwrap();
+    let config = config(Some(&data), "ot", "tracker.example");
+    assert!(config.starts_with(
+        "multigraph ot_tracker_example_connections\n\
+         graph_title opentracker connections tracker.example\n\
+         graph_vlabel requests per ${graph_period}\n\
+         graph_category network\n\
+         graph_args --base 1000 -l 0\n\
+         tcp_accept.label tcp accept\n\
+         tcp_accept.type DERIVE\n\
+         tcp_accept.min 0\n"
+    ));
+    assert!(config.contains("multigraph ot_tracker_example_peers\n"));
+    assert!(config.contains("\npeers.label peers\nseeds.label seeds\n"));
+    assert!(config.contains("\ncode_400_Invalid_Request.label 400 Invalid Request\ncode_400_Invalid_Request.type DERIVE\n"));
+}
+
+#[test]
+fn fetch_output() {
+    let data = parse_everything(EVERYTHING).unwrap();
+    let values = fetch(Some(&data), "ot", "test");
+    assert!(values.contains("multigraph ot_test_connections\ntcp_accept.value 1000\n"));
+    assert!(values.contains("\nudp_missmatch.value 7\n"));
+    assert!(values.contains("multigraph ot_test_torrents\nmutex.value 42\niterator.value 41\n"));
+    assert!(values.ends_with("multigraph ot_test_http_codes\ncode_400_Invalid_Request.value 3\n"));
+}
+
+#[test]
+fn fetch_unreachable() {
+    let values = fetch(None, "ot", "test");
+    assert!(values.contains("multigraph ot_test_peers\npeers.value U\nseeds.value U\n"));
+    assert!(values.ends_with("multigraph ot_test_http_codes\n"));
+}
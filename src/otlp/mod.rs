@@ -0,0 +1,163 @@
+use std::collections::VecDeque;
+use std::time::UNIX_EPOCH;
+
+use super::collector::{Scrape, Sink};
+use super::error::Result;
+use super::hostname;
+use super::http::{self, Url};
+use super::protobuf::Writer;
+
+// tests as sub module
+#[cfg(test)] // only add when running tests
+mod test;
+
+/// maximum number of requests kept while the collector is unreachable
+const MAX_QUEUE: usize = 100;
+
+/// Endpoint describes the opentelemetry collector to export to
+#[derive(Clone, Debug)]
+pub struct Endpoint {
+    /// base url like `http://otel:4318`, `/v1/metrics` is appended if no path is given
+    pub url: String,
+
+    /// additional http headers, e.g. for authentication
+    pub headers: Vec<(String, String)>,
+
+    /// value of the `service.name` resource attribute
+    pub service_name: String,
+}
+
+impl Endpoint {
+    /// creates a new Endpoint for `url` with default values
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            headers: Vec::new(),
+            service_name: String::from("opentracker"),
+        }
+    }
+}
+
+/// Otlp is a sink exporting metrics with OTLP/HTTP in the protobuf encoding
+///
+/// Current values become gauges, values counting up since the tracker started
+/// become monotonic cumulative sums starting at the tracker start.
+/// The tracker is the `service.instance.id`, `host.name` is the host of the exporter.
+pub struct Otlp {
+    endpoint: Endpoint,
+    url: Url,
+    prefix: String,
+    /// value of the `host.name` resource attribute, left out if unknown
+    host: Option<String>,
+    /// encoded `ExportMetricsServiceRequest`s waiting to be sent
+    queue: VecDeque<Vec<u8>>,
+}
+
+impl Otlp {
+    /// creates a new Otlp for metrics named with `prefix`
+    pub fn new(endpoint: Endpoint, prefix: &str) -> Result<Self> {
+        let mut url = Url::parse(&endpoint.url)?;
+        if url.path == "/" {
+            url.path = String::from("/v1/metrics");
+        }
+        Ok(Self {
+            endpoint,
+            url,
+            prefix: prefix.to_string(),
+            host: hostname(),
+            queue: VecDeque::new(),
+        })
+    }
+
+    /// encodes a scrape as `ExportMetricsServiceRequest`
+    fn encode(&self, scrape: &Scrape) -> Vec<u8> {
+        let time = match scrape.time.duration_since(UNIX_EPOCH) {
+            Ok(time) => time.as_nanos() as u64,
+            Err(_) => 0,
+        };
+        let start = time.saturating_sub(scrape.data.uptime as u64 * 1_000_000_000);
+
+        let mut request = Writer::new();
+        request.message(1, |resource_metrics| {
+            resource_metrics.message(1, |resource| {
+                string_attribute(resource, 1, "service.name", &self.endpoint.service_name);
+                string_attribute(resource, 1, "service.instance.id", &scrape.name);
+                if let Some(host) = &self.host {
+                    string_attribute(resource, 1, "host.name", host);
+                }
+                resource.message(1, |attribute| {
+                    attribute.string(1, "opentracker.tracker_id");
+                    attribute.message(2, |value| value.int64(3, scrape.data.tracker_id as i64));
+                });
+            });
+            resource_metrics.message(2, |scope_metrics| {
+                scope_metrics.message(1, |scope| {
+                    scope.string(1, "opentracker-exporter");
+                    scope.string(2, env!("CARGO_PKG_VERSION"));
+                });
+                for (path, value) in scrape.data.gauges() {
+                    scope_metrics.message(2, |metric| {
+                        metric.string(1, &format!("{}.{}", self.prefix, path));
+                        metric.message(5, |gauge| {
+                            gauge.message(1, |point| {
+                                point.fixed64(3, time);
+                                point.double(4, value);
+                            });
+                        });
+                    });
+                }
+                for (path, value) in scrape.data.counters() {
+                    scope_metrics.message(2, |metric| {
+                        metric.string(1, &format!("{}.{}", self.prefix, path));
+                        metric.message(7, |sum| {
+                            sum.message(1, |point| {
+                                point.fixed64(2, start);
+                                point.fixed64(3, time);
+                                point.fixed64(6, value);
+                            });
+                            sum.uint64(2, 2); // AGGREGATION_TEMPORALITY_CUMULATIVE
+                            sum.uint64(3, 1); // is_monotonic
+                        });
+                    });
+                }
+            });
+        });
+        request.into_bytes()
+    }
+}
+
+/// writes a `KeyValue` with a string value as field `field`
+fn string_attribute(writer: &mut Writer, field: u32, key: &str, value: &str) {
+    writer.message(field, |attribute| {
+        attribute.string(1, key);
+        attribute.message(2, |any| any.string(1, value));
+    });
+}
+
+impl Sink for Otlp {
+    fn name(&self) -> String {
+        format!("otlp {}", self.endpoint.url)
+    }
+
+    fn collect(&mut self, scrape: &Scrape) {
+        let request = self.encode(scrape);
+        self.queue.push_back(request);
+        if self.queue.len() > MAX_QUEUE {
+            self.queue.pop_front();
+            eprintln!("otlp queue full, dropped oldest export");
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let mut headers: Vec<(&str, &str)> = vec![("Content-Type", "application/x-protobuf")];
+        for (key, value) in &self.endpoint.headers {
+            headers.push((key, value));
+        }
+
+        while let Some(request) = self.queue.front() {
+            http::request("POST", &self.url, &headers, request)?;
+            self.queue.pop_front();
+        }
+        Ok(())
+    }
+}
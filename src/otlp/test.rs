@@ -0,0 +1,93 @@
+//! test file for the otlp sink
+
+use super::{Endpoint, Otlp};
+use crate::collector::{test, Scrape, Sink};
+use crate::http::test::stand_in;
+use std::time::Duration;
+
+fn scrape() -> Scrape {
+    test::scrape("tracker", 100, |data| {
+        data.tracker_id = 7;
+        data.uptime = 10;
+        data.peers = 300;
+        data.completed = 55;
+    })
+}
+
+/// searches `needle` in `haystack`
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|window| window == needle)
+}
+
+#[test]
+fn default_path() {
+    let sink = Otlp::new(Endpoint::new("http://localhost:4318"), "ot").unwrap();
+    assert_eq!(sink.url.path, String::from("/v1/metrics"));
+
+    let sink = Otlp::new(Endpoint::new("http://localhost:4318/otlp/v1/metrics"), "ot").unwrap();
+    assert_eq!(sink.url.path, String::from("/otlp/v1/metrics"));
+}
+
+#[test]
+fn encode_gauge() {
+    let mut sink = Otlp::new(Endpoint::new("http://localhost:4318"), "ot").unwrap();
+    sink.host = Some(String::from("exporter"));
+    let request = sink.encode(&scrape());
+
+    // Metric { name = "ot.peers", gauge { data_points { time_unix_nano, as_double = 300 } } }
+    let mut gauge = vec![0x0a, 0x08];
+    gauge.extend_from_slice(b"ot.peers");
+    gauge.extend_from_slice(&[0x2a, 0x14, 0x0a, 0x12, 0x19]);
+    gauge.extend_from_slice(&100_000_000_000u64.to_le_bytes());
+    gauge.push(0x21);
+    gauge.extend_from_slice(&300f64.to_le_bytes());
+    assert!(contains(&request, &gauge));
+
+    assert!(contains(&request, b"\x0a\x0cservice.name\x12\x0d\x0a\x0bopentracker"));
+    assert!(contains(&request, b"\x0a\x13service.instance.id\x12\x09\x0a\x07tracker"));
+    assert!(contains(&request, b"\x0a\x09host.name\x12\x0a\x0a\x08exporter"));
+}
+
+#[test]
+fn encode_sum() {
+    let sink = Otlp::new(Endpoint::new("http://localhost:4318"), "ot").unwrap();
+    let request = sink.encode(&scrape());
+
+    // Metric { name = "ot.completed", sum { data_points { start, time, as_int = 55 }, cumulative, monotonic } }
+    let mut sum = vec![0x0a, 0x0c];
+    sum.extend_from_slice(b"ot.completed");
+    sum.extend_from_slice(&[0x3a, 0x21, 0x0a, 0x1b, 0x11]);
+    sum.extend_from_slice(&90_000_000_000u64.to_le_bytes());
+    sum.push(0x19);
+    sum.extend_from_slice(&100_000_000_000u64.to_le_bytes());
+    sum.push(0x31);
+    sum.extend_from_slice(&55u64.to_le_bytes());
+    sum.extend_from_slice(&[0x10, 0x02, 0x18, 0x01]);
+    assert!(contains(&request, &sum));
+}
+
+#[test]
+fn flush() {
+    let (addr, request) = stand_in("200 OK");
+    let mut endpoint = Endpoint::new(&format!("http://{}", addr));
+    endpoint.headers = vec![(String::from("X-Scope-OrgID"), String::from("edge"))];
+    let mut sink = Otlp::new(endpoint, "ot").unwrap();
+    sink.collect(&scrape());
+    sink.flush().unwrap();
+    assert!(sink.queue.is_empty());
+
+    let request = request.recv_timeout(Duration::from_secs(5)).unwrap();
+    let request = String::from_utf8_lossy(&request);
+    assert!(request.starts_with("POST /v1/metrics HTTP/1.1\r\n"));
+    assert!(request.contains("\r\nContent-Type: application/x-protobuf\r\n"));
+    assert!(request.contains("\r\nX-Scope-OrgID: edge\r\n"));
+}
+
+#[test]
+fn keeps_queue_on_error() {
+    let (addr, _request) = stand_in("503 Service Unavailable");
+    let mut sink = Otlp::new(Endpoint::new(&format!("http://{}", addr)), "ot").unwrap();
+    sink.collect(&scrape());
+    assert!(sink.flush().is_err());
+    assert_eq!(sink.queue.len(), 1);
+}
@@ -0,0 +1,78 @@
+// protobuf is a minimal encoder for the protobuf wire format
+
+// tests as sub module
+#[cfg(test)] // only add when running tests
+mod test;
+
+/// Writer encodes fields into a protobuf message
+#[derive(Default)]
+pub struct Writer {
+    buffer: Vec<u8>,
+}
+
+impl Writer {
+    /// creates a new empty message
+    pub fn new() -> Self {
+        Self { buffer: Vec::new() }
+    }
+
+    /// returns the encoded message
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buffer
+    }
+
+    /// writes a base 128 varint
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.buffer.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        self.buffer.push(value as u8);
+    }
+
+    /// writes the key of a field
+    fn key(&mut self, field: u32, wire_type: u8) {
+        self.varint((u64::from(field) << 3) | u64::from(wire_type));
+    }
+
+    /// writes an uint64, uint32 or enum field
+    pub fn uint64(&mut self, field: u32, value: u64) {
+        self.key(field, 0);
+        self.varint(value);
+    }
+
+    /// writes an int64 field
+    pub fn int64(&mut self, field: u32, value: i64) {
+        self.uint64(field, value as u64);
+    }
+
+    /// writes a fixed64 field
+    pub fn fixed64(&mut self, field: u32, value: u64) {
+        self.key(field, 1);
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// writes a double field
+    pub fn double(&mut self, field: u32, value: f64) {
+        self.fixed64(field, value.to_bits());
+    }
+
+    /// writes a bytes field
+    pub fn bytes(&mut self, field: u32, value: &[u8]) {
+        self.key(field, 2);
+        self.varint(value.len() as u64);
+        self.buffer.extend_from_slice(value);
+    }
+
+    /// writes a string field
+    pub fn string(&mut self, field: u32, value: &str) {
+        self.bytes(field, value.as_bytes());
+    }
+
+    /// writes an embedded message built by `build`
+    pub fn message<F: FnOnce(&mut Writer)>(&mut self, field: u32, build: F) {
+        let mut message = Writer::new();
+        build(&mut message);
+        self.bytes(field, &message.buffer);
+    }
+}
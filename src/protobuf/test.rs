@@ -0,0 +1,41 @@
+//! test file for the protobuf encoder
+
+use super::Writer;
+
+#[test]
+fn varint() {
+    let mut writer = Writer::new();
+    writer.uint64(1, 150);
+    assert_eq!(writer.into_bytes(), vec![0x08, 0x96, 0x01]);
+}
+
+#[test]
+fn negative_int64() {
+    let mut writer = Writer::new();
+    writer.int64(2, -1);
+    assert_eq!(
+        writer.into_bytes(),
+        vec![0x10, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]
+    );
+}
+
+#[test]
+fn double() {
+    let mut writer = Writer::new();
+    writer.double(1, 1.0);
+    assert_eq!(writer.into_bytes(), vec![0x09, 0, 0, 0, 0, 0, 0, 0xf0, 0x3f]);
+}
+
+#[test]
+fn string() {
+    let mut writer = Writer::new();
+    writer.string(2, "testing");
+    assert_eq!(writer.into_bytes(), b"\x12\x07testing".to_vec());
+}
+
+#[test]
+fn message() {
+    let mut writer = Writer::new();
+    writer.message(3, |message| message.uint64(1, 150));
+    assert_eq!(writer.into_bytes(), vec![0x1a, 0x03, 0x08, 0x96, 0x01]);
+}
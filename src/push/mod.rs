@@ -0,0 +1,127 @@
+use super::collector::{Scrape, Sink};
+use super::error::{Error, ErrorKind, Result};
+use super::http::{self, Url};
+
+// tests as sub module
+#[cfg(test)] // only add when running tests
+mod test;
+
+/// http method used to push to the pushgateway
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Method {
+    /// replaces all metrics of the grouping key
+    Put,
+
+    /// only replaces metrics with the same name
+    Post,
+}
+
+impl std::str::FromStr for Method {
+    type Err = Error;
+
+    fn from_str(method: &str) -> Result<Self> {
+        match method.to_lowercase().as_str() {
+            "put" => Ok(Method::Put),
+            "post" => Ok(Method::Post),
+            _ => Err(Error::new(ErrorKind::NotParsable(format!("unknown push method {}", method)))),
+        }
+    }
+}
+
+/// Gateway describes the pushgateway and the grouping key to push to
+#[derive(Clone, Debug)]
+pub struct Gateway {
+    /// url of the pushgateway, e.g. `http://localhost:9091`
+    pub url: String,
+
+    /// job label of the grouping key
+    pub job: String,
+
+    /// instance label of the grouping key
+    pub instance: String,
+
+    /// http method to push with
+    pub method: Method,
+
+    /// user and password for basic auth, overrides credentials in the url
+    pub auth: Option<(String, String)>,
+}
+
+impl Gateway {
+    /// creates a new Gateway for `url` with default values
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            job: String::from("opentracker"),
+            instance: String::from("tracker"),
+            method: Method::Put,
+            auth: None,
+        }
+    }
+
+    /// returns the url of the grouping key
+    fn target(&self) -> Result<Url> {
+        let mut url = Url::parse(&self.url)?.join(&format!(
+            "metrics/job/{}/instance/{}",
+            http::encode_segment(&self.job),
+            http::encode_segment(&self.instance)
+        ));
+        if self.auth.is_some() {
+            url.auth = self.auth.clone();
+        }
+        Ok(url)
+    }
+
+    /// pushes the rendered metrics once
+    pub fn push(&self, content: &str) -> Result<()> {
+        let method = match self.method {
+            Method::Put => "PUT",
+            Method::Post => "POST",
+        };
+        http::request(
+            method,
+            &self.target()?,
+            &[("Content-Type", "text/plain; version=0.0.4")],
+            content.as_bytes(),
+        )?;
+        Ok(())
+    }
+}
+
+/// Pusher is a sink pushing the latest scrape to a Gateway
+pub struct Pusher {
+    gateway: Gateway,
+    prefix: String,
+    content: Option<String>,
+}
+
+impl Pusher {
+    /// creates a new Pusher for metrics named with `prefix`
+    pub fn new(gateway: Gateway, prefix: &str) -> Result<Self> {
+        gateway.target()?;
+        Ok(Self {
+            gateway,
+            prefix: prefix.to_string(),
+            content: None,
+        })
+    }
+}
+
+impl Sink for Pusher {
+    fn name(&self) -> String {
+        format!("pushgateway {}", self.gateway.url)
+    }
+
+    fn collect(&mut self, scrape: &Scrape) {
+        // the pushgateway only keeps the latest value, older scrapes are replaced
+        self.content = Some(scrape.data.get_string(&self.prefix, &scrape.name));
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if let Some(content) = &self.content {
+            self.gateway.push(content)?;
+            self.content = None;
+        }
+        Ok(())
+    }
+}
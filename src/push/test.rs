@@ -0,0 +1,50 @@
+//! test file to test pushing against a local pushgateway stand-in
+
+mod gateway {
+    use super::super::{Gateway, Method};
+    use crate::error::ErrorKind;
+    use crate::http::test::stand_in;
+    use std::time::Duration;
+
+    #[test]
+    fn put() {
+        let (addr, request) = stand_in("200 OK");
+        let mut gateway = Gateway::new(&format!("http://{}", addr));
+        gateway.instance = String::from("tracker 1");
+        gateway.push("metric 1\n").unwrap();
+
+        let request = String::from_utf8(request.recv_timeout(Duration::from_secs(5)).unwrap()).unwrap();
+        assert!(request.starts_with("PUT /metrics/job/opentracker/instance/tracker%201 HTTP/1.1\r\n"));
+        assert!(request.contains("\r\nContent-Type: text/plain; version=0.0.4\r\n"));
+        assert!(request.ends_with("\r\n\r\nmetric 1\n"));
+    }
+
+    #[test]
+    fn post_with_auth() {
+        let (addr, request) = stand_in("202 Accepted");
+        let mut gateway = Gateway::new(&format!("http://{}/prefix", addr));
+        gateway.method = Method::Post;
+        gateway.auth = Some((String::from("user"), String::from("pass")));
+        gateway.push("metric 1\n").unwrap();
+
+        let request = String::from_utf8(request.recv_timeout(Duration::from_secs(5)).unwrap()).unwrap();
+        assert!(request.starts_with("POST /prefix/metrics/job/opentracker/instance/tracker HTTP/1.1\r\n"));
+        assert!(request.contains("\r\nAuthorization: Basic dXNlcjpwYXNz\r\n"));
+    }
+
+    #[test]
+    fn error_status() {
+        let (addr, _request) = stand_in("500 Internal Server Error");
+        let gateway = Gateway::new(&addr);
+        let err = gateway.push("metric 1\n").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::HttpStatus(500));
+    }
+}
+
+#[test]
+fn method_from_str() {
+    use super::Method;
+    assert_eq!("PUT".parse::<Method>().unwrap(), Method::Put);
+    assert_eq!("post".parse::<Method>().unwrap(), Method::Post);
+    assert!("get".parse::<Method>().is_err());
+}
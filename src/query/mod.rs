@@ -0,0 +1,169 @@
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::error::Error;
+use super::{json, scrape, Everything, Upstream};
+
+// tests as sub module
+#[cfg(test)] // only add when running tests
+mod test;
+
+/// version of the json document, raised on incompatible changes
+pub const VERSION: u32 = 1;
+
+/// Format is the output format of the query subcommand
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Json,
+    Table,
+}
+
+impl std::str::FromStr for Format {
+    type Err = Error;
+
+    fn from_str(format: &str) -> Result<Self, Error> {
+        match format {
+            "json" => Ok(Format::Json),
+            "table" => Ok(Format::Table),
+            _ => Err(Error::new(super::error::ErrorKind::FormatNotSupported)),
+        }
+    }
+}
+
+/// Report is the result of one scrape with its metadata
+pub struct Report {
+    name: String,
+    url: String,
+    time: SystemTime,
+    duration: Duration,
+    data: Arc<Result<Everything, Error>>,
+}
+
+impl Report {
+    /// scrapes the tracker named `name` once
+    pub fn new(upstream: &Upstream, name: &str) -> Self {
+        let time = SystemTime::now();
+        let start = Instant::now();
+        let data = Arc::new(scrape(upstream));
+        Self::scraped(upstream, name, time, start.elapsed(), data)
+    }
+
+    /// reports the tracker named `name` from a scrape made elsewhere, e.g. one shared with other requests
+    ///
+    /// `time` is when the scrape was asked for and `duration` how long it took.
+    pub(crate) fn scraped(
+        upstream: &Upstream,
+        name: &str,
+        time: SystemTime,
+        duration: Duration,
+        data: Arc<Result<Everything, Error>>,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            url: upstream.url.clone(),
+            time,
+            duration,
+            data,
+        }
+    }
+
+    /// returns true if the tracker could be scraped
+    pub fn success(&self) -> bool {
+        self.data.is_ok()
+    }
+
+    /// returns the report in the requested format
+    pub fn format(&self, format: Format) -> String {
+        match format {
+            Format::Json => self.json(),
+            Format::Table => self.table(),
+        }
+    }
+
+    /// returns the report as versioned json document
+    pub fn json(&self) -> String {
+        let time = self.time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let (error, tracker) = match &*self.data {
+            Ok(data) => (String::from("null"), tracker(data)),
+            Err(err) => (json::string(&err.kind().error_string()), String::from("null")),
+        };
+        format!(
+            r#"{{"version":{},"exporter":{},"scrape":{{"name":{},"url":{},"time":{},"duration_seconds":{},"success":{},"error":{}}},"tracker":{}}}"#,
+            VERSION,
+            json::string(env!("CARGO_PKG_VERSION")),
+            json::string(&self.name),
+            json::string(&self.url),
+            json::number(time.as_millis() as f64 / 1000.0),
+            json::number(self.duration.as_secs_f64()),
+            self.success(),
+            error,
+            tracker
+        )
+    }
+
+    /// returns the report as table for humans
+    pub fn table(&self) -> String {
+        let mut rows = vec![
+            (String::from("name"), self.name.clone()),
+            (String::from("url"), self.url.clone()),
+            (String::from("duration"), format!("{:?}", self.duration)),
+        ];
+        match &*self.data {
+            Ok(data) => {
+                rows.push((String::from("tracker_id"), data.tracker_id.to_string()));
+                for (path, value) in data.gauges() {
+                    rows.push((path, value.to_string()));
+                }
+                for (path, value) in data.counters() {
+                    rows.push((path, value.to_string()));
+                }
+            }
+            Err(err) => rows.push((String::from("error"), err.kind().error_string())),
+        }
+
+        let width = rows.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+        let mut ret = String::new();
+        for (key, value) in rows {
+            let _ = writeln!(ret, "{:width$}  {}", key, value, width = width);
+        }
+        ret
+    }
+}
+
+/// returns the tracker stats as json object
+fn tracker(data: &Everything) -> String {
+    let mut codes: Vec<(&String, &usize)> = data.http_error.iter().collect();
+    codes.sort();
+    let codes: Vec<String> = codes
+        .iter()
+        .map(|(code, count)| format!("{}:{}", json::string(code), count))
+        .collect();
+    let clock_skew = data
+        .clock_skew
+        .map_or(String::from("null"), |skew| skew.to_string());
+    let c = &data.connections;
+
+    format!(
+        r#"{{"id":{},"uptime":{},"clock_skew_seconds":{},"torrents":{{"mutex":{},"iterator":{}}},"peers":{},"seeds":{},"completed":{},"mutex_stall":{},"connections":{{"tcp":{{"accept":{},"announce":{},"scrape":{}}},"udp":{{"overall":{},"connect":{},"announce":{},"scrape":{},"missmatch":{}}},"livesync":{}}},"http_error":{{{}}}}}"#,
+        data.tracker_id,
+        data.uptime,
+        clock_skew,
+        data.torrents.mutex,
+        data.torrents.iterator,
+        data.peers,
+        data.seeds,
+        data.completed,
+        data.mutex_stall,
+        c.tcp_accept,
+        c.tcp_announce,
+        c.tcp_scrape,
+        c.udp_overall,
+        c.udp_connect,
+        c.udp_announce,
+        c.udp_scrape,
+        c.udp_missmatch,
+        c.livesync,
+        codes.join(",")
+    )
+}
@@ -0,0 +1,59 @@
+//! test file to test the json and table output
+
+use super::{Format, Report};
+use crate::error::{Error, ErrorKind};
+use crate::{parse_everything, test::EVERYTHING};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+fn report(data: Result<crate::Everything, Error>) -> Report {
+    Report {
+        name: String::from("test"),
+        url: String::from("localhost"),
+        time: UNIX_EPOCH + Duration::from_millis(1_600_000_000_500),
+        duration: Duration::from_millis(20),
+        data: Arc::new(data),
+    }
+}
+
+#[test]
+fn format() {
+    assert_eq!("json".parse::<Format>().unwrap(), Format::Json);
+    assert_eq!("table".parse::<Format>().unwrap(), Format::Table);
+    assert!("yaml".parse::<Format>().is_err());
+}
+
+#[test]
+fn json() {
+    let mut data = parse_everything(EVERYTHING).unwrap();
+    data.clock_skew = Some(-2);
+    assert_eq!(
+        report(Ok(data)).json(),
+        format!(
+            concat!(
+                r#"{{"version":1,"exporter":"{}","scrape":{{"name":"test","url":"localhost","time":1600000000.5,"duration_seconds":0.02,"success":true,"error":null}},"#,
+                r#""tracker":{{"id":1337,"uptime":3600,"clock_skew_seconds":-2,"torrents":{{"mutex":42,"iterator":41}},"peers":300,"seeds":120,"completed":55,"mutex_stall":2,"#,
+                r#""connections":{{"tcp":{{"accept":1000,"announce":900,"scrape":100}},"udp":{{"overall":5000,"connect":1500,"announce":3000,"scrape":400,"missmatch":7}},"livesync":0}},"#,
+                r#""http_error":{{"400 Invalid Request":3}}}}}}"#
+            ),
+            env!("CARGO_PKG_VERSION")
+        )
+    );
+}
+
+#[test]
+fn json_error() {
+    let json = report(Err(Error::new(ErrorKind::IoConnectionRefused))).json();
+    assert!(json.contains(r#""success":false,"error":"IoConnectionRefused"},"tracker":null}"#));
+}
+
+#[test]
+fn table() {
+    let table = report(Ok(parse_everything(EVERYTHING).unwrap())).table();
+    assert!(table.starts_with("name                            test\n"));
+    assert!(table.contains("\npeers                           300\n"));
+    assert!(table.contains("\nhttp_codes.400_Invalid_Request  3\n"));
+
+    let table = report(Err(Error::new(ErrorKind::IoTimedOut))).table();
+    assert!(table.ends_with("\nerror     IoTimedOut\n"));
+}
@@ -0,0 +1,270 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use super::collector::{Scrape, Sink};
+use super::error::{Error, ErrorKind, Result};
+use super::{sanitize, Everything};
+
+// tests as sub module
+#[cfg(test)] // only add when running tests
+mod test;
+
+/// maximum number of rows kept while the files cannot be written
+const MAX_QUEUE: usize = 10_000;
+
+/// first line of every file
+pub const HEADER: &str = "timestamp,time,name,tracker_id,uptime,torrents_mutex,torrents_iterator,peers,seeds,completed,mutex_stall,tcp_accept,tcp_announce,tcp_scrape,udp_overall,udp_connect,udp_announce,udp_scrape,udp_missmatch,livesync,http_error";
+
+/// Recorder is a sink appending every scrape to daily rotated csv files
+pub struct Recorder {
+    dir: PathBuf,
+    /// days to keep files for, older files are removed on rotation
+    keep: Option<u64>,
+    /// rows by file not written yet
+    rows: VecDeque<(PathBuf, String)>,
+    /// file written last, used to notice the rotation
+    current: Option<PathBuf>,
+}
+
+impl Recorder {
+    /// creates a new Recorder writing into `dir`
+    pub fn new(dir: &Path, keep: Option<u64>) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            keep,
+            rows: VecDeque::new(),
+            current: None,
+        })
+    }
+
+    /// removes the files of `tracker` older than `keep` days
+    ///
+    /// Failing to remove a file is only logged, recording goes on.
+    fn cleanup(&self, tracker: &str, today: u64) {
+        let keep = match self.keep {
+            Some(keep) => keep,
+            None => return,
+        };
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) => {
+                eprintln!("cannot list {} to remove old files: {}", self.dir.display(), err);
+                return;
+            }
+        };
+        for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+            if file_tracker(&path).as_deref() != Some(tracker) {
+                continue;
+            }
+            if file_day(&path).is_some_and(|day| day + keep <= today) {
+                if let Err(err) = fs::remove_file(&path) {
+                    eprintln!("cannot remove {}: {}", path.display(), err);
+                }
+            }
+        }
+    }
+}
+
+impl Sink for Recorder {
+    fn name(&self) -> String {
+        format!("csv files in {}", self.dir.display())
+    }
+
+    fn collect(&mut self, scrape: &Scrape) {
+        let timestamp = scrape.time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let path = self.dir.join(format!(
+            "{}-{}.csv",
+            sanitize(&scrape.name),
+            date(timestamp / 86400)
+        ));
+        self.rows.push_back((path, row(timestamp, &scrape.name, &scrape.data)));
+
+        if self.rows.len() > MAX_QUEUE {
+            let dropped = self.rows.len() - MAX_QUEUE;
+            self.rows.drain(..dropped);
+            eprintln!("csv queue full, dropped {} rows", dropped);
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        while let Some((path, row)) = self.rows.front() {
+            if self.current.as_ref() != Some(path) {
+                if let (Some(tracker), Some(day)) = (file_tracker(path), file_day(path)) {
+                    self.cleanup(&tracker, day);
+                }
+                self.current = Some(path.clone());
+            }
+
+            let new = !path.exists();
+            let mut file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+            if new {
+                writeln!(file, "{}", HEADER)?;
+            }
+            writeln!(file, "{}", row)?;
+            self.rows.pop_front();
+        }
+        Ok(())
+    }
+}
+
+/// returns the csv row of one scrape
+pub(crate) fn row(timestamp: u64, name: &str, data: &Everything) -> String {
+    let mut codes: Vec<(&String, &usize)> = data.http_error.iter().collect();
+    codes.sort();
+    let codes: Vec<String> = codes.iter().map(|(code, count)| format!("{}={}", code, count)).collect();
+    let c = &data.connections;
+    let fields = [
+        timestamp.to_string(),
+        format!("{}T{}Z", date(timestamp / 86400), clock(timestamp % 86400)),
+        escape(name),
+        data.tracker_id.to_string(),
+        data.uptime.to_string(),
+        data.torrents.mutex.to_string(),
+        data.torrents.iterator.to_string(),
+        data.peers.to_string(),
+        data.seeds.to_string(),
+        data.completed.to_string(),
+        data.mutex_stall.to_string(),
+        c.tcp_accept.to_string(),
+        c.tcp_announce.to_string(),
+        c.tcp_scrape.to_string(),
+        c.udp_overall.to_string(),
+        c.udp_connect.to_string(),
+        c.udp_announce.to_string(),
+        c.udp_scrape.to_string(),
+        c.udp_missmatch.to_string(),
+        c.livesync.to_string(),
+        escape(&codes.join(";")),
+    ];
+    fields.join(",")
+}
+
+/// quotes a csv field if needed
+fn escape(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// writes all rows from `from` until before `to` recorded in `dir` to `out`
+/// files are read in name order, so rows of one tracker stay together
+pub fn export(dir: &Path, from: u64, to: u64, name: Option<&str>, out: &mut dyn Write) -> Result<()> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| file_day(path).is_some_and(|day| day >= from / 86400 && day <= to / 86400))
+        .filter(|path| match name {
+            Some(name) => file_tracker(path).is_some_and(|tracker| tracker == sanitize(name)),
+            None => true,
+        })
+        .collect();
+    files.sort();
+
+    writeln!(out, "{}", HEADER)?;
+    for file in files {
+        for line in fs::read_to_string(&file)?.lines().skip(1) {
+            let timestamp = line.split(',').next().and_then(|time| time.parse::<u64>().ok());
+            if timestamp.is_some_and(|time| time >= from && time < to) {
+                writeln!(out, "{}", line)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// returns the day of a `{name}-YYYY-MM-DD.csv` file
+fn file_day(path: &Path) -> Option<u64> {
+    let name = path.file_name()?.to_string_lossy().to_string();
+    let stem = name.strip_suffix(".csv")?;
+    stem.get(stem.len().checked_sub(10)?..).and_then(parse_date)
+}
+
+/// returns the sanitized tracker name of a `{name}-YYYY-MM-DD.csv` file
+fn file_tracker(path: &Path) -> Option<String> {
+    file_day(path)?;
+    let name = path.file_name()?.to_string_lossy().to_string();
+    let stem = name.strip_suffix(".csv")?;
+    stem.get(..stem.len().checked_sub(11)?)
+        .filter(|_| stem.as_bytes()[stem.len() - 11] == b'-')
+        .map(str::to_string)
+}
+
+/// parses a unix time, `YYYY-MM-DD` or `YYYY-MM-DDTHH:MM:SSZ` to seconds
+pub fn parse_time(value: &str) -> Result<u64> {
+    let invalid = || Error::new(ErrorKind::NotParsable(format!("invalid time {}", value)));
+    if let Ok(timestamp) = value.parse() {
+        return Ok(timestamp);
+    }
+    let day = value.get(..10).and_then(parse_date).ok_or_else(invalid)?;
+    let seconds = match value.get(10..) {
+        Some("") => 0,
+        Some(time) => {
+            let time = time.strip_prefix('T').and_then(|time| time.strip_suffix('Z')).ok_or_else(invalid)?;
+            let parts: Vec<u64> = time.split(':').filter_map(|part| part.parse().ok()).collect();
+            match parts.as_slice() {
+                [hours, minutes, seconds] if *hours < 24 && *minutes < 60 && *seconds < 60 => {
+                    hours * 3600 + minutes * 60 + seconds
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        None => return Err(invalid()),
+    };
+    Ok(day * 86400 + seconds)
+}
+
+/// returns `HH:MM:SS` of the seconds since midnight
+fn clock(seconds: u64) -> String {
+    format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+/// returns `YYYY-MM-DD` of the days since the unix epoch
+pub(crate) fn date(days: u64) -> String {
+    // civil from days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// returns the days since the unix epoch of a `YYYY-MM-DD` date
+fn parse_date(value: &str) -> Option<u64> {
+    let mut parts = value.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    if value.len() != 10 || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // days from civil, the inverse of `date`
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    if days < 0 {
+        return None;
+    }
+    Some(days as u64)
+}
+
+/// returns the default end of an export, the current time
+pub fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::from_secs(0))
+        .as_secs()
+}
@@ -0,0 +1,156 @@
+//! test file to test recording and exporting csv files
+
+use super::{date, export, file_day, file_tracker, parse_date, parse_time, row, Recorder, HEADER, MAX_QUEUE};
+use crate::collector::{Scrape, Sink};
+use crate::{parse_everything, test::EVERYTHING};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+// 2020-09-13T12:26:40Z
+const TIME: u64 = 1_600_000_000;
+
+fn dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("opentracker_exporter_record_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+fn scrape(time: u64) -> Scrape {
+    Scrape {
+        name: String::from("tracker.example"),
+        time: UNIX_EPOCH + Duration::from_secs(time),
+        data: parse_everything(EVERYTHING).unwrap(),
+    }
+}
+
+#[test]
+fn dates() {
+    assert_eq!(date(0), "1970-01-01");
+    assert_eq!(date(TIME / 86400), "2020-09-13");
+    assert_eq!(date(11016), "2000-02-29");
+    for days in [0, 59, 60, 11016, 18518, 20000, 50000].iter() {
+        assert_eq!(parse_date(&date(*days)), Some(*days));
+    }
+    assert_eq!(parse_date("2020-13-01"), None);
+    assert_eq!(parse_date("20-1-1"), None);
+}
+
+#[test]
+fn times() {
+    assert_eq!(parse_time("1600000000").unwrap(), TIME);
+    assert_eq!(parse_time("2020-09-13").unwrap(), TIME - 44800);
+    assert_eq!(parse_time("2020-09-13T12:26:40Z").unwrap(), TIME);
+    assert!(parse_time("2020-09-13T25:00:00Z").is_err());
+    assert!(parse_time("yesterday").is_err());
+}
+
+#[test]
+fn csv_row() {
+    let row = row(TIME, "tracker.example", &parse_everything(EVERYTHING).unwrap());
+    assert_eq!(
+        row,
+        "1600000000,2020-09-13T12:26:40Z,tracker.example,1337,3600,42,41,300,120,55,2,1000,900,100,5000,1500,3000,400,7,0,400 Invalid Request=3"
+    );
+    assert_eq!(row.split(',').count(), HEADER.split(',').count());
+}
+
+#[test]
+fn rotation() {
+    let dir = dir("rotation");
+    let mut recorder = Recorder::new(&dir, Some(2)).unwrap();
+    // files of other trackers are left alone
+    fs::write(dir.join("other-2020-09-01.csv"), HEADER).unwrap();
+    recorder.collect(&scrape(TIME - 3 * 86400));
+    recorder.collect(&scrape(TIME));
+    recorder.collect(&scrape(TIME + 60));
+    recorder.flush().unwrap();
+
+    // the file of 2020-09-10 was removed when rotating to 2020-09-13
+    let mut files: Vec<String> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    files.sort();
+    assert_eq!(
+        files,
+        vec![String::from("other-2020-09-01.csv"), String::from("tracker_example-2020-09-13.csv")]
+    );
+
+    let content = fs::read_to_string(dir.join("tracker_example-2020-09-13.csv")).unwrap();
+    assert_eq!(content.lines().count(), 3);
+    assert!(content.starts_with(HEADER));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn export_range() {
+    let dir = dir("export");
+    let mut recorder = Recorder::new(&dir, None).unwrap();
+    for offset in [0, 60, 86400, 86460].iter() {
+        recorder.collect(&scrape(TIME + offset));
+    }
+    recorder.flush().unwrap();
+
+    let mut out = Vec::new();
+    export(&dir, TIME + 60, TIME + 86460, None, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let timestamps: Vec<&str> = out.lines().skip(1).map(|line| &line[..10]).collect();
+    assert_eq!(timestamps, vec!["1600000060", "1600086400"]);
+
+    let mut out = Vec::new();
+    export(&dir, 0, u64::MAX, Some("other"), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), format!("{}\n", HEADER));
+
+    // a prefix of the name is another tracker
+    let mut out = Vec::new();
+    export(&dir, 0, u64::MAX, Some("tracker"), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), format!("{}\n", HEADER));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn file_days() {
+    assert_eq!(file_day(Path::new("/x/a-b-2020-09-13.csv")), Some(TIME / 86400));
+    assert_eq!(file_day(Path::new("/x/notes.txt")), None);
+    assert_eq!(file_tracker(Path::new("/x/a-b-2020-09-13.csv")), Some(String::from("a-b")));
+    assert_eq!(file_tracker(Path::new("/x/a_2020-09-13.csv")), None);
+    assert_eq!(file_tracker(Path::new("/x/2020-09-13.csv")), None);
+}
+
+#[test]
+fn failed_cleanup_keeps_recording() {
+    let dir = dir("cleanup");
+    let mut recorder = Recorder::new(&dir, Some(2)).unwrap();
+    // a directory with the name of an old file cannot be removed as file
+    fs::create_dir(dir.join("tracker_example-2020-09-01.csv")).unwrap();
+    recorder.collect(&scrape(TIME));
+    recorder.flush().unwrap();
+    assert!(dir.join("tracker_example-2020-09-13.csv").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn queue_is_bounded() {
+    let dir = dir("queue");
+    let mut recorder = Recorder::new(&dir, None).unwrap();
+    // a file in place of the directory cannot be written into
+    fs::remove_dir(&dir).unwrap();
+    fs::write(&dir, "").unwrap();
+
+    let mut scrape = scrape(TIME);
+    for time in 0..=MAX_QUEUE as u64 {
+        scrape.time = UNIX_EPOCH + Duration::from_secs(TIME + time);
+        recorder.collect(&scrape);
+    }
+    assert!(recorder.flush().is_err());
+    assert_eq!(recorder.rows.len(), MAX_QUEUE);
+    // the oldest row is dropped
+    assert!(recorder.rows[0].1.starts_with(&format!("{},", TIME + 1)));
+
+    fs::remove_file(&dir).unwrap();
+    fs::create_dir(&dir).unwrap();
+    recorder.flush().unwrap();
+    assert!(recorder.rows.is_empty());
+    fs::remove_dir_all(&dir).unwrap();
+}
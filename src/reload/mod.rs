@@ -0,0 +1,104 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::error::{Error, ErrorKind, Result};
+use super::Config;
+
+// reload reads the config file and checks a new config before it replaces the running one
+
+// tests as sub module
+#[cfg(test)] // only add when running tests
+mod test;
+
+/// Loader builds the config again from the flags and the config file
+pub type Loader = Arc<dyn Fn() -> std::result::Result<Config, String> + Send + Sync>;
+
+/// returns the flags of a config file
+///
+/// Each line is a long flag without the dashes, optionally followed by `=` and a value.
+/// Empty lines and lines starting with `#` are ignored.
+pub fn args(content: &str) -> Result<Vec<String>> {
+    let mut ret = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (flag, value) = match line.find('=') {
+            Some(split) => (line[..split].trim(), Some(line[split + 1..].trim())),
+            None => (line, None),
+        };
+        if flag.is_empty() || flag.starts_with('-') || !flag.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(Error::new(ErrorKind::NotParsable(format!(
+                "invalid line {}: {}",
+                number + 1,
+                line
+            ))));
+        }
+        ret.push(match value {
+            Some(value) => format!("--{}={}", flag, value),
+            None => format!("--{}", flag),
+        });
+    }
+    Ok(ret)
+}
+
+/// reads the flags of the config file at `path`
+pub fn read(path: &Path) -> Result<Vec<String>> {
+    args(&fs::read_to_string(path)?)
+}
+
+/// checks a config before it is used
+pub fn validate(conf: &Config) -> std::result::Result<(), String> {
+    if conf.url.is_empty() {
+        return Err(String::from("url must not be empty"));
+    }
+    let mut prefix = conf.prefix.chars();
+    let valid_start = prefix.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == ':');
+    if !valid_start || !prefix.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':') {
+        return Err(format!("{} is not a valid metric prefix", conf.prefix));
+    }
+    if conf.threads == 0 {
+        return Err(String::from("threads must be at least 1"));
+    }
+    if conf.max_threads.is_some_and(|max| max < conf.threads) {
+        return Err(String::from("max threads must not be less than threads"));
+    }
+    if conf.interval == 0 {
+        return Err(String::from("interval must be at least 1 second"));
+    }
+    if conf.scrape_timeout == 0 || conf.probe_timeout == 0 {
+        return Err(String::from("timeouts must be at least 1 second"));
+    }
+    Ok(())
+}
+
+/// Fixed are the settings of a running server which only change on restart
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Fixed {
+    listen: (String, u16),
+    queue: Option<usize>,
+}
+
+impl Fixed {
+    pub(crate) fn of(conf: &Config) -> Self {
+        Self {
+            listen: (conf.interface.clone(), conf.port),
+            queue: conf.queue,
+        }
+    }
+
+    /// returns the names of the settings `conf` changes
+    pub(crate) fn changes(&self, conf: &Config) -> Vec<&'static str> {
+        let new = Self::of(conf);
+        let mut ret = Vec::new();
+        if new.listen != self.listen {
+            ret.push("listen address");
+        }
+        if new.queue != self.queue {
+            ret.push("queue size");
+        }
+        ret
+    }
+}
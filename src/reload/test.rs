@@ -0,0 +1,66 @@
+//! test file to test the config file and the validation of reloaded configs
+
+use super::{args, validate, Fixed};
+use crate::Config;
+
+#[test]
+fn config_file() {
+    let content = "# tracker\nurl = 127.0.0.1:6969\n\nhostname=tracker one\n  dogstatsd\nexternal-label = dc=fra\n";
+    assert_eq!(
+        args(content).unwrap(),
+        vec![
+            "--url=127.0.0.1:6969",
+            "--hostname=tracker one",
+            "--dogstatsd",
+            "--external-label=dc=fra"
+        ]
+    );
+}
+
+#[test]
+fn config_file_invalid() {
+    let err = args("url = a\n= b\n").unwrap_err();
+    assert_eq!(err.kind().error_string(), "NotParsable(invalid line 2: = b)");
+    assert!(args("--url=a").is_err());
+}
+
+#[test]
+fn validation() {
+    let mut conf = Config::new();
+    assert_eq!(validate(&conf), Ok(()));
+
+    conf.prefix = String::from("1tracker");
+    assert!(validate(&conf).is_err());
+    conf.prefix = String::from("open:tracker_2");
+    assert_eq!(validate(&conf), Ok(()));
+
+    conf.max_threads = Some(4);
+    assert!(validate(&conf).is_err());
+    conf.max_threads = Some(8);
+    assert_eq!(validate(&conf), Ok(()));
+
+    conf.scrape_timeout = 0;
+    assert!(validate(&conf).is_err());
+    conf.scrape_timeout = 10;
+
+    conf.url = String::new();
+    assert_eq!(validate(&conf), Err(String::from("url must not be empty")));
+}
+
+#[test]
+fn restart_required() {
+    let conf = Config::new();
+    let fixed = Fixed::of(&conf);
+    assert!(fixed.changes(&conf).is_empty());
+
+    let mut new = Config::new();
+    new.port = 9100;
+    new.url = String::from("tracker.example:6969");
+    new.threads = 2;
+    new.history = Some(2);
+    new.interval = 30;
+    assert_eq!(fixed.changes(&new), vec!["listen address"]);
+
+    new.queue = None;
+    assert_eq!(fixed.changes(&new), vec!["listen address", "queue size"]);
+}
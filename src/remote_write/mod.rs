@@ -0,0 +1,217 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::time::UNIX_EPOCH;
+
+use super::collector::{Scrape, Sink};
+use super::error::{ErrorKind, Result};
+use super::http::{self, Url};
+use super::metrics::Kind;
+use super::protobuf::Writer;
+use super::snappy;
+
+// tests as sub module
+#[cfg(test)] // only add when running tests
+mod test;
+
+/// Endpoint describes a prometheus remote write receiver
+#[derive(Clone, Debug)]
+pub struct Endpoint {
+    /// url to post to, e.g. `http://mimir:9009/api/v1/push`
+    pub url: String,
+
+    /// user and password for basic auth, overrides credentials in the url
+    pub auth: Option<(String, String)>,
+
+    /// labels added to every series unless it already has them
+    pub external_labels: Vec<(String, String)>,
+
+    /// maximum number of samples per request
+    pub batch_size: usize,
+
+    /// maximum number of queued samples, the oldest are dropped when full
+    pub queue_size: usize,
+}
+
+impl Endpoint {
+    /// creates a new Endpoint for `url` with default values
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            auth: None,
+            external_labels: Vec::new(),
+            batch_size: 2000,
+            queue_size: 50_000,
+        }
+    }
+}
+
+/// label names and values of a series
+type Labels = [(String, String)];
+
+/// one queued sample of a series
+struct Point {
+    /// labels including `__name__`, sorted by name
+    labels: Vec<(String, String)>,
+    value: f64,
+    /// milliseconds since the unix epoch
+    timestamp: i64,
+}
+
+/// RemoteWrite is a sink queueing samples and sending them to an Endpoint
+pub struct RemoteWrite {
+    endpoint: Endpoint,
+    url: Url,
+    prefix: String,
+    queue: VecDeque<Point>,
+    /// name, help and type of every family seen in the last scrape
+    metadata: Vec<(String, &'static str, Kind)>,
+}
+
+impl RemoteWrite {
+    /// creates a new RemoteWrite for metrics named with `prefix`
+    pub fn new(endpoint: Endpoint, prefix: &str) -> Result<Self> {
+        let mut url = Url::parse(&endpoint.url)?;
+        if endpoint.auth.is_some() {
+            url.auth = endpoint.auth.clone();
+        }
+        Ok(Self {
+            endpoint,
+            url,
+            prefix: prefix.to_string(),
+            queue: VecDeque::new(),
+            metadata: Vec::new(),
+        })
+    }
+
+    /// sends the oldest `count` queued samples in one request
+    fn send(&self, count: usize) -> Result<()> {
+        let body = encode(self.queue.iter().take(count), &self.metadata);
+        http::request(
+            "POST",
+            &self.url,
+            &[
+                ("Content-Encoding", "snappy"),
+                ("Content-Type", "application/x-protobuf"),
+                ("X-Prometheus-Remote-Write-Version", "0.1.0"),
+            ],
+            &snappy::compress(&body),
+        )?;
+        Ok(())
+    }
+}
+
+impl Sink for RemoteWrite {
+    fn name(&self) -> String {
+        format!("remote write {}", self.endpoint.url)
+    }
+
+    fn collect(&mut self, scrape: &Scrape) {
+        let timestamp = match scrape.time.duration_since(UNIX_EPOCH) {
+            Ok(time) => time.as_millis() as i64,
+            Err(_) => 0,
+        };
+
+        let families = scrape.data.families(&self.prefix, &scrape.name);
+        self.metadata = families
+            .iter()
+            .map(|family| (family.name.clone(), family.help, family.kind))
+            .collect();
+
+        for family in families {
+            for sample in family.samples {
+                let mut labels = sample.labels;
+                labels.push((String::from("__name__"), family.name.clone()));
+                for (key, value) in &self.endpoint.external_labels {
+                    if !labels.iter().any(|(name, _)| name == key) {
+                        labels.push((key.clone(), value.clone()));
+                    }
+                }
+                labels.sort();
+
+                self.queue.push_back(Point {
+                    labels,
+                    value: sample.value,
+                    timestamp,
+                });
+            }
+        }
+
+        if self.queue.len() > self.endpoint.queue_size {
+            let dropped = self.queue.len() - self.endpoint.queue_size;
+            self.queue.drain(..dropped);
+            eprintln!("remote write queue full, dropped {} samples", dropped);
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        while !self.queue.is_empty() {
+            let count = std::cmp::min(self.queue.len(), self.endpoint.batch_size.max(1));
+            match self.send(count) {
+                Ok(()) => {}
+                // the receiver will never accept these, retrying would block the queue
+                Err(ref err) if is_rejected(&err.kind()) => {
+                    eprintln!("remote write rejected {} samples: {}", count, err);
+                }
+                Err(err) => return Err(err),
+            }
+            self.queue.drain(..count);
+        }
+        Ok(())
+    }
+}
+
+/// checks if the receiver rejected the data instead of failing temporarily
+fn is_rejected(kind: &ErrorKind) -> bool {
+    match kind {
+        ErrorKind::HttpStatus(status) => *status >= 400 && *status < 500 && *status != 429,
+        _ => false,
+    }
+}
+
+/// encodes points as a remote write 1.0 `WriteRequest`
+///
+/// Points of the same series are grouped into one `TimeSeries`.
+fn encode<'a, I>(points: I, metadata: &[(String, &'static str, Kind)]) -> Vec<u8>
+where
+    I: Iterator<Item = &'a Point>,
+{
+    let mut series: BTreeMap<&Labels, Vec<(f64, i64)>> = BTreeMap::new();
+    for point in points {
+        series
+            .entry(point.labels.as_slice())
+            .or_default()
+            .push((point.value, point.timestamp));
+    }
+
+    let mut request = Writer::new();
+    for (labels, samples) in series {
+        request.message(1, |timeseries| {
+            for (name, value) in labels {
+                timeseries.message(1, |label| {
+                    label.string(1, name);
+                    label.string(2, value);
+                });
+            }
+            for (value, timestamp) in samples {
+                timeseries.message(2, |sample| {
+                    sample.double(1, value);
+                    sample.int64(2, timestamp);
+                });
+            }
+        });
+    }
+    for (name, help, kind) in metadata {
+        request.message(3, |meta| {
+            meta.uint64(
+                1,
+                match kind {
+                    Kind::Counter => 1,
+                    Kind::Gauge => 2,
+                    Kind::Histogram => 3,
+                },
+            );
+            meta.string(2, name);
+            meta.string(4, help);
+        });
+    }
+    request.into_bytes()
+}
@@ -0,0 +1,108 @@
+//! test file for the remote write sink
+
+use super::{encode, is_rejected, Endpoint, Point, RemoteWrite};
+use crate::collector::{test::scrape, Sink};
+use crate::error::ErrorKind;
+use crate::http::test::stand_in;
+use crate::metrics::Kind;
+use std::time::Duration;
+
+fn point(name: &str, value: f64, timestamp: i64) -> Point {
+    Point {
+        labels: vec![(String::from("__name__"), name.to_string())],
+        value,
+        timestamp,
+    }
+}
+
+#[test]
+fn encode_write_request() {
+    let points = [point("up", 1.0, 1000), point("up", 0.0, 2000)];
+    let metadata = vec![(String::from("up"), "help", Kind::Gauge)];
+
+    let mut expected = vec![
+        0x0a, 0x2c, // timeseries
+        0x0a, 0x0e, 0x0a, 0x08, // label, name
+    ];
+    expected.extend_from_slice(b"__name__");
+    expected.extend_from_slice(&[0x12, 0x02]);
+    expected.extend_from_slice(b"up");
+    expected.extend_from_slice(&[0x12, 0x0c, 0x09]); // sample, value
+    expected.extend_from_slice(&1.0f64.to_le_bytes());
+    expected.extend_from_slice(&[0x10, 0xe8, 0x07]); // timestamp 1000
+    expected.extend_from_slice(&[0x12, 0x0c, 0x09]);
+    expected.extend_from_slice(&0.0f64.to_le_bytes());
+    expected.extend_from_slice(&[0x10, 0xd0, 0x0f]); // timestamp 2000
+    expected.extend_from_slice(&[0x1a, 0x0c, 0x08, 0x02, 0x12, 0x02]); // metadata
+    expected.extend_from_slice(b"up");
+    expected.extend_from_slice(&[0x22, 0x04]);
+    expected.extend_from_slice(b"help");
+
+    assert_eq!(encode(points.iter(), &metadata), expected);
+}
+
+#[test]
+fn collect_labels() {
+    let mut endpoint = Endpoint::new("localhost:9009");
+    endpoint.external_labels = vec![
+        (String::from("cluster"), String::from("edge")),
+        (String::from("name"), String::from("ignored")),
+    ];
+    let mut sink = RemoteWrite::new(endpoint, "ot").unwrap();
+    sink.collect(&scrape("test", 1, |_| {}));
+
+    let uptime = sink.queue.front().unwrap();
+    assert_eq!(
+        uptime.labels,
+        vec![
+            (String::from("__name__"), String::from("ot_uptime")),
+            (String::from("cluster"), String::from("edge")),
+            (String::from("name"), String::from("test")),
+            (String::from("tracker"), String::from("0")),
+        ]
+    );
+    assert_eq!(uptime.timestamp, 1000);
+}
+
+#[test]
+fn queue_size() {
+    let mut endpoint = Endpoint::new("localhost:9009");
+    endpoint.queue_size = 5;
+    let mut sink = RemoteWrite::new(endpoint, "ot").unwrap();
+    sink.collect(&scrape("test", 1, |_| {}));
+    assert_eq!(sink.queue.len(), 5);
+}
+
+#[test]
+fn flush() {
+    let (addr, request) = stand_in("204 No Content");
+    let mut sink = RemoteWrite::new(Endpoint::new(&addr), "ot").unwrap();
+    sink.collect(&scrape("test", 1, |_| {}));
+    sink.flush().unwrap();
+    assert!(sink.queue.is_empty());
+
+    let request = request.recv_timeout(Duration::from_secs(5)).unwrap();
+    let request = String::from_utf8_lossy(&request);
+    assert!(request.starts_with("POST / HTTP/1.1\r\n"));
+    assert!(request.contains("\r\nContent-Encoding: snappy\r\n"));
+    assert!(request.contains("\r\nX-Prometheus-Remote-Write-Version: 0.1.0\r\n"));
+}
+
+#[test]
+fn keeps_queue_on_server_error() {
+    let (addr, _request) = stand_in("503 Service Unavailable");
+    let mut sink = RemoteWrite::new(Endpoint::new(&addr), "ot").unwrap();
+    sink.collect(&scrape("test", 1, |_| {}));
+    let queued = sink.queue.len();
+
+    assert_eq!(sink.flush().unwrap_err().kind(), ErrorKind::HttpStatus(503));
+    assert_eq!(sink.queue.len(), queued);
+}
+
+#[test]
+fn rejected() {
+    assert!(is_rejected(&ErrorKind::HttpStatus(400)));
+    assert!(!is_rejected(&ErrorKind::HttpStatus(429)));
+    assert!(!is_rejected(&ErrorKind::HttpStatus(500)));
+    assert!(!is_rejected(&ErrorKind::IoConnectionRefused));
+}
@@ -0,0 +1,79 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::error::{Error, ErrorKind, Result};
+use super::textfile::write_atomic;
+
+// tests as sub module
+#[cfg(test)] // only add when running tests
+mod test;
+
+/// stores a raw upstream response as `{unix millis}-{mode}.http` in `dir`
+///
+/// `time` is the local time the response was generated at, it is used as
+/// local time again when replaying so the clock skew is reproduced.
+pub(crate) fn record(dir: &Path, mode: &str, time: SystemTime, raw: &[u8]) -> Result<PathBuf> {
+    let millis = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+    let path = dir.join(format!("{:013}-{}.http", millis, mode));
+    fs::create_dir_all(dir)?;
+    write_atomic(&path, raw)?;
+    Ok(path)
+}
+
+/// Replay hands out recorded responses in the order they were recorded
+///
+/// After the last response it starts again with the first one. Clones share
+/// the position, so every request of the server gets the next response.
+#[derive(Clone, Debug)]
+pub struct Replay {
+    /// recorded responses with mode and time
+    files: Arc<Vec<(String, SystemTime, PathBuf)>>,
+    position: Arc<AtomicUsize>,
+}
+
+impl Replay {
+    /// reads the list of responses recorded in `dir`
+    pub fn open(dir: &Path) -> Result<Self> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if let Some((mode, time)) = parse_name(&path) {
+                files.push((mode, time, path));
+            }
+        }
+        if files.is_empty() {
+            return Err(Error::new(ErrorKind::Other(format!(
+                "no recorded responses in {}",
+                dir.display()
+            ))));
+        }
+        files.sort_by(|a, b| a.2.cmp(&b.2));
+        Ok(Self {
+            files: Arc::new(files),
+            position: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    /// returns the next recorded response for `mode` and the time it was recorded at
+    pub(crate) fn next(&self, mode: &str) -> Result<(SystemTime, String)> {
+        let files: Vec<&(String, SystemTime, PathBuf)> = self.files.iter().filter(|file| file.0 == mode).collect();
+        if files.is_empty() {
+            return Err(Error::new(ErrorKind::IoNotFound));
+        }
+        let (_, time, path) = files[self.position.fetch_add(1, Ordering::SeqCst) % files.len()];
+        let raw = fs::read(path)?;
+        Ok((*time, String::from_utf8_lossy(&raw).to_string()))
+    }
+}
+
+/// returns mode and time of a `{unix millis}-{mode}.http` file
+fn parse_name(path: &Path) -> Option<(String, SystemTime)> {
+    let name = path.file_name()?.to_string_lossy().to_string();
+    let stem = name.strip_suffix(".http")?;
+    let split = stem.find('-')?;
+    let millis: u64 = stem[..split].parse().ok()?;
+    Some((stem[split + 1..].to_string(), UNIX_EPOCH + Duration::from_millis(millis)))
+}
@@ -0,0 +1,63 @@
+//! test file to test recording and replaying upstream responses
+
+use super::{parse_name, record, Replay};
+use crate::{scrape, test::EVERYTHING, Upstream};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+fn dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("opentracker_exporter_replay_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+fn response(uptime: usize) -> String {
+    format!(
+        "HTTP/1.0 200 OK\r\nContent-Type: text/plain\r\nDate: Sun, 06 Nov 1994 08:49:37 GMT\r\n\r\n{}",
+        EVERYTHING.replace("<uptime>3600</uptime>", &format!("<uptime>{}</uptime>", uptime))
+    )
+}
+
+#[test]
+fn file_name() {
+    let dir = dir("name");
+    let time = UNIX_EPOCH + Duration::from_millis(784_111_777_250);
+    let path = record(&dir, "everything", time, b"raw").unwrap();
+    assert_eq!(path, dir.join("0784111777250-everything.http"));
+    assert_eq!(fs::read(&path).unwrap(), b"raw");
+    assert_eq!(parse_name(&path), Some((String::from("everything"), time)));
+    assert_eq!(parse_name(Path::new("notes.txt")), None);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn empty_dir() {
+    let dir = dir("empty");
+    fs::create_dir_all(&dir).unwrap();
+    assert!(Replay::open(&dir).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn replays_in_order() {
+    let dir = dir("order");
+    // the tracker clock was 30 and 60 seconds ahead
+    let date = UNIX_EPOCH + Duration::from_secs(784_111_777);
+    record(&dir, "everything", date - Duration::from_secs(60), response(2).as_bytes()).unwrap();
+    record(&dir, "everything", date - Duration::from_secs(30), response(1).as_bytes()).unwrap();
+    record(&dir, "top100", date, b"HTTP/1.0 200 OK\r\n\r\n").unwrap();
+
+    let upstream = Upstream {
+        url: String::from("127.0.0.1:1"),
+        record_dir: None,
+        replay: Some(Replay::open(&dir).unwrap()),
+        timeout: Duration::from_secs(1),
+    };
+    let first = scrape(&upstream).unwrap();
+    assert_eq!((first.uptime, first.clock_skew), (2, Some(60)));
+    let second = scrape(&upstream.clone()).unwrap();
+    assert_eq!((second.uptime, second.clock_skew), (1, Some(30)));
+    assert_eq!(scrape(&upstream).unwrap().uptime, 2);
+    fs::remove_dir_all(&dir).unwrap();
+}
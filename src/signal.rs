@@ -0,0 +1,66 @@
+// signal handles SIGINT and SIGTERM to shut down gracefully and SIGHUP to reload
+//
+// The handlers only set a flag, everything else happens in the threads polling it.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// set once SIGINT or SIGTERM was received
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+/// set when SIGHUP was received until the reload is taken
+static RELOAD: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+mod sys {
+    use std::os::raw::c_int;
+
+    pub const SIGHUP: c_int = 1;
+    pub const SIGINT: c_int = 2;
+    pub const SIGTERM: c_int = 15;
+
+    extern "C" {
+        pub fn signal(signum: c_int, handler: usize) -> usize;
+        pub fn _exit(status: c_int) -> !;
+    }
+}
+
+/// handler for SIGINT and SIGTERM, a second signal exits immediately
+#[cfg(unix)]
+extern "C" fn on_shutdown(signum: std::os::raw::c_int) {
+    if SHUTDOWN.swap(true, Ordering::SeqCst) {
+        // only async signal safe functions are allowed here
+        unsafe { sys::_exit(128 + signum) }
+    }
+}
+
+/// handler for SIGHUP
+#[cfg(unix)]
+extern "C" fn on_reload(_: std::os::raw::c_int) {
+    RELOAD.store(true, Ordering::SeqCst);
+}
+
+/// installs the handlers for SIGINT, SIGTERM and SIGHUP
+pub fn install() {
+    #[cfg(unix)]
+    unsafe {
+        let handler = on_shutdown as extern "C" fn(std::os::raw::c_int) as usize;
+        sys::signal(sys::SIGINT, handler);
+        sys::signal(sys::SIGTERM, handler);
+        sys::signal(sys::SIGHUP, on_reload as extern "C" fn(std::os::raw::c_int) as usize);
+    }
+}
+
+/// returns true once a shutdown was requested
+pub fn shutdown_requested() -> bool {
+    SHUTDOWN.load(Ordering::SeqCst)
+}
+
+/// returns true once for every received SIGHUP
+pub fn take_reload() -> bool {
+    RELOAD.swap(false, Ordering::SeqCst)
+}
+
+/// requests a shutdown as if SIGTERM was received
+pub fn request_shutdown() {
+    SHUTDOWN.store(true, Ordering::SeqCst);
+}
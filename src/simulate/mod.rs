@@ -0,0 +1,372 @@
+use std::collections::BTreeMap;
+use std::io::prelude::*;
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use super::error::Result;
+use super::threads::ThreadPool;
+use super::{query_param, request_target, Connections, Everything, Torrents};
+
+// tests as sub module
+#[cfg(test)] // only add when running tests
+mod test;
+
+/// how long an error spike lasts
+const SPIKE: f64 = 30.0;
+
+/// how long the tracker hangs on a stall
+const STALL: f64 = 3.0;
+
+/// http errors opentracker reports, with their usual rate per second
+const HTTP_ERRORS: [(&str, f64); 4] = [
+    ("302 Redirect", 0.01),
+    ("400 Invalid Request", 0.05),
+    ("403 Access Denied", 0.01),
+    ("404 Not found", 0.02),
+];
+
+/// counters with their mean rate per second
+const RATES: [(&str, f64); 9] = [
+    ("completed", 0.5),
+    ("tcp_accept", 50.0),
+    ("tcp_announce", 40.0),
+    ("tcp_scrape", 8.0),
+    ("udp_connect", 300.0),
+    ("udp_announce", 600.0),
+    ("udp_scrape", 80.0),
+    ("udp_missmatch", 1.0),
+    ("livesync", 0.0),
+];
+
+/// Options configure the events of the simulation, intervals are in seconds
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// restart the tracker every interval
+    pub restart: Option<u64>,
+
+    /// raise the http error rates for 30 seconds every interval
+    pub spikes: Option<u64>,
+
+    /// let the tracker hang for 3 seconds every interval, counted as mutex stall
+    pub stalls: Option<u64>,
+
+    /// seed of the random numbers, same seeds give the same numbers
+    pub seed: u64,
+}
+
+/// Simulation is the state of the simulated tracker
+pub struct Simulation {
+    options: Options,
+    rng: u64,
+    /// seconds since the simulation started
+    time: f64,
+    /// time the tracker started
+    started: f64,
+    tracker_id: usize,
+    peers: f64,
+    seeds: f64,
+    torrents: f64,
+    mutex_stall: usize,
+    stalled_until: f64,
+    counters: BTreeMap<&'static str, f64>,
+    http_error: BTreeMap<&'static str, f64>,
+}
+
+impl Simulation {
+    /// creates a freshly started tracker
+    pub fn new(options: Options) -> Self {
+        let mut simulation = Self {
+            // xorshift needs a state other than zero
+            rng: (options.seed ^ 0x9e37_79b9_7f4a_7c15).max(1),
+            options,
+            time: 0.0,
+            started: 0.0,
+            tracker_id: 0,
+            peers: 50_000.0,
+            seeds: 20_000.0,
+            torrents: 20_000.0,
+            mutex_stall: 0,
+            stalled_until: 0.0,
+            counters: BTreeMap::new(),
+            http_error: BTreeMap::new(),
+        };
+        simulation.restart(0.0);
+        simulation
+    }
+
+    /// returns a random number in `[0, 1)`, xorshift64
+    fn random(&mut self) -> f64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// starts the tracker again at `time` with all counters at zero
+    fn restart(&mut self, time: f64) {
+        self.started = time;
+        self.tracker_id = (self.random() * 1e9) as usize;
+        self.mutex_stall = 0;
+        self.counters = RATES.iter().map(|(name, _)| (*name, 0.0)).collect();
+        self.http_error.clear();
+    }
+
+    /// returns true if a multiple of `interval` lies in `(from, to]`
+    fn crossed(interval: Option<u64>, from: f64, to: f64) -> bool {
+        match interval {
+            Some(interval) if interval > 0 => (to / interval as f64).floor() > (from / interval as f64).floor(),
+            _ => false,
+        }
+    }
+
+    /// lets `seconds` pass in the simulation
+    pub fn advance(&mut self, seconds: f64) {
+        let from = self.time;
+        self.time += seconds;
+
+        let mut seconds = seconds;
+        if let Some(interval) = self.options.restart.filter(|_| Self::crossed(self.options.restart, from, self.time)) {
+            let interval = interval as f64;
+            self.restart((self.time / interval).floor() * interval);
+            // the counters only grew since the restart
+            seconds = self.time - self.started;
+        }
+        if Self::crossed(self.options.stalls, from, self.time) {
+            self.mutex_stall += 1;
+            self.stalled_until = self.time + STALL;
+        }
+        let spike = match self.options.spikes {
+            Some(interval) if interval > 0 => self.time % interval as f64 <= SPIKE && self.time >= interval as f64,
+            _ => false,
+        };
+
+        // swarms drift slowly with the time of the day
+        let day = (self.time / 86400.0 * std::f64::consts::TAU).sin();
+        self.peers = (self.peers + (self.random() - 0.5 + day * 0.1) * seconds * 20.0).max(0.0);
+        self.seeds = (self.seeds + (self.random() - 0.5 + day * 0.1) * seconds * 8.0).clamp(0.0, self.peers);
+        self.torrents = (self.torrents + (self.random() - 0.45) * seconds * 2.0).max(0.0);
+
+        let load = 1.0 + day * 0.3;
+        for (name, rate) in RATES.iter() {
+            let jitter = 0.8 + self.random() * 0.4;
+            *self.counters.entry(name).or_insert(0.0) += rate * load * jitter * seconds;
+        }
+        for (code, rate) in HTTP_ERRORS.iter() {
+            let rate = if spike { rate * 200.0 } else { *rate };
+            let jitter = 0.5 + self.random();
+            *self.http_error.entry(code).or_insert(0.0) += rate * jitter * seconds;
+        }
+    }
+
+    /// returns how long the tracker still hangs
+    pub fn stalled(&self) -> Duration {
+        Duration::from_secs_f64((self.stalled_until - self.time).max(0.0))
+    }
+
+    /// returns the current stats
+    pub(crate) fn everything(&self) -> Everything {
+        let counter = |name: &str| self.counters.get(name).copied().unwrap_or(0.0) as usize;
+        let udp = counter("udp_connect") + counter("udp_announce") + counter("udp_scrape") + counter("udp_missmatch");
+        let torrents = self.torrents as usize;
+        Everything {
+            tracker_id: self.tracker_id,
+            uptime: (self.time - self.started) as usize,
+            torrents: Torrents {
+                mutex: torrents,
+                iterator: torrents.saturating_sub(((self.time * 7.0) % 3.0) as usize),
+            },
+            peers: self.peers as usize,
+            seeds: self.seeds as usize,
+            completed: counter("completed"),
+            connections: Connections {
+                tcp_accept: counter("tcp_accept"),
+                tcp_announce: counter("tcp_announce"),
+                tcp_scrape: counter("tcp_scrape"),
+                udp_overall: udp,
+                udp_connect: counter("udp_connect"),
+                udp_announce: counter("udp_announce"),
+                udp_scrape: counter("udp_scrape"),
+                udp_missmatch: counter("udp_missmatch"),
+                livesync: counter("livesync") as isize,
+            },
+            http_error: self
+                .http_error
+                .iter()
+                .filter(|(_, count)| **count >= 1.0)
+                .map(|(code, count)| (code.to_string(), *count as usize))
+                .collect(),
+            mutex_stall: self.mutex_stall,
+            clock_skew: None,
+        }
+    }
+}
+
+/// returns the stats as opentracker renders `/stats?mode=everything`
+pub(crate) fn xml(data: &Everything) -> String {
+    let mut codes: Vec<(&String, &usize)> = data.http_error.iter().collect();
+    codes.sort();
+    let codes: String = codes
+        .iter()
+        .map(|(code, count)| format!("      <count code=\"{}\">{}</count>\n", code, count))
+        .collect();
+    let c = &data.connections;
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<stats>
+  <tracker_id>{}</tracker_id>
+  <version>
+opentracker_exporter simulate {}
+  </version>
+  <uptime>{}</uptime>
+  <torrents>
+    <count_mutex>{}</count_mutex>
+    <count_iterator>{}</count_iterator>
+  </torrents>
+  <peers>
+    <count>{}</count>
+  </peers>
+  <seeds>
+    <count>{}</count>
+  </seeds>
+  <completed>
+    <count>{}</count>
+  </completed>
+  <connections>
+    <tcp>
+      <accept>{}</accept>
+      <announce>{}</announce>
+      <scrape>{}</scrape>
+    </tcp>
+    <udp>
+      <overall>{}</overall>
+      <connect>{}</connect>
+      <announce>{}</announce>
+      <scrape>{}</scrape>
+      <missmatch>{}</missmatch>
+    </udp>
+    <livesync>
+      <count>{}</count>
+    </livesync>
+  </connections>
+  <debug>
+    <renew>
+    </renew>
+    <http_error>
+{}    </http_error>
+    <mutex_stall>
+      <count>{}</count>
+    </mutex_stall>
+  </debug>
+</stats>"#,
+        data.tracker_id,
+        env!("CARGO_PKG_VERSION"),
+        data.uptime,
+        data.torrents.mutex,
+        data.torrents.iterator,
+        data.peers,
+        data.seeds,
+        data.completed,
+        c.tcp_accept,
+        c.tcp_announce,
+        c.tcp_scrape,
+        c.udp_overall,
+        c.udp_connect,
+        c.udp_announce,
+        c.udp_scrape,
+        c.udp_missmatch,
+        c.livesync,
+        codes,
+        data.mutex_stall
+    )
+}
+
+/// returns the body for a `mode`, None if opentracker does not know the mode
+///
+/// Modes besides `everything` use the four line mrtg format of opentracker.
+pub(crate) fn body(data: &Everything, mode: &str) -> Option<String> {
+    let mrtg = |first: usize, second: usize, what: &str| {
+        Some(format!(
+            "{}\n{}\nopentracker {} up {} seconds\nopentracker\n",
+            first, second, what, data.uptime
+        ))
+    };
+    let c = &data.connections;
+    match mode {
+        "everything" => Some(xml(data)),
+        "peer" => mrtg(data.peers, data.seeds, &format!("serving {} torrents", data.torrents.mutex)),
+        "torr" => mrtg(data.torrents.mutex, data.torrents.mutex, "serving torrents"),
+        "conn" => mrtg(c.tcp_accept + c.udp_overall, c.tcp_announce + c.udp_announce, "connections"),
+        "tcp4" => mrtg(c.tcp_announce, c.tcp_scrape, "tcp4 stats"),
+        "udp4" => mrtg(c.udp_announce, c.udp_scrape, "udp4 stats"),
+        "completed" => mrtg(data.completed, 0, "completed"),
+        "herr" => {
+            let mut codes: Vec<(&String, &usize)> = data.http_error.iter().collect();
+            codes.sort();
+            Some(codes.iter().map(|(code, count)| format!("{}: {}\n", code, count)).collect())
+        }
+        _ => None,
+    }
+}
+
+/// serves the simulated tracker on `addr` until killed
+pub fn serve(addr: &str, options: Options, verbose: u8) -> Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    if verbose >= 1 {
+        println!("Debug1: simulating opentracker on {}", listener.local_addr()?);
+    }
+    let simulation = Arc::new(Mutex::new(Simulation::new(options)));
+    let pool = ThreadPool::new(4)?;
+    let mut last = Instant::now();
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        {
+            let mut simulation = simulation.lock().unwrap_or_else(|err| err.into_inner());
+            simulation.advance(last.elapsed().as_secs_f64());
+            last = Instant::now();
+        }
+        let simulation = Arc::clone(&simulation);
+        pool.execute(move || {
+            let _ = respond(stream, &simulation);
+        })?;
+    }
+    Ok(())
+}
+
+/// answers one request of the exporter
+fn respond(mut stream: TcpStream, simulation: &Mutex<Simulation>) -> Result<()> {
+    let mut buffer = [0; 1024];
+    let amount = stream.read(&mut buffer)?;
+    let (path, query) = request_target(&String::from_utf8_lossy(&buffer[..amount]));
+
+    let (data, stalled) = {
+        let simulation = simulation.lock().unwrap_or_else(|err| err.into_inner());
+        (simulation.everything(), simulation.stalled())
+    };
+    // a stalled tracker answers late
+    thread::sleep(stalled);
+
+    let mode = query_param(&query, "mode").unwrap_or_else(|| String::from("everything"));
+    let (status, body) = match (path.as_str(), body(&data, &mode)) {
+        ("/stats", Some(body)) => ("200 OK", body),
+        ("/stats", None) => ("400 Invalid Request", String::from("invalid mode\n")),
+        _ => ("404 Not Found", String::from("not found\n")),
+    };
+    stream.write_all(
+        format!(
+            "HTTP/1.0 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nDate: {}\r\n\r\n{}",
+            status,
+            body.len(),
+            httpdate::fmt_http_date(SystemTime::now()),
+            body
+        )
+        .as_bytes(),
+    )?;
+    stream.flush()?;
+    Ok(())
+}
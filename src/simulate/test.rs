@@ -0,0 +1,91 @@
+//! test file to test the simulated tracker
+
+use super::{body, xml, Options, Simulation};
+use crate::parse_everything;
+
+#[test]
+fn xml_parses() {
+    let mut simulation = Simulation::new(Options::default());
+    simulation.advance(600.0);
+    let data = simulation.everything();
+    let parsed = parse_everything(&xml(&data)).unwrap();
+
+    let sorted = |mut counters: Vec<(String, u64)>| {
+        counters.sort();
+        counters
+    };
+    assert_eq!(sorted(parsed.counters()), sorted(data.counters()));
+    assert_eq!(parsed.gauges(), data.gauges());
+    assert_eq!(parsed.tracker_id, data.tracker_id);
+    assert_eq!(parsed.uptime, 600);
+    assert!(parsed.connections.udp_announce > 0);
+    assert!(!parsed.http_error.is_empty());
+}
+
+#[test]
+fn counters_grow() {
+    let mut simulation = Simulation::new(Options::default());
+    simulation.advance(60.0);
+    let first = simulation.everything();
+    simulation.advance(60.0);
+    let second = simulation.everything();
+
+    let after: std::collections::HashMap<String, u64> = second.counters().into_iter().collect();
+    for (path, before) in first.counters() {
+        assert!(after[&path] >= before, "{} went from {} to {}", path, before, after[&path]);
+    }
+    assert!(second.connections.tcp_accept > first.connections.tcp_accept);
+}
+
+#[test]
+fn same_seed_same_numbers() {
+    let options = Options {
+        seed: 42,
+        ..Options::default()
+    };
+    let (mut a, mut b) = (Simulation::new(options.clone()), Simulation::new(options));
+    a.advance(10.0);
+    b.advance(10.0);
+    assert_eq!(xml(&a.everything()), xml(&b.everything()));
+}
+
+#[test]
+fn restarts() {
+    let mut simulation = Simulation::new(Options {
+        restart: Some(100),
+        ..Options::default()
+    });
+    simulation.advance(90.0);
+    let id = simulation.everything().tracker_id;
+    simulation.advance(20.0);
+    let data = simulation.everything();
+    assert_eq!(data.uptime, 10);
+    assert_ne!(data.tracker_id, id);
+    assert!(data.connections.tcp_accept < 1000);
+}
+
+#[test]
+fn stalls_and_spikes() {
+    let mut simulation = Simulation::new(Options {
+        spikes: Some(100),
+        stalls: Some(50),
+        ..Options::default()
+    });
+    simulation.advance(90.0);
+    let before: usize = simulation.everything().http_error.values().sum();
+    assert_eq!(simulation.everything().mutex_stall, 1);
+
+    simulation.advance(20.0);
+    let after: usize = simulation.everything().http_error.values().sum();
+    assert!(after - before > 100, "no spike, {} to {}", before, after);
+    assert_eq!(simulation.everything().mutex_stall, 2);
+    assert!(simulation.stalled().as_secs_f64() > 0.0);
+}
+
+#[test]
+fn modes() {
+    let data = Simulation::new(Options::default()).everything();
+    assert!(body(&data, "everything").unwrap().starts_with("<?xml"));
+    assert_eq!(body(&data, "peer").unwrap().lines().count(), 4);
+    assert_eq!(body(&data, "nonsense"), None);
+}
@@ -0,0 +1,99 @@
+// snappy implements compression in the snappy block format
+
+// tests as sub module
+#[cfg(test)] // only add when running tests
+mod test;
+
+/// number of bits used for the hash table of previous positions
+const HASH_BITS: u32 = 14;
+
+/// compresses `input` into the raw snappy block format
+///
+/// Matches are searched greedily with a hash table of 4 byte sequences,
+/// this is fast and good enough for repetitive data like label sets.
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len() / 2 + 16);
+
+    // preamble: uncompressed length as varint
+    let mut length = input.len() as u64;
+    while length >= 0x80 {
+        output.push((length as u8) | 0x80);
+        length >>= 7;
+    }
+    output.push(length as u8);
+
+    // positions are stored +1, so 0 is an empty slot
+    let mut table = vec![0usize; 1 << HASH_BITS];
+    let mut literal_start = 0;
+    let mut pos = 0;
+
+    while pos + 4 <= input.len() {
+        let hash = hash(&input[pos..pos + 4]);
+        let candidate = table[hash];
+        table[hash] = pos + 1;
+
+        if candidate > 0 {
+            let candidate = candidate - 1;
+            let offset = pos - candidate;
+            if offset <= 0xffff && input[candidate..candidate + 4] == input[pos..pos + 4] {
+                let mut length = 4;
+                while pos + length < input.len() && input[candidate + length] == input[pos + length] {
+                    length += 1;
+                }
+
+                emit_literal(&mut output, &input[literal_start..pos]);
+                emit_copy(&mut output, offset, length);
+
+                pos += length;
+                literal_start = pos;
+                continue;
+            }
+        }
+        pos += 1;
+    }
+    emit_literal(&mut output, &input[literal_start..]);
+
+    output
+}
+
+/// hashes 4 bytes into an index of the hash table
+fn hash(bytes: &[u8]) -> usize {
+    let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    (value.wrapping_mul(0x1e35_a7bd) >> (32 - HASH_BITS)) as usize
+}
+
+/// writes a literal element
+fn emit_literal(output: &mut Vec<u8>, literal: &[u8]) {
+    if literal.is_empty() {
+        return;
+    }
+
+    let n = literal.len() - 1;
+    if n < 60 {
+        output.push((n as u8) << 2);
+    } else {
+        let bytes = (n as u32).to_le_bytes();
+        let count = if n < 1 << 8 {
+            1
+        } else if n < 1 << 16 {
+            2
+        } else if n < 1 << 24 {
+            3
+        } else {
+            4
+        };
+        output.push((59 + count as u8) << 2);
+        output.extend_from_slice(&bytes[..count]);
+    }
+    output.extend_from_slice(literal);
+}
+
+/// writes copy elements with a 2 byte offset, splitting long matches
+fn emit_copy(output: &mut Vec<u8>, offset: usize, mut length: usize) {
+    while length > 0 {
+        let chunk = std::cmp::min(length, 64);
+        output.push((((chunk - 1) as u8) << 2) | 2);
+        output.extend_from_slice(&(offset as u16).to_le_bytes());
+        length -= chunk;
+    }
+}
@@ -0,0 +1,90 @@
+//! test file for the snappy compression
+
+use super::compress;
+
+/// decompresses the snappy block format, only used to verify `compress`
+fn decompress(input: &[u8]) -> Vec<u8> {
+    let mut pos = 0;
+    let mut length = 0usize;
+    let mut shift = 0;
+    loop {
+        let byte = input[pos];
+        pos += 1;
+        length |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+        if byte < 0x80 {
+            break;
+        }
+    }
+
+    let mut output: Vec<u8> = Vec::with_capacity(length);
+    while pos < input.len() {
+        let tag = input[pos];
+        pos += 1;
+        match tag & 3 {
+            0 => {
+                let mut len = (tag >> 2) as usize;
+                if len >= 60 {
+                    let count = len - 59;
+                    let mut bytes = [0u8; 4];
+                    bytes[..count].copy_from_slice(&input[pos..pos + count]);
+                    len = u32::from_le_bytes(bytes) as usize;
+                    pos += count;
+                }
+                len += 1;
+                output.extend_from_slice(&input[pos..pos + len]);
+                pos += len;
+            }
+            2 => {
+                let len = (tag >> 2) as usize + 1;
+                let offset = u16::from_le_bytes([input[pos], input[pos + 1]]) as usize;
+                pos += 2;
+                let start = output.len() - offset;
+                for i in 0..len {
+                    let byte = output[start + i];
+                    output.push(byte);
+                }
+            }
+            _ => panic!("unexpected tag {}", tag),
+        }
+    }
+    assert_eq!(output.len(), length);
+    output
+}
+
+#[test]
+fn empty() {
+    assert_eq!(compress(b""), vec![0]);
+}
+
+#[test]
+fn literal_only() {
+    assert_eq!(compress(b"abc"), vec![3, 2 << 2, b'a', b'b', b'c']);
+}
+
+#[test]
+fn repeated() {
+    let input = b"abcdabcdabcdabcdabcdabcdabcdabcd".to_vec();
+    let compressed = compress(&input);
+    assert!(compressed.len() < input.len());
+    assert_eq!(decompress(&compressed), input);
+}
+
+#[test]
+fn long_literal_and_match() {
+    let mut input = Vec::new();
+    for i in 0..1000u32 {
+        input.extend_from_slice(&i.wrapping_mul(2_654_435_761).to_le_bytes());
+    }
+    let copy = input.clone();
+    input.extend_from_slice(&copy);
+    input.extend_from_slice(&[7; 300]);
+
+    assert_eq!(decompress(&compress(&input)), input);
+}
+
+#[test]
+fn exposition() {
+    let input = super::super::test::EVERYTHING.as_bytes();
+    assert_eq!(decompress(&compress(input)), input.to_vec());
+}
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::net::UdpSocket;
+
+use super::collector::{Scrape, Sink};
+use super::error::Result;
+use super::sanitize;
+
+// tests as sub module
+#[cfg(test)] // only add when running tests
+mod test;
+
+/// maximum payload of one datagram, fits into an ethernet frame
+const MAX_DATAGRAM: usize = 1432;
+
+/// maximum number of lines kept while the server is unreachable
+const MAX_QUEUE: usize = 100_000;
+
+/// Server describes the statsd server to send to
+#[derive(Clone, Debug)]
+pub struct Server {
+    /// address of the server, e.g. `localhost:8125`
+    pub addr: String,
+
+    /// add dogstatsd tags for `tracker` and `name` instead of putting the name into the metric
+    pub dogstatsd: bool,
+}
+
+impl Server {
+    /// creates a new Server for `addr` without tags
+    pub fn new(addr: &str) -> Self {
+        Self {
+            addr: addr.to_string(),
+            dogstatsd: false,
+        }
+    }
+}
+
+/// Statsd is a sink sending gauges and counters over udp
+///
+/// Cumulative values of the tracker are sent as the difference to the
+/// previous scrape, so the first scrape of a tracker only sends gauges.
+pub struct Statsd {
+    server: Server,
+    prefix: String,
+    socket: UdpSocket,
+    /// last cumulative values by tracker name and path
+    last: HashMap<String, HashMap<String, u64>>,
+    /// lines waiting to be sent
+    lines: Vec<String>,
+}
+
+impl Statsd {
+    /// creates a new Statsd for metrics named with `prefix`
+    pub fn new(server: Server, prefix: &str) -> Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(&server.addr)?;
+        Ok(Self {
+            server,
+            prefix: prefix.to_string(),
+            socket,
+            last: HashMap::new(),
+            lines: Vec::new(),
+        })
+    }
+
+    /// formats one line of the statsd protocol
+    fn line(&self, scrape: &Scrape, path: &str, value: &str, kind: &str) -> String {
+        if self.server.dogstatsd {
+            format!(
+                "{}.{}:{}|{}|#tracker:{},name:{}",
+                self.prefix,
+                path,
+                value,
+                kind,
+                scrape.data.tracker_id,
+                scrape.name.replace([',', '|', '#'], "_")
+            )
+        } else {
+            format!("{}.{}.{}:{}|{}", self.prefix, sanitize(&scrape.name), path, value, kind)
+        }
+    }
+}
+
+impl Sink for Statsd {
+    fn name(&self) -> String {
+        format!("statsd {}", self.server.addr)
+    }
+
+    fn collect(&mut self, scrape: &Scrape) {
+        for (path, value) in scrape.data.gauges() {
+            // a leading sign would change the gauge relative to its old value
+            if value < 0.0 {
+                self.lines.push(self.line(scrape, &path, "0", "g"));
+            }
+            self.lines.push(self.line(scrape, &path, &value.to_string(), "g"));
+        }
+
+        let counters = scrape.data.counters();
+        if let Some(last) = self.last.get(&scrape.name) {
+            for (path, value) in &counters {
+                let delta = match last.get(path) {
+                    // smaller than before, the tracker restarted
+                    Some(old) if old > value => *value,
+                    Some(old) => value - old,
+                    None => *value,
+                };
+                if delta > 0 {
+                    self.lines.push(self.line(scrape, path, &delta.to_string(), "c"));
+                }
+            }
+        }
+        self.last.insert(scrape.name.clone(), counters.into_iter().collect());
+
+        if self.lines.len() > MAX_QUEUE {
+            let dropped = self.lines.len() - MAX_QUEUE;
+            self.lines.drain(..dropped);
+            eprintln!("statsd queue full, dropped {} lines", dropped);
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        while !self.lines.is_empty() {
+            // as many lines as fit into one datagram, but at least one
+            let mut len = self.lines[0].len();
+            let mut count = 1;
+            while count < self.lines.len() && len + self.lines[count].len() < MAX_DATAGRAM {
+                len += self.lines[count].len() + 1;
+                count += 1;
+            }
+            self.socket.send(self.lines[..count].join("\n").as_bytes())?;
+            // sent lines are dropped right away, a retry must not count the deltas twice
+            self.lines.drain(..count);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+//! test file for the statsd sink
+
+use super::{Server, Statsd, MAX_QUEUE};
+use crate::collector::{test, Scrape, Sink};
+use std::net::UdpSocket;
+use std::time::Duration;
+
+fn scrape(peers: usize, tcp_accept: usize) -> Scrape {
+    test::scrape("tracker.example", 100, |data| {
+        data.tracker_id = 7;
+        data.peers = peers;
+        data.connections.tcp_accept = tcp_accept;
+    })
+}
+
+#[test]
+fn gauges_only_on_first_scrape() {
+    let mut sink = Statsd::new(Server::new("127.0.0.1:8125"), "ot").unwrap();
+    sink.collect(&scrape(10, 100));
+    assert!(sink.lines.contains(&String::from("ot.tracker_example.peers:10|g")));
+    assert!(!sink.lines.iter().any(|line| line.ends_with("|c")));
+}
+
+#[test]
+fn counter_deltas() {
+    let mut sink = Statsd::new(Server::new("127.0.0.1:8125"), "ot").unwrap();
+    sink.collect(&scrape(10, 100));
+    sink.lines.clear();
+
+    sink.collect(&scrape(10, 150));
+    assert!(sink
+        .lines
+        .contains(&String::from("ot.tracker_example.connections.tcp.accept:50|c")));
+
+    // restart of the tracker
+    sink.lines.clear();
+    sink.collect(&scrape(10, 20));
+    assert!(sink
+        .lines
+        .contains(&String::from("ot.tracker_example.connections.tcp.accept:20|c")));
+}
+
+#[test]
+fn dogstatsd_tags() {
+    let mut server = Server::new("127.0.0.1:8125");
+    server.dogstatsd = true;
+    let mut sink = Statsd::new(server, "ot").unwrap();
+    sink.collect(&scrape(10, 100));
+    assert!(sink
+        .lines
+        .contains(&String::from("ot.peers:10|g|#tracker:7,name:tracker.example")));
+}
+
+#[test]
+fn negative_gauge() {
+    let mut sink = Statsd::new(Server::new("127.0.0.1:8125"), "ot").unwrap();
+    let mut scrape = scrape(0, 0);
+    scrape.data.clock_skew = Some(-2);
+    sink.collect(&scrape);
+
+    let skew: Vec<&String> = sink
+        .lines
+        .iter()
+        .filter(|line| line.contains("clock_skew_seconds"))
+        .collect();
+    assert_eq!(
+        skew,
+        vec![
+            "ot.tracker_example.clock_skew_seconds:0|g",
+            "ot.tracker_example.clock_skew_seconds:-2|g"
+        ]
+    );
+}
+
+#[test]
+fn flush() {
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+    server.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let addr = server.local_addr().unwrap().to_string();
+
+    let mut sink = Statsd::new(Server::new(&addr), "ot").unwrap();
+    sink.collect(&scrape(10, 100));
+    sink.flush().unwrap();
+    assert!(sink.lines.is_empty());
+
+    let mut buffer = [0; 1500];
+    let len = server.recv(&mut buffer).unwrap();
+    let datagram = String::from_utf8_lossy(&buffer[..len]);
+    assert!(datagram.starts_with("ot.tracker_example.uptime:0|g\n"));
+}
+
+#[test]
+fn failed_flush_keeps_unsent_lines() {
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap().to_string();
+    let mut sink = Statsd::new(Server::new(&addr), "ot").unwrap();
+    for peers in 0..10 {
+        let mut scrape = scrape(peers, 100);
+        scrape.name = format!("tracker{}", peers);
+        sink.collect(&scrape);
+    }
+    let count = sink.lines.len();
+
+    // the first datagram is sent, the refused port fails one of the next
+    drop(server);
+    assert!(sink.flush().is_err());
+    assert!(!sink.lines.is_empty());
+    assert!(sink.lines.len() < count);
+    assert!(sink.lines[0].starts_with("ot.tracker"));
+}
+
+#[test]
+fn queue_is_bounded() {
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap().to_string();
+    let mut sink = Statsd::new(Server::new(&addr), "ot").unwrap();
+    let mut peers = 0;
+    while sink.lines.len() < MAX_QUEUE {
+        peers += 1;
+        sink.collect(&scrape(peers, 100 + peers));
+    }
+    sink.collect(&scrape(peers + 1, 200 + peers));
+
+    // the oldest lines are dropped
+    assert_eq!(sink.lines.len(), MAX_QUEUE);
+    assert!(!sink.lines.contains(&String::from("ot.tracker_example.peers:1|g")));
+    assert!(sink.lines.contains(&format!("ot.tracker_example.peers:{}|g", peers + 1)));
+}
@@ -0,0 +1,425 @@
+//! test file to test the parsing of the opentracker stats
+
+mod parse_response {
+    use super::super::parse_response;
+
+    #[test]
+    fn headers_and_body() {
+        let response = parse_response(
+            "HTTP/1.0 200 OK\r\nContent-Type: text/plain\r\nDate: Sun, 06 Nov 1994 08:49:37 GMT\r\n\r\n<stats/>\r\n",
+        )
+        .unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(
+            response.headers.get("date"),
+            Some(&String::from("Sun, 06 Nov 1994 08:49:37 GMT"))
+        );
+        assert_eq!(
+            response.headers.get("content-type"),
+            Some(&String::from("text/plain"))
+        );
+        assert_eq!(response.body, String::from("<stats/>"));
+    }
+
+    #[test]
+    fn invalid_status_line() {
+        assert!(parse_response("garbage\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn missing_header_end() {
+        assert!(parse_response("HTTP/1.0 200 OK\r\nDate: Sun").is_err());
+    }
+}
+
+mod request_target {
+    use super::super::{query_param, query_params, request_target};
+
+    #[test]
+    fn paths() {
+        assert_eq!(
+            request_target("GET /stats.json HTTP/1.1\r\nHost: x\r\n\r\n"),
+            (String::from("/stats.json"), String::new())
+        );
+        assert_eq!(
+            request_target("GET /api/history?field=peers HTTP/1.1\r\n"),
+            (String::from("/api/history"), String::from("field=peers"))
+        );
+        assert_eq!(request_target(""), (String::from("/"), String::new()));
+    }
+
+    #[test]
+    fn query() {
+        let query = "field=http_codes.400_Invalid_Request&since=-3600&name=a+b%2Fc%zz%";
+        assert_eq!(query_param(query, "field"), Some(String::from("http_codes.400_Invalid_Request")));
+        assert_eq!(query_param(query, "since"), Some(String::from("-3600")));
+        assert_eq!(query_param(query, "name"), Some(String::from("a b/c%zz%")));
+        assert_eq!(query_param(query, "missing"), None);
+        assert_eq!(
+            query_params("target=a%3A6969&x=1&target=b", "target"),
+            vec![String::from("a:6969"), String::from("b")]
+        );
+    }
+}
+
+mod clock_skew {
+    use super::super::clock_skew;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    // Sun, 06 Nov 1994 08:49:37 GMT
+    const DATE: u64 = 784_111_777;
+
+    #[test]
+    fn in_sync() {
+        let local = UNIX_EPOCH + Duration::from_secs(DATE);
+        assert_eq!(clock_skew("Sun, 06 Nov 1994 08:49:37 GMT", local), Some(0));
+    }
+
+    #[test]
+    fn tracker_ahead() {
+        let local = UNIX_EPOCH + Duration::from_secs(DATE - 90);
+        assert_eq!(clock_skew("Sun, 06 Nov 1994 08:49:37 GMT", local), Some(90));
+    }
+
+    #[test]
+    fn tracker_behind() {
+        let local = UNIX_EPOCH + Duration::from_secs(DATE + 3600);
+        assert_eq!(clock_skew("Sun, 06 Nov 1994 08:49:37 GMT", local), Some(-3600));
+    }
+
+    #[test]
+    fn not_parsable() {
+        assert_eq!(clock_skew("yesterday", UNIX_EPOCH), None);
+    }
+}
+
+mod get_string {
+    use super::super::Everything;
+
+    #[test]
+    fn clock_skew() {
+        let mut data = Everything::new();
+        assert!(!data.get_string("ot", "test").contains("ot_clock_skew_seconds"));
+
+        data.clock_skew = Some(-3);
+        assert!(data
+            .get_string("ot", "test")
+            .contains("\not_clock_skew_seconds{tracker=\"0\",name=\"test\"} -3\n"));
+    }
+}
+
+/// stats as returned by opentracker on `/stats?mode=everything`
+pub(crate) const EVERYTHING: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<stats>
+  <tracker_id>1337</tracker_id>
+  <version>
+  </version>
+  <uptime>3600</uptime>
+  <torrents>
+    <count_mutex>42</count_mutex>
+    <count_iterator>41</count_iterator>
+  </torrents>
+  <peers>
+    <count>300</count>
+  </peers>
+  <seeds>
+    <count>120</count>
+  </seeds>
+  <completed>
+    <count>55</count>
+  </completed>
+  <connections>
+    <tcp>
+      <accept>1000</accept>
+      <announce>900</announce>
+      <scrape>100</scrape>
+    </tcp>
+    <udp>
+      <overall>5000</overall>
+      <connect>1500</connect>
+      <announce>3000</announce>
+      <scrape>400</scrape>
+      <missmatch>7</missmatch>
+    </udp>
+    <livesync>
+      <count>0</count>
+    </livesync>
+  </connections>
+  <debug>
+    <renew>
+    </renew>
+    <http_error>
+      <count code="400 Invalid Request">3</count>
+    </http_error>
+    <mutex_stall>
+      <count>2</count>
+    </mutex_stall>
+  </debug>
+</stats>"#;
+
+mod parse_everything {
+    use super::super::parse_everything;
+    use super::EVERYTHING;
+
+    #[test]
+    fn fields() {
+        let data = parse_everything(EVERYTHING).unwrap();
+        assert_eq!(data.tracker_id, 1337);
+        assert_eq!(data.uptime, 3600);
+        assert_eq!(data.torrents.mutex, 42);
+        assert_eq!(data.torrents.iterator, 41);
+        assert_eq!(data.peers, 300);
+        assert_eq!(data.seeds, 120);
+        assert_eq!(data.completed, 55);
+        assert_eq!(data.mutex_stall, 2);
+        assert_eq!(data.connections.tcp_accept, 1000);
+        assert_eq!(data.connections.udp_missmatch, 7);
+        assert_eq!(data.http_error.get("400 Invalid Request"), Some(&3));
+    }
+
+    #[test]
+    fn not_xml() {
+        let err = parse_everything("<html><body>404").err().unwrap();
+        assert_eq!(err.kind().error_string(), "NotParsable(unexpected root element html)");
+        assert!(parse_everything("garbage").is_err());
+    }
+
+    #[test]
+    fn exposition() {
+        let data = parse_everything(EVERYTHING).unwrap();
+        assert_eq!(
+            data.get_string("ot", "test"),
+            format!(
+                r#"# HELP ot_uptime uptime of the tracker
+# TYPE ot_uptime gauge
+ot_uptime{{tracker="1337",name="test"}} 3600
+# HELP ot_torrents counts torrents on server
+# TYPE ot_torrents gauge
+ot_torrents{{tracker="1337",type="mutex",name="test"}} 42
+ot_torrents{{tracker="1337",type="iterator",name="test"}} 41
+# HELP ot_count count for varios things
+# TYPE ot_count gauge
+ot_count{{tracker="1337",name="test",type="peers"}} 300
+ot_count{{tracker="1337",name="test",type="seeds"}} 120
+ot_count{{tracker="1337",name="test",type="completed"}} 55
+ot_count{{tracker="1337",name="test",type="mutex_stall"}} 2
+# HELP ot_connections to the tracker
+# TYPE ot_connections gauge
+ot_connections{{tracker="1337",name="test",protocol="tcp",type="accept"}} 1000
+ot_connections{{tracker="1337",name="test",protocol="tcp",type="announce"}} 900
+ot_connections{{tracker="1337",name="test",protocol="tcp",type="scrape"}} 100
+ot_connections{{tracker="1337",name="test",protocol="udp",type="overall"}} 5000
+ot_connections{{tracker="1337",name="test",protocol="udp",type="connect"}} 1500
+ot_connections{{tracker="1337",name="test",protocol="udp",type="announce"}} 3000
+ot_connections{{tracker="1337",name="test",protocol="udp",type="scrape"}} 400
+ot_connections{{tracker="1337",name="test",protocol="udp",type="missmatch"}} 7
+ot_connections{{tracker="1337",name="test",type="livesync"}} 0
+# HELP ot_http_codes http error code count
+# TYPE ot_http_codes gauge
+ot_http_codes{{tracker="1337",name="test",code="400 Invalid Request"}} 3
+
+# opentracker/export_prometheus {}
+"#,
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+    }
+}
+
+mod shutdown {
+    use super::super::{signal, Config};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn stops_listening() {
+        let mut conf = Config::new();
+        conf.interface = String::from("127.0.0.1");
+        conf.port = 0;
+
+        let (done, stopped) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = done.send(conf.run());
+        });
+        std::thread::sleep(Duration::from_millis(200));
+        signal::request_shutdown();
+        assert_eq!(stopped.recv_timeout(Duration::from_secs(5)), Ok(Ok(())));
+    }
+}
+
+mod pool_families {
+    use super::super::{metrics, pool_families, threads::Stats};
+
+    #[test]
+    fn render() {
+        let stats = Stats {
+            workers: 4,
+            busy: 1,
+            queued: 2,
+            executed: 3,
+            panics: 0,
+            rejected: 5,
+            durations: vec![(0.1, 1), (1.0, 2)],
+            duration_sum: 1.5,
+        };
+        let text = metrics::render(&pool_families(&stats, "ot"));
+        assert!(text.contains("ot_exporter_pool_workers{state=\"busy\"} 1\not_exporter_pool_workers{state=\"idle\"} 3\n"));
+        assert!(text.contains("ot_exporter_pool_queue_depth{} 2\n"));
+        assert!(text.contains(
+            "# TYPE ot_exporter_pool_job_duration_seconds histogram\n\
+             ot_exporter_pool_job_duration_seconds_bucket{le=\"0.1\"} 1\n\
+             ot_exporter_pool_job_duration_seconds_bucket{le=\"1\"} 2\n\
+             ot_exporter_pool_job_duration_seconds_bucket{le=\"+Inf\"} 3\n\
+             ot_exporter_pool_job_duration_seconds_sum{} 1.5\n\
+             ot_exporter_pool_job_duration_seconds_count{} 3\n"
+        ));
+        assert!(text.contains("ot_exporter_pool_rejected_total{} 5\n"));
+    }
+}
+
+mod flight_families {
+    use super::super::{flight::Stats, flight_families, metrics};
+
+    #[test]
+    fn render() {
+        let stats = Stats {
+            calls: 2,
+            in_flight: 5,
+            recent: 1,
+        };
+        let text = metrics::render(&flight_families(&stats, "ot"));
+        assert!(text.contains("# TYPE ot_exporter_upstream_scrapes_total counter\not_exporter_upstream_scrapes_total{} 2\n"));
+        assert!(text.contains(
+            "ot_exporter_scrapes_coalesced_total{reason=\"in_flight\"} 5\n\
+             ot_exporter_scrapes_coalesced_total{reason=\"recent\"} 1\n"
+        ));
+    }
+}
+
+mod forbidden_target {
+    use super::super::forbidden_target;
+
+    #[test]
+    fn only_configured_trackers() {
+        let allowed = vec![String::from("a:6969"), String::from("b:6969")];
+        let targets = vec![String::from("b:6969"), String::from("a:6969")];
+        assert_eq!(forbidden_target(&targets, &allowed), None);
+
+        let targets = vec![String::from("a:6969"), String::from("10.0.0.1:22")];
+        assert_eq!(forbidden_target(&targets, &allowed), Some(&targets[1]));
+        assert_eq!(forbidden_target(&targets, &[]), Some(&targets[0]));
+    }
+}
+
+mod merge_families {
+    use super::super::{merge_families, parse_everything, test::EVERYTHING};
+
+    #[test]
+    fn samples_of_same_family() {
+        let data = parse_everything(EVERYTHING).unwrap();
+        let mut families = data.families("ot", "a");
+        let count = families.len();
+        merge_families(&mut families, data.families("ot", "b"));
+
+        assert_eq!(families.len(), count);
+        let uptime = families.iter().find(|family| family.name == "ot_uptime").unwrap();
+        let names: Vec<_> = uptime.samples.iter().map(|sample| sample.label("name").unwrap()).collect();
+        assert_eq!(names, vec!["a", "b"]);
+    }
+}
+
+mod scrape {
+    use super::super::{error::ErrorKind, scrape, Config};
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn silent_tracker_times_out() {
+        // accepts the connection but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut conf = Config::new();
+        conf.url = listener.local_addr().unwrap().to_string();
+        conf.scrape_timeout = 1;
+
+        let start = Instant::now();
+        let err = scrape(&conf.upstream()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::IoTimedOut);
+        assert!(start.elapsed() < Duration::from_secs(3));
+        drop(listener);
+    }
+
+    #[test]
+    fn unreachable_tracker() {
+        // nothing listens on the port once the listener is gone
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let mut conf = Config::new();
+        conf.url = addr.to_string();
+        match scrape(&conf.upstream()).unwrap_err().kind() {
+            ErrorKind::Unreachable(reason) => assert!(reason.starts_with(&conf.url)),
+            kind => panic!("unexpected {}", kind),
+        }
+
+        conf.url = String::from("tracker.invalid:6969");
+        assert!(matches!(scrape(&conf.upstream()).unwrap_err().kind(), ErrorKind::Unreachable(_)));
+    }
+
+    #[test]
+    fn error_status() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut conf = Config::new();
+        conf.url = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.read(&mut [0; 512]);
+            stream.write_all(b"HTTP/1.0 503 Service Unavailable\r\n\r\nbusy").unwrap();
+        });
+        assert_eq!(scrape(&conf.upstream()).unwrap_err().kind(), ErrorKind::HttpStatus(503));
+    }
+}
+
+mod hostname {
+    use super::super::hostname;
+
+    #[test]
+    #[cfg(unix)]
+    fn of_this_host() {
+        let name = hostname().unwrap();
+        assert!(!name.is_empty());
+        assert!(!name.contains('\0'));
+    }
+}
+
+mod reload_collection {
+    use super::super::{reload, reload_collection, Config, Context};
+    use std::sync::Arc;
+
+    #[test]
+    fn restarts_with_new_config() {
+        let mut conf = Config::new();
+        conf.url = String::from("127.0.0.1:1");
+        let fixed = reload::Fixed::of(&conf);
+        let mut context = Arc::new(Context::new(&conf, None));
+        let mut collector = None;
+
+        // nothing is replaced while the config cannot be loaded
+        let failing: reload::Loader = Arc::new(|| Err(String::from("broken")));
+        assert!(reload_collection(&Some(failing), &fixed, &mut context, &mut collector).is_err());
+        assert!(reload_collection(&None, &fixed, &mut context, &mut collector).is_err());
+        assert_eq!(context.name, conf.name);
+        assert!(collector.is_none());
+
+        let loader: reload::Loader = Arc::new(|| {
+            let mut conf = Config::new();
+            conf.url = String::from("127.0.0.1:1");
+            conf.name = String::from("reloaded");
+            conf.history = Some(1);
+            Ok(conf)
+        });
+        let conf = reload_collection(&Some(loader), &fixed, &mut context, &mut collector).unwrap();
+        assert_eq!(conf.name, "reloaded");
+        assert_eq!(context.name, "reloaded");
+        assert!(context.history.is_some());
+        collector.take().unwrap().stop();
+    }
+}
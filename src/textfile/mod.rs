@@ -0,0 +1,49 @@
+use std::fs;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use super::error::Result;
+use super::{scrape, Config};
+
+// tests as sub module
+#[cfg(test)] // only add when running tests
+mod test;
+
+/// scrapes the tracker once and writes the exposition to `path`
+/// the file is left untouched if the scrape fails
+pub fn write(conf: &Config, path: &Path) -> Result<()> {
+    let content = scrape(&conf.upstream())?.get_string(&conf.prefix, &conf.name);
+    write_atomic(path, content.as_bytes())
+}
+
+/// scrapes the tracker once and prints the exposition
+pub fn print(conf: &Config) -> Result<()> {
+    let content = scrape(&conf.upstream())?.get_string(&conf.prefix, &conf.name);
+    std::io::stdout().write_all(content.as_bytes())?;
+    Ok(())
+}
+
+/// writes `content` to a hidden temp file next to `path` and renames it into place,
+/// so the textfile collector never reads a partial file
+pub(crate) fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    let tmp = temp_path(path);
+    let result = (|| {
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(content)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// returns `.{file}.{pid}.tmp` in the directory of `path`, ignored by the textfile collector
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map_or(String::from("output"), |name| name.to_string_lossy().to_string());
+    path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()))
+}
@@ -0,0 +1,58 @@
+//! test file to test the atomic textfile output
+
+use super::{temp_path, write, write_atomic};
+use crate::error::ErrorKind;
+use crate::Config;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("opentracker_exporter_textfile_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn temp_name() {
+    assert_eq!(
+        temp_path(Path::new("/var/lib/node_exporter/opentracker.prom")),
+        PathBuf::from(format!(
+            "/var/lib/node_exporter/.opentracker.prom.{}.tmp",
+            std::process::id()
+        ))
+    );
+}
+
+#[test]
+fn replaces_file() {
+    let dir = dir("replace");
+    let path = dir.join("opentracker.prom");
+    fs::write(&path, "old").unwrap();
+
+    write_atomic(&path, b"new\n").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "new\n");
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn missing_dir() {
+    let path = dir("missing").join("not").join("opentracker.prom");
+    let err = write_atomic(&path, b"new\n").err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::IoNotFound);
+}
+
+#[test]
+fn unreachable_keeps_file() {
+    let dir = dir("unreachable");
+    let path = dir.join("opentracker.prom");
+    fs::write(&path, "old").unwrap();
+
+    let mut conf = Config::new();
+    conf.url = String::from("127.0.0.1:1");
+    assert!(write(&conf, &path).is_err());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,8 +1,12 @@
+use std::any::Any;
 use std::fmt;
-use std::sync::{mpsc, Arc, Mutex};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard};
 use std::thread;
+use std::time::{Duration, Instant};
 
-/// threads is the lib for the ThreadPool struct
+// threads is the lib for the ThreadPool struct
 
 #[doc(inline)]
 pub use super::error::Result;
@@ -11,12 +15,95 @@ use super::error::{Error, ErrorKind};
 
 /// struct used a type for the ThreadPool
 pub struct ThreadPool {
-    size: usize,
-    workers: Vec<Worker>,
+    size: AtomicUsize,
+    next_id: AtomicUsize,
+    workers: Mutex<Vec<Worker>>,
     sender: mpsc::Sender<Message>,
+    shared: Arc<Shared>,
+    queue: Option<usize>,
+    autoscale: Mutex<Option<Autoscale>>,
+    /// last time a job had to wait for a worker, used to retire idle workers
+    last_full: Mutex<Instant>,
     do_verbose: bool,
 }
 
+/// Autoscale adds workers while jobs are waiting and retires them when idle
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Autoscale {
+    /// fewest workers to keep
+    pub min: usize,
+    /// most workers to start
+    pub max: usize,
+    /// time without waiting jobs before a worker is retired
+    pub idle: Duration,
+}
+
+/// upper bounds in seconds of the buckets of the job duration histogram
+pub const DURATION_BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// state shared between the ThreadPool and its workers
+struct Shared {
+    receiver: Mutex<mpsc::Receiver<Message>>,
+    queued: AtomicUsize,
+    busy: AtomicUsize,
+    executed: AtomicUsize,
+    rejected: AtomicUsize,
+    panics: AtomicUsize,
+    /// jobs per bucket of `DURATION_BUCKETS`, the last one counts the longer jobs
+    durations: [AtomicU64; DURATION_BUCKETS.len() + 1],
+    /// sum of all job durations in microseconds
+    duration_sum: AtomicU64,
+}
+
+impl Shared {
+    /// runs a job and records it
+    fn run(&self, job: Job) {
+        self.busy.fetch_add(1, Ordering::SeqCst);
+        let start = Instant::now();
+        // the panic hook already printed the message
+        if panic::catch_unwind(AssertUnwindSafe(|| job.call_box())).is_err() {
+            self.panics.fetch_add(1, Ordering::SeqCst);
+        }
+        let duration = start.elapsed();
+        let bucket = DURATION_BUCKETS
+            .iter()
+            .position(|bound| duration.as_secs_f64() <= *bound)
+            .unwrap_or(DURATION_BUCKETS.len());
+        self.durations[bucket].fetch_add(1, Ordering::SeqCst);
+        self.duration_sum.fetch_add(duration.as_micros() as u64, Ordering::SeqCst);
+        self.busy.fetch_sub(1, Ordering::SeqCst);
+        self.executed.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Stats is a snapshot of the counters of a ThreadPool
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stats {
+    /// number of worker threads
+    pub workers: usize,
+    /// workers running a job
+    pub busy: usize,
+    /// jobs waiting for a worker
+    pub queued: usize,
+    /// jobs finished, including the ones which panicked
+    pub executed: usize,
+    /// jobs which panicked
+    pub panics: usize,
+    /// jobs rejected because the queue was full
+    pub rejected: usize,
+    /// cumulative number of jobs per upper bound of `DURATION_BUCKETS`
+    pub durations: Vec<(f64, u64)>,
+    /// sum of all job durations in seconds
+    pub duration_sum: f64,
+}
+
+impl Stats {
+    /// returns the workers waiting for a job
+    pub fn idle(&self) -> usize {
+        self.workers.saturating_sub(self.busy)
+    }
+}
+
 impl ThreadPool {
     /// creates a new `ThreadPool` with the given number of threads
     ///
@@ -30,13 +117,13 @@ impl ThreadPool {
     ///
     /// ## Create a ThreadPool with 4 threads
     /// ```
-    /// use poke_escape_server::threads::ThreadPool;
+    /// use opentracker_exporter::threads::ThreadPool;
     /// let pool = ThreadPool::new(4).unwrap();  // creates a threadpool with 4 threads
     /// ```
     ///
     /// ## Fails when trying to create 4 threads
     /// ```should_panic
-    /// use poke_escape_server::threads::ThreadPool;
+    /// use opentracker_exporter::threads::ThreadPool;
     /// let pool = ThreadPool::new(0).unwrap();     // unwrap panics
     /// ```
     pub fn new(size: usize) -> Result<ThreadPool> {
@@ -45,18 +132,32 @@ impl ThreadPool {
         }
 
         let (sender, receiver) = mpsc::channel();
-        let receiver = Arc::new(Mutex::new(receiver));
+        let shared = Arc::new(Shared {
+            receiver: Mutex::new(receiver),
+            queued: AtomicUsize::new(0),
+            busy: AtomicUsize::new(0),
+            executed: AtomicUsize::new(0),
+            rejected: AtomicUsize::new(0),
+            panics: AtomicUsize::new(0),
+            durations: Default::default(),
+            duration_sum: AtomicU64::new(0),
+        });
 
         let mut workers = Vec::with_capacity(size);
 
         for id in 0..size {
-            workers.push(Worker::new(id, Arc::clone(&receiver)));
+            workers.push(Worker::new(id, Arc::clone(&shared)));
         }
 
         Ok(ThreadPool {
-            workers,
-            size,
+            workers: Mutex::new(workers),
+            size: AtomicUsize::new(size),
+            next_id: AtomicUsize::new(size),
             sender,
+            shared,
+            queue: None,
+            autoscale: Mutex::new(None),
+            last_full: Mutex::new(Instant::now()),
             do_verbose: false,
         })
     }
@@ -67,7 +168,7 @@ impl ThreadPool {
     ///
     /// # Example
     /// ```
-    /// use poke_escape_server::threads::ThreadPool;
+    /// use opentracker_exporter::threads::ThreadPool;
     /// let mut pool = ThreadPool::new(4).unwrap();
     /// pool.verbose();
     /// assert_eq!(pool.is_verbose(), true);
@@ -83,7 +184,7 @@ impl ThreadPool {
     /// # Example
     /// ## set into verbose mode
     /// ```
-    /// use poke_escape_server::threads::ThreadPool;
+    /// use opentracker_exporter::threads::ThreadPool;
     /// let mut pool = ThreadPool::new(4).unwrap();
     /// pool.set_verbose_mode(true);
     /// assert_eq!(pool.is_verbose(), true);
@@ -91,7 +192,7 @@ impl ThreadPool {
     ///
     /// ## set out of verbose mode
     /// ```
-    /// use poke_escape_server::threads::ThreadPool;
+    /// use opentracker_exporter::threads::ThreadPool;
     /// let mut pool = ThreadPool::new(4).unwrap();
     /// pool.set_verbose_mode(false);
     /// assert_eq!(pool.is_verbose(), false);
@@ -105,7 +206,7 @@ impl ThreadPool {
     ///
     /// # Example
     /// ```
-    /// use poke_escape_server::threads::ThreadPool;
+    /// use opentracker_exporter::threads::ThreadPool;
     /// let mut pool = ThreadPool::new(4).unwrap();
     /// pool.verbose();
     /// assert_eq!(pool.is_verbose(), true);
@@ -119,19 +220,380 @@ impl ThreadPool {
     /// # Examples
     ///
     /// ```
-    /// use poke_escape_server::threads::ThreadPool;
+    /// use opentracker_exporter::threads::ThreadPool;
     /// let pool = ThreadPool::new(4).unwrap();
     /// assert_eq!(pool.get_threads(), 4);
     /// ```
     pub fn get_threads(&self) -> usize {
-        self.size
+        self.size.load(Ordering::SeqCst)
+    }
+
+    /// starts or retires workers until the ThreadPool has `size` workers
+    ///
+    /// Retired workers finish the jobs queued before the resize first.
+    ///
+    /// # Errors
+    /// Errors if `size` is 0.
+    ///
+    /// # Example
+    /// ```
+    /// use opentracker_exporter::threads::ThreadPool;
+    /// let pool = ThreadPool::new(4).unwrap();
+    /// pool.resize(8).unwrap();
+    /// assert_eq!(pool.get_threads(), 8);
+    /// pool.resize(2).unwrap();
+    /// assert_eq!(pool.get_threads(), 2);
+    /// assert!(pool.resize(0).is_err());
+    /// ```
+    pub fn resize(&self, size: usize) -> Result<()> {
+        if size == 0 {
+            return Err(Error::new(ErrorKind::PoolToSmall));
+        }
+        let mut workers = self.workers();
+        let current = self.size.swap(size, Ordering::SeqCst);
+        if self.do_verbose && current != size {
+            println!("resizing ThreadPool from {} to {} workers", current, size);
+        }
+        for _ in current..size {
+            let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+            workers.push(Worker::new(id, Arc::clone(&self.shared)));
+        }
+        for _ in size..current {
+            // the first worker taking it retires, `maintain` removes it afterwards
+            let _ = self.sender.send(Message::Terminate);
+        }
+        Ok(())
+    }
+
+    /// sets the policy to resize the ThreadPool on its own, `None` keeps the size
+    ///
+    /// The policy is applied when jobs are executed.
+    ///
+    /// # Example
+    /// ```
+    /// use opentracker_exporter::threads::{Autoscale, ThreadPool};
+    /// use std::sync::mpsc;
+    /// use std::time::Duration;
+    ///
+    /// let pool = ThreadPool::new(1).unwrap();
+    /// pool.set_autoscale(Some(Autoscale { min: 1, max: 3, idle: Duration::from_secs(60) }));
+    ///
+    /// let (sender, receiver) = mpsc::channel::<()>();
+    /// let receiver = std::sync::Arc::new(std::sync::Mutex::new(receiver));
+    /// for _ in 0..4 {
+    ///     let receiver = receiver.clone();
+    ///     pool.execute(move || { let _ = receiver.lock().unwrap().recv(); }).unwrap();
+    ///     # std::thread::sleep(Duration::from_millis(10));
+    /// }
+    /// assert_eq!(pool.get_threads(), 3);
+    /// drop(sender);
+    /// ```
+    pub fn set_autoscale(&self, policy: Option<Autoscale>) {
+        *self.autoscale.lock().unwrap_or_else(|err| err.into_inner()) = policy;
+    }
+
+    /// limits the number of jobs waiting for a worker, `None` means unlimited
+    ///
+    /// Jobs beyond the limit are rejected instead of queued.
+    ///
+    /// # Example
+    /// ```
+    /// use opentracker_exporter::threads::ThreadPool;
+    /// use std::sync::mpsc;
+    ///
+    /// let mut pool = ThreadPool::new(1).unwrap();
+    /// pool.set_queue_size(Some(1));
+    ///
+    /// let (sender, receiver) = mpsc::channel::<()>();
+    /// pool.execute(move || receiver.recv().unwrap()).unwrap(); // keeps the worker busy
+    /// # while pool.queued() > 0 { std::thread::yield_now() }
+    /// pool.execute(|| {}).unwrap();
+    /// assert!(pool.execute(|| {}).is_err());
+    /// assert_eq!(pool.rejected(), 1);
+    /// sender.send(()).unwrap();
+    /// ```
+    pub fn set_queue_size(&mut self, size: Option<usize>) -> &Self {
+        self.queue = size;
+        self
+    }
+
+    /// returns the number of jobs waiting for a worker
+    pub fn queued(&self) -> usize {
+        self.shared.queued.load(Ordering::SeqCst)
+    }
+
+    /// returns the number of jobs rejected because the queue was full
+    pub fn rejected(&self) -> usize {
+        self.shared.rejected.load(Ordering::SeqCst)
+    }
+
+    /// returns the number of jobs which panicked
+    ///
+    /// A panicking job does not take its worker down, the worker continues with the next job.
+    ///
+    /// # Example
+    /// ```
+    /// use opentracker_exporter::threads::ThreadPool;
+    /// use std::sync::mpsc;
+    ///
+    /// let pool = ThreadPool::new(1).unwrap();
+    /// pool.execute(|| panic!("bad request")).unwrap();
+    ///
+    /// let (sender, receiver) = mpsc::channel();
+    /// pool.execute(move || sender.send(42).unwrap()).unwrap();
+    /// assert_eq!(receiver.recv().unwrap(), 42);
+    /// assert_eq!(pool.panics(), 1);
+    /// ```
+    pub fn panics(&self) -> usize {
+        self.shared.panics.load(Ordering::SeqCst)
+    }
+
+    /// returns a snapshot of the counters of the ThreadPool
+    ///
+    /// # Example
+    /// ```
+    /// use opentracker_exporter::threads::ThreadPool;
+    ///
+    /// let pool = ThreadPool::new(2).unwrap();
+    /// pool.execute(|| {}).unwrap();
+    /// pool.execute(|| panic!("bad request")).unwrap();
+    /// # while pool.stats().executed < 2 { std::thread::yield_now() }
+    /// let stats = pool.stats();
+    /// assert_eq!(stats.executed, 2);
+    /// assert_eq!(stats.panics, 1);
+    /// assert_eq!(stats.idle(), 2);
+    /// assert_eq!(stats.durations.last(), Some(&(10.0, 2)));
+    /// ```
+    pub fn stats(&self) -> Stats {
+        let shared = &self.shared;
+        let mut cumulative = 0;
+        let durations = DURATION_BUCKETS
+            .iter()
+            .zip(shared.durations.iter())
+            .map(|(bound, count)| {
+                cumulative += count.load(Ordering::SeqCst);
+                (*bound, cumulative)
+            })
+            .collect();
+        Stats {
+            workers: self.get_threads(),
+            busy: shared.busy.load(Ordering::SeqCst),
+            queued: shared.queued.load(Ordering::SeqCst),
+            executed: shared.executed.load(Ordering::SeqCst),
+            panics: shared.panics.load(Ordering::SeqCst),
+            rejected: shared.rejected.load(Ordering::SeqCst),
+            durations,
+            duration_sum: shared.duration_sum.load(Ordering::SeqCst) as f64 / 1_000_000.0,
+        }
+    }
+
+    /// locks the workers, a panic while holding the lock does not matter for the list
+    fn workers(&self) -> MutexGuard<'_, Vec<Worker>> {
+        self.workers.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// replaces workers whose thread died, removes retired ones and applies the autoscale policy
+    fn maintain(&self) {
+        self.workers().retain_mut(|worker| {
+            if !worker.thread.as_ref().is_some_and(|thread| thread.is_finished()) {
+                return true;
+            }
+            match worker.thread.take().map(|thread| thread.join()) {
+                Some(Err(panic)) => {
+                    eprintln!("worker {} died: {}", worker.id, panic_message(&panic));
+                    if self.do_verbose {
+                        println!("respawning worker {}", worker.id);
+                    }
+                    *worker = Worker::new(worker.id, Arc::clone(&self.shared));
+                    true
+                }
+                // a worker only stops on its own when it was retired by `resize`
+                _ => false,
+            }
+        });
+
+        let policy = *self.autoscale.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(policy) = policy {
+            self.autoscale(policy);
+        }
+    }
+
+    /// grows the ThreadPool while jobs have to wait and shrinks it after `policy.idle`
+    fn autoscale(&self, policy: Autoscale) {
+        let size = self.get_threads();
+        let mut last_full = self.last_full.lock().unwrap_or_else(|err| err.into_inner());
+        let full = self.queued() > 0 || self.shared.busy.load(Ordering::SeqCst) >= size;
+        let target = if full {
+            *last_full = Instant::now();
+            size.saturating_add(1).min(policy.max)
+        } else if last_full.elapsed() >= policy.idle {
+            // one worker per idle period
+            *last_full = Instant::now();
+            size.saturating_sub(1)
+        } else {
+            size
+        };
+        let target = target.max(policy.min).max(1);
+        if target != size {
+            let _ = self.resize(target);
+        }
+    }
+
+    /// stops the ThreadPool after the queued jobs are done
+    ///
+    /// Waits at most `timeout` for the workers, workers still busy after that
+    /// are left running detached. Returns the number of those workers.
+    /// Workers which panicked are reported, not propagated.
+    ///
+    /// # Example
+    /// ```
+    /// use opentracker_exporter::threads::ThreadPool;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    ///
+    /// let pool = ThreadPool::new(2).unwrap();
+    /// let done = Arc::new(AtomicUsize::new(0));
+    /// for _ in 0..4 {
+    ///     let done = Arc::clone(&done);
+    ///     pool.execute(move || {
+    ///         std::thread::sleep(Duration::from_millis(10));
+    ///         done.fetch_add(1, Ordering::SeqCst);
+    ///     }).unwrap();
+    /// }
+    /// assert_eq!(pool.shutdown(Duration::from_secs(5)), 0);
+    /// assert_eq!(done.load(Ordering::SeqCst), 4);
+    /// ```
+    pub fn shutdown(&self, timeout: Duration) -> usize {
+        self.stop(Some(Instant::now() + timeout))
+    }
+
+    /// signals the workers to stop and joins them until `deadline`
+    fn stop(&self, deadline: Option<Instant>) -> usize {
+        if self.do_verbose {
+            println!("signaling workers to stop");
+        }
+        let mut workers = self.workers();
+        for _ in workers.iter().filter(|worker| worker.thread.is_some()) {
+            // fails if all workers are gone already, nothing left to stop then
+            let _ = self.sender.send(Message::Terminate);
+        }
+
+        let mut stuck = 0;
+        for worker in workers.iter_mut() {
+            let thread = match worker.thread.take() {
+                Some(thread) => thread,
+                None => continue,
+            };
+            if self.do_verbose {
+                println!("Stopping worker {}", worker.id);
+            }
+            if let Some(deadline) = deadline {
+                while !thread.is_finished() && Instant::now() < deadline {
+                    thread::sleep(Duration::from_millis(10));
+                }
+                if !thread.is_finished() {
+                    eprintln!("worker {} did not finish in time", worker.id);
+                    stuck += 1;
+                    continue;
+                }
+            }
+            if let Err(panic) = thread.join() {
+                eprintln!("worker {} panicked: {}", worker.id, panic_message(&panic));
+            }
+        }
+        stuck
+    }
+
+    /// takes a place in the queue, counts a rejection if it is full
+    fn reserve(&self) -> bool {
+        let limit = self.queue.unwrap_or(usize::MAX);
+        let reserved = self
+            .shared
+            .queued
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |queued| {
+                if queued < limit {
+                    Some(queued + 1)
+                } else {
+                    None
+                }
+            })
+            .is_ok();
+        if !reserved {
+            self.shared.rejected.fetch_add(1, Ordering::SeqCst);
+        }
+        reserved
+    }
+
+    /// executes `f` with `input` in a thread, returns `input` if the queue is full
+    ///
+    /// Lets the caller handle the rejected input, e.g. answer a connection.
+    pub fn execute_with<T, F>(&self, input: T, f: F) -> std::result::Result<(), T>
+    where
+        T: Send + 'static,
+        F: FnOnce(T) + Send + 'static,
+    {
+        self.maintain();
+        if !self.reserve() {
+            return Err(input);
+        }
+        // the receiver lives as long as the ThreadPool, so sending cannot fail
+        let _ = self.sender.send(Message::NewJob(Box::new(move || f(input))));
+        Ok(())
+    }
+
+    /// executes `f` in a thread and returns a handle to wait for its result
+    ///
+    /// # Errors
+    /// Errors with `PoolFull` if the queue is full.
+    ///
+    /// # Example
+    /// ```
+    /// use opentracker_exporter::threads::ThreadPool;
+    /// use std::time::Duration;
+    ///
+    /// let pool = ThreadPool::new(2).unwrap();
+    /// let answer = pool.execute_with_handle(|| 6 * 7).unwrap();
+    /// let stuck = pool.execute_with_handle(|| std::thread::sleep(Duration::from_millis(500))).unwrap();
+    /// let failed = pool.execute_with_handle(|| -> u8 { panic!("bad tracker") }).unwrap();
+    ///
+    /// assert_eq!(answer.join().unwrap(), 42);
+    /// assert_eq!(stuck.join_timeout(Duration::from_millis(10)).unwrap_err().kind().error_string(), "JobTimedOut");
+    /// assert_eq!(failed.join().unwrap_err().kind().error_string(), "JobPanicked(bad tracker)");
+    /// ```
+    pub fn execute_with_handle<T, F>(&self, f: F) -> Result<JobHandle<T>>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        self.execute(move || {
+            match panic::catch_unwind(AssertUnwindSafe(f)) {
+                Ok(result) => {
+                    let _ = sender.send(Ok(result));
+                }
+                Err(panic) => {
+                    let _ = sender.send(Err(panic_message(&panic)));
+                    // let the worker count the panic as well
+                    panic::resume_unwind(panic);
+                }
+            }
+        })?;
+        Ok(JobHandle { receiver })
     }
 
     /// execute send a function into a thread to be executed there
-    pub fn execute<'a, F>(&self, f: F) -> Result<()>
+    ///
+    /// # Errors
+    /// Errors with `PoolFull` if the queue is full.
+    pub fn execute<F>(&self, f: F) -> Result<()>
     where
         F: FnOnce() + Send + 'static,
     {
+        self.maintain();
+        if !self.reserve() {
+            return Err(Error::new(ErrorKind::PoolFull));
+        }
         let job = Box::new(f);
 
         /*match self.sender.send(Message::NewJob(job)) {
@@ -146,31 +608,62 @@ impl ThreadPool {
 impl fmt::Display for ThreadPool {
     /// standart formater for print! macro
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "ThreadPool with {} workers", self.size) // TODO: Colors
+        write!(f, "ThreadPool with {} workers", self.get_threads()) // TODO: Colors
     }
 }
 
 impl Drop for ThreadPool {
     /// signals each thread to stop befor droping itself
     fn drop(&mut self) {
-        if self.do_verbose {
-            println!("signaling workers to stop");
-        }
-        for _ in &mut self.workers {
-            self.sender.send(Message::Terminate).unwrap(); //FIXME: unwrap
+        self.stop(None);
+    }
+}
+
+/// JobHandle waits for the result of a job started with `execute_with_handle`
+pub struct JobHandle<T> {
+    receiver: mpsc::Receiver<std::result::Result<T, String>>,
+}
+
+impl<T> JobHandle<T> {
+    /// waits for the job to finish and returns its result
+    ///
+    /// # Errors
+    /// Errors with `JobPanicked` if the job panicked.
+    pub fn join(self) -> Result<T> {
+        match self.receiver.recv() {
+            Ok(result) => result.map_err(|message| Error::new(ErrorKind::JobPanicked(message))),
+            Err(_) => Err(Error::new(ErrorKind::JobPanicked(String::from("job was dropped")))),
         }
+    }
 
-        for worker in &mut self.workers {
-            if self.do_verbose {
-                println!("Stopping worker {}", worker.id);
-            }
-            if let Some(thread) = worker.thread.take() {
-                thread.join().unwrap(); //FIXME: unwrap
+    /// waits at most `timeout` for the job to finish and returns its result
+    ///
+    /// The job keeps running after a timeout, the handle can be joined again.
+    ///
+    /// # Errors
+    /// Errors with `JobTimedOut` if the job did not finish in time and `JobPanicked` if it panicked.
+    pub fn join_timeout(&self, timeout: Duration) -> Result<T> {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(result) => result.map_err(|message| Error::new(ErrorKind::JobPanicked(message))),
+            Err(mpsc::RecvTimeoutError::Timeout) => Err(Error::new(ErrorKind::JobTimedOut)),
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                Err(Error::new(ErrorKind::JobPanicked(String::from("job was dropped"))))
             }
         }
     }
 }
 
+/// returns the message a thread panicked with
+pub(crate) fn panic_message(panic: &Box<dyn Any + Send>) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        String::from("unknown panic")
+    }
+}
+
 /// implement function to convert sendError to Error
 impl std::convert::From<std::sync::mpsc::SendError<super::threads::Message>> for Error {
     fn from(err: std::sync::mpsc::SendError<super::threads::Message>) -> Self {
@@ -194,15 +687,21 @@ struct Worker {
 }
 
 impl Worker {
-    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Message>>>) -> Worker {
+    fn new(id: usize, shared: Arc<Shared>) -> Worker {
         let thread = thread::spawn(move || loop {
-            let message = receiver.lock().unwrap().recv().unwrap(); //FIXME: unwrap
+            // the lock is only held while waiting, poisoning does not affect the receiver
+            let message = shared.receiver.lock().unwrap_or_else(|err| err.into_inner()).recv();
 
             match message {
-                Message::NewJob(job) => {
-                    job.call_box();
+                Ok(Message::NewJob(job)) => {
+                    shared.queued.fetch_sub(1, Ordering::SeqCst);
+                    shared.run(job);
+                }
+                // the ThreadPool is gone
+                Err(_) => {
+                    break;
                 }
-                Message::Terminate => {
+                Ok(Message::Terminate) => {
                     break;
                 }
             }
@@ -221,7 +720,7 @@ trait FnBox {
 }
 
 /// Type for the Job to send to a worker
-type Job = Box<FnBox + Send + 'static>;
+type Job = Box<dyn FnBox + Send + 'static>;
 
 /// implementation of FnBox for the job type
 impl<F: FnOnce()> FnBox for F {
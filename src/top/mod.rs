@@ -0,0 +1,209 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write as _;
+use std::io::prelude::*;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::error::Error;
+use super::{scrape, Config, Everything};
+
+// tests as sub module
+#[cfg(test)] // only add when running tests
+mod test;
+
+/// number of samples kept for the sparklines
+const HISTORY: usize = 60;
+
+/// blocks used to draw sparklines, from low to high
+const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Series are the last values of one row
+struct Series {
+    label: String,
+    values: VecDeque<f64>,
+}
+
+/// Dashboard keeps the history of the polled stats
+pub struct Dashboard {
+    name: String,
+    url: String,
+    tracker_id: usize,
+    uptime: usize,
+    error: Option<Error>,
+    gauges: Vec<Series>,
+    rates: Vec<Series>,
+    /// counter values and time of the last successful poll
+    last: Option<(Duration, HashMap<String, u64>)>,
+}
+
+impl Dashboard {
+    /// creates an empty Dashboard for the tracker at `url` named `name`
+    pub fn new(url: &str, name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            url: url.to_string(),
+            tracker_id: 0,
+            uptime: 0,
+            error: None,
+            gauges: Vec::new(),
+            rates: Vec::new(),
+            last: None,
+        }
+    }
+
+    /// adds the result of a poll made `time` after the start
+    pub(crate) fn update(&mut self, time: Duration, data: Result<Everything, Error>) {
+        let data = match data {
+            Ok(data) => data,
+            Err(err) => {
+                self.error = Some(err);
+                return;
+            }
+        };
+        self.error = None;
+        self.tracker_id = data.tracker_id;
+        self.uptime = data.uptime;
+
+        // uptime is part of the header
+        for (path, value) in data.gauges().into_iter().filter(|(path, _)| path != "uptime") {
+            push(&mut self.gauges, &path, value);
+        }
+
+        let counters: HashMap<String, u64> = data.counters().into_iter().collect();
+        if let Some((last_time, last)) = &self.last {
+            let elapsed = time.checked_sub(*last_time).unwrap_or_default().as_secs_f64();
+            for (path, value) in data.counters() {
+                // a counter going backwards means a restart, there is no rate until the next poll
+                let rate = match last.get(&path) {
+                    Some(last) if *last <= value && elapsed > 0.0 => (value - last) as f64 / elapsed,
+                    Some(_) => continue,
+                    None => 0.0,
+                };
+                push(&mut self.rates, &path, rate);
+            }
+        }
+        self.last = Some((time, counters));
+    }
+
+    /// renders the dashboard as text
+    pub fn render(&self) -> String {
+        let mut ret = String::new();
+        let _ = writeln!(
+            ret,
+            "opentracker top - {} ({})  tracker {}  up {}",
+            self.name,
+            self.url,
+            self.tracker_id,
+            uptime(self.uptime)
+        );
+        match &self.error {
+            Some(err) => {
+                let _ = writeln!(ret, "error: {}", err.kind().error_string());
+            }
+            None => ret.push('\n'),
+        }
+
+        let width = self
+            .gauges
+            .iter()
+            .chain(self.rates.iter())
+            .map(|series| series.label.len())
+            .max()
+            .unwrap_or(0);
+
+        let _ = writeln!(ret, "{:width$}  {:>12}", "", "current", width = width);
+        for series in &self.gauges {
+            row(&mut ret, series, width);
+        }
+        ret.push('\n');
+        let _ = writeln!(ret, "{:width$}  {:>12}", "", "per second", width = width);
+        if self.rates.is_empty() {
+            ret.push_str("waiting for the second poll\n");
+        }
+        for series in &self.rates {
+            row(&mut ret, series, width);
+        }
+        ret
+    }
+}
+
+/// appends `value` to the series with `label`, creating it if needed
+fn push(series: &mut Vec<Series>, label: &str, value: f64) {
+    let index = match series.iter().position(|series| series.label == label) {
+        Some(index) => index,
+        None => {
+            series.push(Series {
+                label: label.to_string(),
+                values: VecDeque::with_capacity(HISTORY),
+            });
+            series.len() - 1
+        }
+    };
+    let values = &mut series[index].values;
+    if values.len() == HISTORY {
+        values.pop_front();
+    }
+    values.push_back(value);
+}
+
+/// writes one row with the latest value and the sparkline
+fn row(ret: &mut String, series: &Series, width: usize) {
+    let value = series.values.back().copied().unwrap_or(0.0);
+    let values: Vec<f64> = series.values.iter().copied().collect();
+    let _ = writeln!(
+        ret,
+        "{:width$}  {:>12}  {}",
+        series.label,
+        format!("{:.1}", value).trim_end_matches(".0"),
+        sparkline(&values),
+        width = width
+    );
+}
+
+/// returns a sparkline of `values` scaled from zero to the maximum
+pub(crate) fn sparkline(values: &[f64]) -> String {
+    let max = values.iter().copied().fold(0.0, f64::max);
+    values
+        .iter()
+        .map(|value| {
+            if max <= 0.0 || !value.is_finite() {
+                return BLOCKS[0];
+            }
+            let index = (value.max(0.0) / max * (BLOCKS.len() - 1) as f64).round() as usize;
+            BLOCKS[index.min(BLOCKS.len() - 1)]
+        })
+        .collect()
+}
+
+/// formats seconds as `1d 2h 3m 4s`
+pub(crate) fn uptime(seconds: usize) -> String {
+    let (days, hours) = (seconds / 86400, seconds / 3600 % 24);
+    let (minutes, seconds) = (seconds / 60 % 60, seconds % 60);
+    if days > 0 {
+        format!("{}d {}h {}m {}s", days, hours, minutes, seconds)
+    } else if hours > 0 {
+        format!("{}h {}m {}s", hours, minutes, seconds)
+    } else {
+        format!("{}m {}s", minutes, seconds)
+    }
+}
+
+/// polls the tracker every `interval` and redraws the dashboard until killed
+pub fn run(conf: &Config, interval: Duration) -> ! {
+    let mut dashboard = Dashboard::new(&conf.url, &conf.name);
+    let upstream = conf.upstream();
+    let start = Instant::now();
+    let mut stdout = std::io::stdout();
+    loop {
+        let poll = Instant::now();
+        dashboard.update(poll - start, scrape(&upstream));
+
+        // move home and clear the screen before drawing
+        let _ = write!(stdout, "\x1b[H\x1b[2J{}", dashboard.render());
+        let _ = stdout.flush();
+
+        if let Some(wait) = interval.checked_sub(poll.elapsed()) {
+            thread::sleep(wait);
+        }
+    }
+}
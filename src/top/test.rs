@@ -0,0 +1,79 @@
+//! test file to test the rates and drawing of the dashboard
+
+use super::{sparkline, uptime, Dashboard, HISTORY};
+use crate::error::{Error, ErrorKind};
+use crate::Everything;
+use std::time::Duration;
+
+fn data(peers: usize, tcp_accept: usize) -> Everything {
+    let mut data = Everything::new();
+    data.tracker_id = 1337;
+    data.uptime = 3725;
+    data.peers = peers;
+    data.connections.tcp_accept = tcp_accept;
+    data
+}
+
+fn rate(dashboard: &Dashboard, label: &str) -> Vec<f64> {
+    let series = dashboard.rates.iter().find(|series| series.label == label).unwrap();
+    series.values.iter().copied().collect()
+}
+
+#[test]
+fn sparklines() {
+    assert_eq!(sparkline(&[]), "");
+    assert_eq!(sparkline(&[0.0, 0.0]), "▁▁");
+    assert_eq!(sparkline(&[0.0, 3.5, 7.0]), "▁▅█");
+}
+
+#[test]
+fn uptimes() {
+    assert_eq!(uptime(59), "0m 59s");
+    assert_eq!(uptime(3725), "1h 2m 5s");
+    assert_eq!(uptime(90061), "1d 1h 1m 1s");
+}
+
+#[test]
+fn rates() {
+    let mut dashboard = Dashboard::new("localhost", "test");
+    dashboard.update(Duration::from_secs(0), Ok(data(10, 100)));
+    assert!(dashboard.rates.is_empty());
+
+    dashboard.update(Duration::from_secs(2), Ok(data(12, 150)));
+    assert_eq!(rate(&dashboard, "connections.tcp.accept"), vec![25.0]);
+
+    // restart, no rate for this poll
+    dashboard.update(Duration::from_secs(3), Ok(data(12, 10)));
+    assert_eq!(rate(&dashboard, "connections.tcp.accept"), vec![25.0]);
+
+    dashboard.update(Duration::from_secs(4), Ok(data(12, 20)));
+    assert_eq!(rate(&dashboard, "connections.tcp.accept"), vec![25.0, 10.0]);
+}
+
+#[test]
+fn history_is_bounded() {
+    let mut dashboard = Dashboard::new("localhost", "test");
+    for i in 0..HISTORY + 10 {
+        dashboard.update(Duration::from_secs(i as u64), Ok(data(i, i)));
+    }
+    let peers = dashboard.gauges.iter().find(|series| series.label == "peers").unwrap();
+    assert_eq!(peers.values.len(), HISTORY);
+    assert_eq!(peers.values.back(), Some(&((HISTORY + 9) as f64)));
+}
+
+#[test]
+fn render() {
+    let mut dashboard = Dashboard::new("localhost", "test");
+    dashboard.update(Duration::from_secs(0), Ok(data(10, 100)));
+    let screen = dashboard.render();
+    assert!(screen.starts_with("opentracker top - test (localhost)  tracker 1337  up 1h 2m 5s\n"));
+    assert!(screen.contains("waiting for the second poll"));
+
+    dashboard.update(Duration::from_secs(1), Ok(data(20, 150)));
+    dashboard.update(Duration::from_secs(2), Err(Error::new(ErrorKind::IoConnectionRefused)));
+    let screen = dashboard.render();
+    assert!(screen.contains("\nerror: IoConnectionRefused\n"));
+    // labels are padded to the longest one, connections.udp.missmatch
+    assert!(screen.contains(&format!("\n{:25}  {:>12}  ▅█\n", "peers", 20)));
+    assert!(screen.contains(&format!("\n{:25}  {:>12}  █\n", "connections.tcp.accept", 50)));
+}
@@ -0,0 +1,204 @@
+use std::io::prelude::*;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, UNIX_EPOCH};
+
+use super::collector::{Scrape, Sink};
+use super::error::{Error, ErrorKind, Result};
+use super::{json, scrape, Config};
+
+// tests as sub module
+#[cfg(test)] // only add when running tests
+mod test;
+
+/// timeout for connecting, reading and writing to the trapper
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// maximum number of items kept while the server is unreachable
+const MAX_QUEUE: usize = 100_000;
+
+/// Server describes the zabbix server or proxy to send to
+#[derive(Clone, Debug)]
+pub struct Server {
+    /// address of the trapper, the port defaults to 10051
+    pub addr: String,
+
+    /// name of the host in zabbix the items belong to
+    pub host: String,
+}
+
+impl Server {
+    /// creates a new Server for `addr` sending items of `host`
+    pub fn new(addr: &str, host: &str) -> Self {
+        let addr = if addr.contains(':') && !addr.ends_with(']') {
+            addr.to_string()
+        } else {
+            format!("{}:10051", addr)
+        };
+        Self {
+            addr,
+            host: host.to_string(),
+        }
+    }
+}
+
+/// Zabbix is a sink speaking the zabbix sender protocol
+///
+/// Item keys look like `opentracker.peers[tracker]` and
+/// `opentracker.http_codes[tracker,"404 Not Found"]`.
+pub struct Zabbix {
+    server: Server,
+    prefix: String,
+    /// json objects of the queued items
+    items: Vec<String>,
+}
+
+impl Zabbix {
+    /// creates a new Zabbix for items named with `prefix`
+    pub fn new(server: Server, prefix: &str) -> Self {
+        Self {
+            server,
+            prefix: prefix.to_string(),
+            items: Vec::new(),
+        }
+    }
+
+    /// queues one item
+    fn item(&mut self, key: String, value: String, clock: u64) {
+        self.items.push(format!(
+            r#"{{"host":{},"key":{},"value":{},"clock":{}}}"#,
+            json::string(&self.server.host),
+            json::string(&key),
+            json::string(&value),
+            clock
+        ));
+    }
+}
+
+impl Sink for Zabbix {
+    fn name(&self) -> String {
+        format!("zabbix {}", self.server.addr)
+    }
+
+    fn collect(&mut self, scrape: &Scrape) {
+        let clock = match scrape.time.duration_since(UNIX_EPOCH) {
+            Ok(time) => time.as_secs(),
+            Err(_) => 0,
+        };
+        let tracker = key_param(&scrape.name);
+
+        for (path, value) in scrape.data.gauges() {
+            self.item(format!("{}.{}[{}]", self.prefix, path, tracker), value.to_string(), clock);
+        }
+        for (path, value) in scrape.data.counters() {
+            // http codes are sent with the code as parameter for low level discovery
+            if !path.starts_with("http_codes.") {
+                self.item(format!("{}.{}[{}]", self.prefix, path, tracker), value.to_string(), clock);
+            }
+        }
+        for (code, count) in &scrape.data.http_error {
+            self.item(
+                format!("{}.http_codes[{},{}]", self.prefix, tracker, key_param(code)),
+                count.to_string(),
+                clock,
+            );
+        }
+
+        if self.items.len() > MAX_QUEUE {
+            let dropped = self.items.len() - MAX_QUEUE;
+            self.items.drain(..dropped);
+            eprintln!("zabbix queue full, dropped {} items", dropped);
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.items.is_empty() {
+            return Ok(());
+        }
+
+        let request = format!(r#"{{"request":"sender data","data":[{}]}}"#, self.items.join(","));
+        let response = send(&self.server.addr, &request)?;
+
+        // {"response":"success","info":"processed: 1; failed: 0; total: 1; seconds spent: 0.000055"}
+        if !response.replace(' ', "").contains(r#""response":"success""#) {
+            return Err(Error::new(ErrorKind::Other(format!("zabbix answered {}", response))));
+        }
+        if !response.contains("failed: 0;") {
+            eprintln!("zabbix did not accept all items: {}", response);
+        }
+        self.items.clear();
+        Ok(())
+    }
+}
+
+/// sends `data` with the zabbix protocol header and returns the answer
+fn send(addr: &str, data: &str) -> Result<String> {
+    let addr = match addr.to_socket_addrs()?.next() {
+        Some(addr) => addr,
+        None => return Err(Error::new(ErrorKind::IoAddrNotAvailable)),
+    };
+    let mut stream = TcpStream::connect_timeout(&addr, TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    let mut packet = Vec::with_capacity(data.len() + 13);
+    packet.extend_from_slice(b"ZBXD\x01");
+    packet.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    packet.extend_from_slice(data.as_bytes());
+    stream.write_all(&packet)?;
+    stream.flush()?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response)?;
+    if response.len() < 13 || &response[..4] != b"ZBXD" {
+        return Err(Error::new(ErrorKind::NotParsable(String::from("invalid zabbix response header"))));
+    }
+    Ok(String::from_utf8_lossy(&response[13..]).to_string())
+}
+
+/// quotes a parameter of an item key if needed
+fn key_param(param: &str) -> String {
+    if param.contains([',', ']', '"', ' ']) || param.starts_with('[') {
+        format!("\"{}\"", param.replace('"', "\\\""))
+    } else {
+        param.to_string()
+    }
+}
+
+/// what to print low level discovery data for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Discovery {
+    /// all configured trackers, with the macros `{#TRACKER}` and `{#URL}`
+    Trackers,
+
+    /// http error codes reported by the trackers, with `{#TRACKER}` and `{#CODE}`
+    HttpCodes,
+}
+
+/// returns the low level discovery json for the configured trackers
+///
+/// Http codes are read from a scrape of every tracker.
+pub fn discovery(conf: &Config, what: Discovery) -> Result<String> {
+    let mut entries = Vec::new();
+    match what {
+        Discovery::Trackers => {
+            entries.push(format!(
+                r#"{{"{{#TRACKER}}":{},"{{#URL}}":{}}}"#,
+                json::string(&conf.name),
+                json::string(&conf.url)
+            ));
+        }
+        Discovery::HttpCodes => {
+            let data = scrape(&conf.upstream())?;
+            let mut codes: Vec<&String> = data.http_error.keys().collect();
+            codes.sort();
+            for code in codes {
+                entries.push(format!(
+                    r#"{{"{{#TRACKER}}":{},"{{#CODE}}":{}}}"#,
+                    json::string(&conf.name),
+                    json::string(code)
+                ));
+            }
+        }
+    }
+    Ok(format!(r#"{{"data":[{}]}}"#, entries.join(",")))
+}
@@ -0,0 +1,104 @@
+//! test file for the zabbix sender
+
+use super::{discovery, key_param, Discovery, Server, Zabbix};
+use crate::collector::{test, Scrape, Sink};
+use crate::Config;
+use std::io::prelude::*;
+use std::net::TcpListener;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+fn scrape() -> Scrape {
+    test::scrape("tracker", 100, |data| {
+        data.peers = 300;
+        data.http_error.insert(String::from("404 Not Found"), 2);
+    })
+}
+
+/// starts a trapper answering one request with `response`
+fn trapper(response: &'static str) -> (String, mpsc::Receiver<Vec<u8>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut header = [0; 13];
+        stream.read_exact(&mut header).unwrap();
+        let mut length = [0; 8];
+        length.copy_from_slice(&header[5..]);
+        let mut data = vec![0; u64::from_le_bytes(length) as usize];
+        stream.read_exact(&mut data).unwrap();
+
+        let mut packet = b"ZBXD\x01".to_vec();
+        packet.extend_from_slice(&(response.len() as u64).to_le_bytes());
+        packet.extend_from_slice(response.as_bytes());
+        stream.write_all(&packet).unwrap();
+
+        let mut request = header.to_vec();
+        request.extend_from_slice(&data);
+        sender.send(request).unwrap();
+    });
+    (addr, receiver)
+}
+
+#[test]
+fn default_port() {
+    assert_eq!(Server::new("zabbix", "host").addr, String::from("zabbix:10051"));
+    assert_eq!(Server::new("zabbix:10052", "host").addr, String::from("zabbix:10052"));
+}
+
+#[test]
+fn items() {
+    let mut sink = Zabbix::new(Server::new("zabbix", "host"), "ot");
+    sink.collect(&scrape());
+    assert!(sink
+        .items
+        .contains(&String::from(r#"{"host":"host","key":"ot.peers[tracker]","value":"300","clock":100}"#)));
+    assert!(sink.items.contains(&String::from(
+        r#"{"host":"host","key":"ot.http_codes[tracker,\"404 Not Found\"]","value":"2","clock":100}"#
+    )));
+    assert!(!sink.items.iter().any(|item| item.contains("http_codes.")));
+}
+
+#[test]
+fn flush() {
+    let (addr, request) = trapper(
+        r#"{"response":"success","info":"processed: 20; failed: 0; total: 20; seconds spent: 0.000055"}"#,
+    );
+    let mut sink = Zabbix::new(Server::new(&addr, "host"), "ot");
+    sink.collect(&scrape());
+    sink.flush().unwrap();
+    assert!(sink.items.is_empty());
+
+    let request = request.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(&request[..5], b"ZBXD\x01");
+    assert!(String::from_utf8_lossy(&request[13..]).starts_with(r#"{"request":"sender data","data":[{"host":"host""#));
+}
+
+#[test]
+fn flush_failed() {
+    let (addr, _request) = trapper(r#"{"response":"failed","info":"invalid request"}"#);
+    let mut sink = Zabbix::new(Server::new(&addr, "host"), "ot");
+    sink.collect(&scrape());
+    assert!(sink.flush().is_err());
+    assert!(!sink.items.is_empty());
+}
+
+#[test]
+fn quoting() {
+    assert_eq!(key_param("tracker"), String::from("tracker"));
+    assert_eq!(key_param("404 Not Found"), String::from("\"404 Not Found\""));
+    assert_eq!(key_param("a\"b,c"), String::from("\"a\\\"b,c\""));
+}
+
+#[test]
+fn discovery_trackers() {
+    let mut conf = Config::new();
+    conf.url = String::from("localhost:6969");
+    conf.name = String::from("tracker");
+    assert_eq!(
+        discovery(&conf, Discovery::Trackers).unwrap(),
+        String::from(r#"{"data":[{"{#TRACKER}":"tracker","{#URL}":"localhost:6969"}]}"#)
+    );
+}